@@ -0,0 +1,68 @@
+//! Benchmarks for the key lookup and access recording done on every redirect.
+//! 
+//! Run with `cargo bench`.
+#![feature(test)]
+extern crate test;
+
+use concurrent_queue::ConcurrentQueue;
+use landmower::{links::Links, LinkAccessEvent};
+use test::{black_box, Bencher};
+
+const LINK_COUNT: usize = 10_000;
+
+fn setup(case_insensitive: bool) -> (Links, Vec<String>) {
+    let mut links = Links::new();
+    links.set_case_insensitive(case_insensitive);
+    let keys = (0..LINK_COUNT)
+        .map(|i| {
+            let key = format!("Key{i}");
+            links.add_named(key.clone(), format!("https://example.com/{i}")).unwrap();
+            key
+        })
+        .collect();
+    (links, keys)
+}
+
+fn lookup_all(b: &mut Bencher, links: &Links, keys: &[String]) {
+    b.iter(|| {
+        for key in keys {
+            black_box(links.get(black_box(key)));
+        }
+    });
+}
+
+#[bench]
+fn lookup_case_sensitive(b: &mut Bencher) {
+    let (links, keys) = setup(false);
+    lookup_all(b, &links, &keys);
+}
+
+#[bench]
+fn lookup_case_insensitive_exact_case(b: &mut Bencher) {
+    let (links, keys) = setup(true);
+    lookup_all(b, &links, &keys);
+}
+
+#[bench]
+fn lookup_case_insensitive_other_case(b: &mut Bencher) {
+    let (links, keys) = setup(true);
+    let keys: Vec<_> = keys.iter().map(|k| k.to_uppercase()).collect();
+    lookup_all(b, &links, &keys);
+}
+
+/// Recording the access of each redirect, which `disable_analytics` skips.
+#[bench]
+fn record_access(b: &mut Bencher) {
+    let (_, keys) = setup(false);
+    let queue = ConcurrentQueue::unbounded();
+    b.iter(|| {
+        for key in &keys {
+            queue.push(LinkAccessEvent {
+                key: key.clone(),
+                timestamp: std::time::SystemTime::now(),
+                visitor: None,
+            }).unwrap();
+        }
+        while queue.pop().is_ok() {}
+    });
+}
@@ -1,16 +1,78 @@
+use std::{path::Path, process::Command};
+
+/// Directory the web UI is built into and embedded from.
+const STATIC_DIR: &str = "static";
+
+/// Page served when the crate is built without a web UI available.
+const PLACEHOLDER_INDEX: &str = "<!doctype html>
+<html lang=\"en\">
+  <head><meta charset=\"UTF-8\" /><title>landmower</title></head>
+  <body><p>The web UI was not built. Install bun and rebuild landmower to enable it.</p></body>
+</html>
+";
+
 fn main() {
-    println!("cargo:rerun-if-changed=/webui");
-
-    std::process::Command::new("bun")
-        .arg("install")
-        .current_dir("webui")
-        .status()
-        .expect("Failed to run bun install");
-
-    std::process::Command::new("bun")
-        .arg("run")
-        .arg("build")
-        .current_dir("webui")
-        .status()
-        .expect("Failed to run bun run build");
-}
\ No newline at end of file
+    println!("cargo:rustc-check-cfg=cfg(webui_placeholder)");
+    for path in ["webui/src", "webui/index.html", "webui/package.json", "webui/.env.production"] {
+        println!("cargo:rerun-if-changed={path}");
+    }
+
+    // The web UI is only embedded when the `webui` feature is enabled.
+    if std::env::var_os("CARGO_FEATURE_WEBUI").is_none() {
+        return;
+    }
+
+    if !Path::new("webui").is_dir() {
+        println!("cargo:warning=`webui` directory not found, using prebuilt `{STATIC_DIR}` directory");
+    } else if !bun_available() {
+        println!("cargo:warning=`bun` not found on PATH, using prebuilt `{STATIC_DIR}` directory");
+    } else {
+        let _ = run_in_webui("bun", &["install"])
+            && run_in_webui("bun", &["run", "build"]);
+    }
+
+    ensure_static_dir();
+}
+
+fn bun_available() -> bool {
+    Command::new("bun")
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+/// Run a command in the web UI directory.
+///
+/// Failures are reported as build warnings rather than aborting the build.
+fn run_in_webui(program: &str, args: &[&str]) -> bool {
+    let command = format!("{program} {}", args.join(" "));
+    match Command::new(program).args(args).current_dir("webui").status() {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            println!("cargo:warning=`{command}` failed ({status}), the web UI may be outdated");
+            false
+        },
+        Err(e) => {
+            println!("cargo:warning=Could not run `{command}`: {e}");
+            false
+        }
+    }
+}
+
+/// Make sure there is something to serve, so the crate still compiles without a built UI.
+///
+/// Build scripts may only write to `OUT_DIR`, so the placeholder page is written there and
+/// the `webui_placeholder` cfg tells the crate to serve it instead of the embedded directory.
+fn ensure_static_dir() {
+    let index = Path::new(STATIC_DIR).join("index.html");
+    if index.exists() {
+        return;
+    }
+    println!("cargo:warning=No prebuilt web UI found in `{STATIC_DIR}`, serving a placeholder page");
+    // Pick up a UI built or copied there later.
+    println!("cargo:rerun-if-changed={}", index.display());
+    println!("cargo:rustc-cfg=webui_placeholder");
+    let out_dir = std::env::var_os("OUT_DIR").expect("OUT_DIR is set by cargo");
+    std::fs::write(Path::new(&out_dir).join("placeholder.html"), PLACEHOLDER_INDEX)
+        .expect("Failed to write placeholder web UI");
+}
@@ -0,0 +1,183 @@
+//! Access log of redirects in the formats of common web servers, for log analysis tools.
+//!
+//! Unlike the [`crate::event_log`], which records clicks for the statistics of this server,
+//! every redirect request is logged with its response status, including unknown keys.
+
+use std::{fs::OpenOptions, io::{self, BufWriter, Write}, path::Path, sync::mpsc};
+
+use chrono::{DateTime, Utc};
+
+/// Format of access log lines.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AccessLogFormat {
+    /// Don't write an access log.
+    #[default]
+    Off,
+    /// NCSA Common Log Format.
+    Common,
+    /// NCSA Combined Log Format, i.e. the common format with the referrer and user agent.
+    /// The requested key is appended as an extra field.
+    Combined,
+}
+
+impl std::str::FromStr for AccessLogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" | "" => Ok(Self::Off),
+            "common" => Ok(Self::Common),
+            "combined" => Ok(Self::Combined),
+            _ => Err(format!("Invalid access log format: '{s}'"))
+        }
+    }
+}
+
+/// A request to log.
+pub struct Access<'a> {
+    /// Address of the client, if known.
+    pub remote: Option<&'a str>,
+    pub time: DateTime<Utc>,
+    pub method: &'a str,
+    /// Path and query of the request.
+    pub target: &'a str,
+    /// Protocol version, e.g. `HTTP/1.1`.
+    pub version: &'a str,
+    pub status: u16,
+    pub referrer: Option<&'a str>,
+    pub user_agent: Option<&'a str>,
+    /// The requested key, if the request was for one.
+    pub key: Option<&'a str>,
+}
+
+impl AccessLogFormat {
+    /// Format the access as a single line without the line break, or `None` if disabled.
+    pub fn format(&self, access: &Access) -> Option<String> {
+        let common = format!(
+            "{} - - [{}] \"{} {} {}\" {} -",
+            access.remote.unwrap_or("-"),
+            access.time.format("%d/%b/%Y:%H:%M:%S %z"),
+            escape(access.method), escape(access.target), escape(access.version),
+            access.status,
+        );
+        match self {
+            Self::Off => None,
+            Self::Common => Some(common),
+            Self::Combined => Some(format!(
+                "{common} {} {} {}",
+                quoted(access.referrer), quoted(access.user_agent), quoted(access.key),
+            )),
+        }
+    }
+}
+
+/// Quote a field of the combined format, or `-` if missing.
+fn quoted(field: Option<&str>) -> String {
+    field.map_or("-".to_string(), |field| format!("\"{}\"", escape(field)))
+}
+
+/// Escape quotes, backslashes and control characters, so fields can't break up the line.
+fn escape(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            },
+            c if c.is_control() => escaped.extend(c.escape_default()),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Destination of access log lines.
+/// 
+/// Lines are written on a dedicated thread, so redirects never wait for the disk. It buffers
+/// lines while more are coming in and flushes whenever it has caught up.
+pub struct AccessLog {
+    format: AccessLogFormat,
+    lines: mpsc::Sender<String>,
+}
+
+impl AccessLog {
+    /// Log in the given format to the given file, appending to it, or to stdout if unset.
+    ///
+    /// Returns `None` if the format is [`AccessLogFormat::Off`].
+    pub fn open(format: AccessLogFormat, path: Option<&Path>) -> io::Result<Option<Self>> {
+        if format == AccessLogFormat::Off {
+            return Ok(None);
+        }
+        let out: Box<dyn Write + Send> = match path {
+            Some(path) => {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                Box::new(OpenOptions::new().create(true).append(true).open(path)?)
+            },
+            None => Box::new(io::stdout()),
+        };
+        let (lines, receiver) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("access-log".to_string())
+            .spawn(move || write_lines(receiver, BufWriter::new(out)))?;
+        Ok(Some(Self { format, lines }))
+    }
+
+    /// Queue the access to be written.
+    pub fn write(&self, access: &Access) -> io::Result<()> {
+        let Some(line) = self.format.format(access) else {
+            return Ok(());
+        };
+        self.lines.send(line)
+            .map_err(|_| io::Error::other("Access log writer has stopped"))
+    }
+}
+
+/// Write lines until every [`AccessLog`] sending them is dropped.
+fn write_lines(receiver: mpsc::Receiver<String>, mut out: impl Write) {
+    while let Ok(line) = receiver.recv() {
+        let mut result = writeln!(out, "{line}");
+        for line in receiver.try_iter() {
+            result = result.and_then(|_| writeln!(out, "{line}"));
+        }
+        if let Err(e) = result.and_then(|_| out.flush()) {
+            tracing::error!("Failed to write access log: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Access<'static> {
+        Access {
+            remote: Some("203.0.113.7"),
+            time: DateTime::parse_from_rfc3339("2024-03-05T14:07:09Z").unwrap().into(),
+            method: "GET",
+            target: "/go/docs?ref=mail",
+            version: "HTTP/1.1",
+            status: 303,
+            referrer: Some("https://mail.example.com/"),
+            user_agent: Some("Mozilla/5.0 \"quoted\""),
+            key: Some("docs"),
+        }
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!(
+            AccessLogFormat::Common.format(&sample()).unwrap(),
+            r#"203.0.113.7 - - [05/Mar/2024:14:07:09 +0000] "GET /go/docs?ref=mail HTTP/1.1" 303 -"#
+        );
+        assert_eq!(
+            AccessLogFormat::Combined.format(&sample()).unwrap(),
+            r#"203.0.113.7 - - [05/Mar/2024:14:07:09 +0000] "GET /go/docs?ref=mail HTTP/1.1" 303 - "https://mail.example.com/" "Mozilla/5.0 \"quoted\"" "docs""#
+        );
+        let anonymous = Access { remote: None, referrer: None, user_agent: None, key: None, ..sample() };
+        assert!(AccessLogFormat::Combined.format(&anonymous).unwrap().ends_with(" 303 - - - -"));
+        assert_eq!(AccessLogFormat::Off.format(&sample()), None);
+    }
+}
@@ -1,10 +1,42 @@
-use axum::{extract::State, http::{StatusCode, Uri}, routing, Json, Router};
-use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
-use crate::{links::Entry, AppState};
+use axum::{
+    body::{Body, Bytes},
+    extract::{rejection::JsonRejection, DefaultBodyLimit, FromRequest, FromRequestParts, Query, Request, State}, 
+    http::{header, request::Parts, StatusCode, Uri}, 
+    response::{IntoResponse, Response}, 
+    routing, 
+    Json, 
+    Router
+};
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt as _;
+use tokio::sync::broadcast::error::RecvError;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    auth::{Authorized, Caller, Writable},
+    event_log::{self, DayCount, KeyFilter, LogFilter, LogOrder, LoggedAccess}, 
+    links::{AddResult, Entry, EntryMetadata, Links, OnExpire, ResolveError, MIN_KEY_LENGTH}, 
+    probe::VerifyReachable,
+    webhook,
+    AppState, 
+    Config,
+    SelfLinks,
+    LinkAccessEvent
+};
 
 pub type HttpError = (StatusCode, String);
 
+/// Value of [`EntryMetadata::source`] for links added through `add_link`.
+const SOURCE_API: &str = "api";
+/// Value of [`EntryMetadata::source`] for links added through `import_links`, unless the
+/// imported metadata has a source.
+const SOURCE_IMPORT: &str = "import";
+
+/// Keys that can't be used for links because they clash with routes under `/links`.
+pub(crate) const RESERVED_KEYS: &[&str] = &["batch-get", "counts", "import", "merge", "meta", "tags"];
+
 pub mod jsend {
     use std::ops::FromResidual;
 
@@ -47,15 +79,72 @@ pub mod jsend {
                 _ => None
             }
         }
+
+        /// The JSend status of this response.
+        pub fn status(&self) -> &'static str {
+            match self {
+                Jsend::Success(_) => "success",
+                Jsend::Fail(_) => "fail",
+                Jsend::Error(_) => "error",
+            }
+        }
+
+        /// Record the status of this response as the `outcome` field of the current span.
+        pub fn record_outcome(self) -> Self {
+            tracing::Span::current().record("outcome", self.status());
+            self
+        }
+
+        /// Respond with the given HTTP status instead of the default, see [`WithStatus`].
+        pub fn with_status(self, status: StatusCode) -> WithStatus<T, F> {
+            WithStatus(status, self)
+        }
+
+        /// The HTTP status of this response unless another one is given.
+        fn default_status(&self) -> StatusCode {
+            match self {
+                Jsend::Success(_) | Jsend::Fail(_) => StatusCode::OK,
+                Jsend::Error(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            }
+        }
     }
 
     impl<T: Serialize, F: Serialize> IntoResponse for Jsend<T, F> {
         fn into_response(self) -> axum::response::Response {
-            match &self {
-                Jsend::Success(_)   => (StatusCode::OK, Json(self)).into_response(),
-                Jsend::Fail(_)      => (StatusCode::OK, Json(self)).into_response(),
-                Jsend::Error(_)     => (StatusCode::INTERNAL_SERVER_ERROR, Json(self)).into_response(),                
-            }
+            (self.default_status(), Json(self)).into_response()
+        }
+    }
+
+    /// A JSend response with an explicit HTTP status instead of the default for its kind,
+    /// which is `200` for successes and failures and `500` for errors.
+    /// 
+    /// Used when the request can't be served through the client's fault, e.g. `400` for a
+    /// request that can't be applied, `403` for a link the caller may not access or `404` for
+    /// a missing link. Internal errors can still be returned with `?`, responding with `500`.
+    pub struct WithStatus<T, F>(pub StatusCode, pub Jsend<T, F>);
+
+    impl<T, F> WithStatus<T, F> {
+        /// Record the status of the response as the `outcome` field of the current span.
+        pub fn record_outcome(self) -> Self {
+            Self(self.0, self.1.record_outcome())
+        }
+    }
+
+    impl<T: Serialize, F: Serialize> IntoResponse for WithStatus<T, F> {
+        fn into_response(self) -> axum::response::Response {
+            (self.0, Json(self.1)).into_response()
+        }
+    }
+
+    impl<T, F> From<Jsend<T, F>> for WithStatus<T, F> {
+        fn from(res: Jsend<T, F>) -> Self {
+            Self(res.default_status(), res)
+        }
+    }
+
+    impl<T, F> FromResidual<Result<std::convert::Infallible, String>> for WithStatus<T, F> {
+        fn from_residual(residual: Result<std::convert::Infallible, String>) -> Self {
+            Jsend::from_residual(residual).into()
         }
     }
 
@@ -90,98 +179,521 @@ trait Validator {
     async fn validate(&self, state: &AppState) -> Option<Self::Fail>;
 }
 
-pub fn router() -> Router<AppState> {
+/// JSON request body. Unlike [`Json`], bodies that can't be parsed are rejected with a
+/// JSend error, so clients get the same response shape as for any other error.
+struct JsonBody<T>(T);
+
+#[axum::async_trait]
+impl<T: DeserializeOwned, S: Send + Sync> FromRequest<S> for JsonBody<T> {
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match Json::<T>::from_request(req, state).await {
+            Ok(Json(value)) => Ok(Self(value)),
+            Err(rejection) => Err(json_rejection(rejection)),
+        }
+    }
+}
+
+/// Optional JSON request body, for endpoints where every field has a default. An empty body
+/// stands for the default value, but bodies that can't be parsed are still rejected like
+/// [`JsonBody`] does.
+struct OptionalJsonBody<T>(T);
+
+#[axum::async_trait]
+impl<T: DeserializeOwned + Default, S: Send + Sync> FromRequest<S> for OptionalJsonBody<T> {
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let (parts, body) = req.into_parts();
+        let bytes = match Bytes::from_request(Request::from_parts(parts.clone(), body), state).await {
+            Ok(bytes) => bytes,
+            Err(rejection) => {
+                return Err(Jsend::<(), ()>::Error(rejection.body_text()).with_status(rejection.status()).into_response());
+            }
+        };
+        if bytes.is_empty() {
+            return Ok(Self(T::default()));
+        }
+        let JsonBody(value) = JsonBody::from_request(Request::from_parts(parts, Body::from(bytes)), state).await?;
+        Ok(Self(value))
+    }
+}
+
+/// Respond to a body that isn't valid JSON for the endpoint, keeping the status chosen by
+/// axum, e.g. `400` for syntax errors or `415` for a missing content type.
+fn json_rejection(rejection: JsonRejection) -> Response {
+    let message = format!("Invalid JSON body: {}", rejection.body_text());
+    Jsend::<(), ()>::Error(message).with_status(rejection.status()).into_response()
+}
+
+pub fn router(config: &Config) -> Router<AppState> {
     Router::new()
         .route(
             "/links", 
             routing::get(get_links)
                     .post(add_link)
         )
+        .route(
+            "/links/counts",
+            routing::get(get_link_counts)
+        )
+        .route(
+            "/links/meta",
+            routing::get(get_links_meta)
+        )
+        .route(
+            "/links/import",
+            routing::post(import_links)
+        )
+        .route(
+            "/links/merge",
+            routing::post(merge_links)
+        )
+        .route(
+            "/links/tags",
+            routing::post(update_tags)
+        )
+        .route(
+            "/links/batch-get",
+            routing::post(batch_get_links)
+        )
         .route(
             "/links/:key", 
             routing::get(get_link)
                     .delete(delete_link)
         )
+        .route(
+            "/links/:key/stats",
+            routing::get(get_link_stats)
+        )
+        .route(
+            "/links/:key/rotate",
+            routing::post(rotate_link)
+        )
+        .route(
+            "/links/:key/make-primary",
+            routing::post(make_primary)
+        )
+        .route(
+            "/links/:key/copy",
+            routing::post(copy_link)
+        )
+        .route(
+            "/links/:key/protect",
+            routing::post(protect_link)
+        )
+        .route(
+            "/targets",
+            routing::get(get_targets)
+        )
+        .route(
+            "/maintenance/check-links",
+            routing::post(check_links)
+        )
+        .route(
+            "/maintenance/reset",
+            routing::post(reset_links)
+        )
+        .route(
+            "/export/signed",
+            routing::get(export_signed)
+        )
+        .route(
+            "/restore/signed",
+            routing::post(restore_signed)
+        )
+        .route(
+            "/links/:key/clicks",
+            routing::post(add_clicks)
+        )
+        .route(
+            "/links/:key/touch",
+            routing::post(touch_link)
+        )
+        .route(
+            "/links/:key/log",
+            routing::get(get_link_log)
+        )
+        .route(
+            "/links/:key/log/export",
+            routing::get(export_link_log)
+        )
+        .route(
+            "/links/:key/heatmap",
+            routing::get(get_link_heatmap)
+        )
+        .route(
+            "/log",
+            routing::get(get_access_log)
+        )
+        .route(
+            "/log/tail",
+            routing::get(tail_access_log)
+        )
+        .route(
+            "/stats/uptime",
+            routing::get(get_uptime_stats)
+        )
+        .route(
+            "/keys/reserved",
+            routing::get(get_reserved_keys)
+        )
         .route(
             "/validate/add_link",
             routing::post(validate_add_link)
         )
+        .route(
+            "/validate/batch",
+            routing::post(validate_batch)
+        )
+        .layer(DefaultBodyLimit::max(config.max_body_bytes))
 }
 
 #[derive(Serialize, Deserialize)]
 struct ResponseEntry {
     key: String,
     link: String,
-    metadata: crate::links::EntryMetadata,
+    metadata: EntryMetadata,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(default)]
+    password_protected: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    alt_target: Option<String>,
+    /// Estimated number of distinct visitors, see [`crate::visitors`].
+    #[serde(default)]
+    unique: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reachable: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    alive: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_checked: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    on_expire: OnExpire,
+    /// Whether this is the canonical key of its link.
+    #[serde(default)]
+    primary: bool,
+    /// Whether the link is kept when deleting links, unless forced.
+    #[serde(default)]
+    protected: bool,
+    /// Users that may view or delete the link.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    acl: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    tags: BTreeSet<String>,
 }
 impl From<(String, Entry)> for ResponseEntry {
     fn from((key, entry): (String, Entry)) -> Self {
         Self {
             key,
             link: entry.link,
-            metadata: entry.metadata
+            metadata: entry.metadata,
+            title: entry.title,
+            password_protected: entry.password.is_some(),
+            alt_target: entry.alt_target,
+            unique: entry.visitors.map_or(0, |v| v.estimate()),
+            reachable: entry.reachable,
+            alive: entry.alive,
+            last_checked: entry.last_checked,
+            expires_at: entry.expires_at,
+            on_expire: entry.on_expire,
+            primary: entry.primary,
+            protected: entry.protected,
+            acl: entry.acl,
+            tags: entry.tags,
         }
     }
 }
 
-#[derive(Serialize, Deserialize)]
+/// How click counts are shown to the client making the request.
+/// 
+/// With a configured bucket size, counts are rounded down to a multiple of it, so a count of
+/// 17 with buckets of 10 reads as 10, i.e. "10+". Exact counts are only shown to clients
+/// authenticated with the API token.
+struct ClickView {
+    bucket_size: Option<u64>,
+}
+
+impl ClickView {
+    fn apply(&self, used: u64) -> u64 {
+        match self.bucket_size {
+            Some(size) => bucket_clicks(used, size),
+            None => used
+        }
+    }
+
+    /// The response for an entry, with its counts as shown to the client.
+    fn entry(&self, key: String, entry: Entry) -> ResponseEntry {
+        let mut res = ResponseEntry::from((key, entry));
+        res.metadata.used = self.apply(res.metadata.used);
+        res.unique = self.apply(res.unique);
+        res
+    }
+}
+
+#[axum::async_trait]
+impl FromRequestParts<AppState> for ClickView {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let Some(size) = state.config.click_bucket_size else {
+            return Ok(Self { bucket_size: None });
+        };
+        let authenticated = state.config.api_token.is_some()
+            && Authorized::from_request_parts(parts, state).await.is_ok();
+        Ok(Self { bucket_size: (!authenticated).then_some(size) })
+    }
+}
+
+/// Proof that the client may see exact click counts.
+/// 
+/// Endpoints whose responses can't be bucketed, like individual access events or daily
+/// counts, take this instead of a [`ClickView`]. Summing them up would reveal the exact
+/// counts that bucketing hides.
+struct ExactClicks;
+
+#[axum::async_trait]
+impl FromRequestParts<AppState> for ExactClicks {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let view = ClickView::from_request_parts(parts, state).await.unwrap_or_else(|e| match e {});
+        if view.bucket_size.is_none() {
+            return Ok(ExactClicks);
+        }
+        Err(Jsend::<(), ()>::Error("Click counts are bucketed; this requires the API token".to_string())
+            .with_status(StatusCode::FORBIDDEN)
+            .into_response())
+    }
+}
+
+/// Round a click count down to a multiple of the bucket size.
+fn bucket_clicks(used: u64, bucket_size: u64) -> u64 {
+    if bucket_size == 0 {
+        return used;
+    }
+    used - used % bucket_size
+}
+
+#[derive(Serialize, Deserialize, Default)]
 struct AddLinkRequest {
     key: Option<String>,
     link: String,
+    /// Password required to follow the link.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    password: Option<String>,
+    /// Destination for clients that don't accept HTML.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    alt_target: Option<String>,
+    /// When the link stops redirecting to its destination.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<DateTime<Utc>>,
+    /// What following the link does once it has expired. Defaults to `410 Gone`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    on_expire: Option<OnExpire>,
+    /// Users that may view or delete the link. Everyone may by default.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    acl: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct AddLinkSuccessResponse {
     key: String,
     entry: Entry,
+    /// Whether the link was already stored, in which case `key` is its existing key.
+    #[serde(default)]
+    existing: bool,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 pub struct AddLinkFailResponse {
     key: Option<String>,
     link: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    password: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    alt_target: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    on_expire: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    acl: Option<String>,
+}
+
+/// Why a URL is rejected, if it is.
+///
+/// `Uri` parsing is lenient, e.g. it accepts `example.com` as an authority without a scheme,
+/// so the common mistakes are checked for explicitly to give a precise message.
+fn url_error(link: &str) -> Option<&'static str> {
+    if link.chars().any(char::is_control) {
+        return Some("URL cannot contain control characters");
+    }
+    if link.chars().any(char::is_whitespace) {
+        return Some("URL cannot contain whitespace");
+    }
+    let has_host = |link: &str| link.parse::<Uri>().is_ok_and(|uri| uri.scheme().is_some() && uri.host().is_some());
+    if has_host(link) {
+        None
+    } else if !link.contains("://") && has_host(&format!("https://{link}")) {
+        Some("URL must start with a scheme, e.g. https://")
+    } else {
+        Some("Invalid URL")
+    }
+}
+
+/// The key a link points at if it leads back into this instance's redirects, i.e. starts
+/// with the configured base URL or the `/go/` route on its host. Schemes and the case of the
+/// host are ignored.
+fn self_link_key(link: &str, config: &Config) -> Option<String> {
+    let strip_scheme = |url: &str| url.split_once("://").map_or(url, |(_, rest)| rest).to_string();
+    let base = strip_scheme(&config.server_base_url);
+    let go_route = format!("{}/go/", base.split('/').next().unwrap_or_default());
+    let link = strip_scheme(link);
+    // ASCII lowercasing keeps byte offsets intact, so prefix lengths are valid for `link`.
+    let lower = link.to_ascii_lowercase();
+
+    let key = [base.as_str(), go_route.as_str()].into_iter()
+        .filter(|prefix| prefix.ends_with('/') && lower.starts_with(&prefix.to_ascii_lowercase()))
+        .find_map(|prefix| {
+            let rest = &link[prefix.len()..];
+            let key = rest.split(['?', '#']).next().unwrap_or_default().trim_end_matches('/');
+            if key.is_empty() || key.contains('/') {
+                return None;
+            }
+            crate::links::percent_decode(key)
+        });
+    key
+}
+
+/// Why a key chosen by the client is rejected, if it is.
+fn key_error(key: &str, config: &Config, links: &Links) -> Option<String> {
+    if key.len() < MIN_KEY_LENGTH {
+        Some(format!("Key cannot be less than {MIN_KEY_LENGTH} characters"))
+    }
+    else if key.chars().count() > config.key_max_length {
+        Some(format!("Key cannot be more than {} characters", config.key_max_length))
+    }
+    else if !key.chars().all(|c| config.key_charset.allows(c)) {
+        Some(format!("Key can only contain {}", config.key_charset.describe()))
+    }
+    else if config.key_blacklist.iter().any(|k| k == key) {
+        Some(format!("Key '{key}' is disallowed"))
+    }
+    else if RESERVED_KEYS.iter().any(|k| k.eq_ignore_ascii_case(key)) {
+        Some(format!("Key '{key}' is reserved"))
+    }
+    else if links.contains_key(key) {
+        Some("Key already in use".to_string())
+    }
+    else {
+        None
+    }
+}
+
+impl AddLinkRequest {
+    /// Rewrite the link as configured before it is validated and stored: normalize it, and
+    /// turn links back into this instance into internal links.
+    fn prepare(&mut self, config: &Config) {
+        if config.normalize_urls {
+            self.link = crate::links::normalize_link(&self.link, config.trailing_slash);
+        }
+        if config.self_links == SelfLinks::Internal {
+            if let Some(key) = self_link_key(&self.link, config) {
+                self.link = format!("{}{key}", crate::links::INTERNAL_SCHEME);
+            }
+        }
+    }
 }
 
 impl Validator for AddLinkRequest {
     type Fail = AddLinkFailResponse;
     async fn validate(&self, state: &AppState) -> Option<Self::Fail> {
-        let mut fail = AddLinkFailResponse {
-            key: None,
-            link: None
-        };
+        let mut fail = AddLinkFailResponse::default();
     
         if self.link.is_empty() {
             fail.link = Some("Link cannot be empty".to_string());
         }
-        else {
-            match self.link.parse::<Uri>() {
-                Ok(uri) => {
-                    if uri.host().is_none() {
-                        fail.link = Some("Invalid URL".to_string());
-                    }                          
-                },
-                Err(_) => {
-                    fail.link = Some("Invalid URL".to_string());           
-                }
+        else if let Some(target) = crate::links::internal_target(&self.link) {
+            if self.key.as_deref() == Some(target) {
+                fail.link = Some("Link cannot point at itself".to_string());
+            }
+            else if let Err(e) = state.links.read().await.resolve(target) {
+                fail.link = Some(match e {
+                    ResolveError::NotFound(key) => format!("Key '{key}' does not exist"),
+                    ResolveError::Loop => format!("Key '{target}' is part of a redirect loop"),
+                });
             }
         }
+        else if let Some(e) = url_error(&self.link) {
+            fail.link = Some(e.to_string());
+        }
+        else if let Some(key) = self_link_key(&self.link, &state.config) {
+            fail.link = Some(format!("Link points back at key '{key}' of this instance"));
+        }
+        else if self.key.is_some() {
+            fail.link = alias_limit_fail(&*state.links.read().await, &self.link, state.config.max_aliases_per_url);
+        }
     
         if let Some(key) = &self.key {
-            if key.len() < 4 {
-                fail.key = Some("Key cannot be less than 4 characters".to_string());
+            fail.key = key_error(key, &state.config, &*state.links.read().await);
+        }
+
+        if let Some(password) = &self.password {
+            if password.is_empty() {
+                fail.password = Some("Password cannot be empty".to_string());
+            }
+            else if self.key.is_none() && state.links.read().await.find_by_link(&self.link).is_some() {
+                // Without a key the existing link would be returned, which we must not lock.
+                fail.password = Some("Link already exists; choose a key to protect it".to_string());
             }
-            else if key.contains(|c: char| !c.is_alphanumeric() && c != '_' && c != '-') {
-                fail.key = Some("Key can only contain 0-9, A-Z, a-z, _ or -".to_string());
+            else if let Some(target) = crate::links::internal_target(&self.link) {
+                // A chain is unlocked with a single password, so it may only have one.
+                let locked = state.links.read().await.chain(target)
+                    .is_ok_and(|chain| chain.iter().any(|(_, entry)| entry.password.is_some()));
+                if locked {
+                    fail.password = Some(format!("Key '{target}' is password protected already; links to it use its password"));
+                }
             }
-            else if state.config.key_blacklist.iter().any(|k| k == key) {
-                fail.key = Some(format!("Key '{key}' is disallowed"));
+        }
+    
+        if let Some(alt_target) = &self.alt_target {
+            if let Some(e) = url_error(alt_target) {
+                fail.alt_target = Some(e.to_string());
             }
-            else if state.links.read().await.get(key).is_some() {
-                fail.key = Some("Key already in use".to_string());
+            else if self.key.is_none() && state.links.read().await.find_by_link(&self.link).is_some() {
+                fail.alt_target = Some("Link already exists; choose a key to set an alternative target".to_string());
             }
         }
+
+        if self.expires_at.is_some() && self.key.is_none() && state.links.read().await.find_by_link(&self.link).is_some() {
+            fail.expires_at = Some("Link already exists; choose a key to set an expiry".to_string());
+        }
+
+        match &self.on_expire {
+            Some(_) if self.expires_at.is_none() => {
+                fail.on_expire = Some("Expiry action requires an expiry time".to_string());
+            },
+            Some(OnExpire::Redirect(url)) => {
+                fail.on_expire = url_error(url).map(str::to_string);
+            },
+            _ => {}
+        }
+
+        if let Some(user) = self.acl.iter().find(|user| !state.config.user_tokens.contains_key(*user)) {
+            fail.acl = Some(format!("Unknown user '{user}'"));
+        }
+        else if !self.acl.is_empty() && self.key.is_none() && state.links.read().await.find_by_link(&self.link).is_some() {
+            fail.acl = Some("Link already exists; choose a key to restrict access".to_string());
+        }
     
-        if fail.key.is_some() || fail.link.is_some() {
+        if fail.key.is_some() || fail.link.is_some() || fail.password.is_some() || fail.alt_target.is_some()
+            || fail.expires_at.is_some() || fail.on_expire.is_some() || fail.acl.is_some()
+        {
             Some(fail)
         } else {
             None
@@ -189,261 +701,4250 @@ impl Validator for AddLinkRequest {
     }
 }
 
+/// Create a new link.
+/// 
+/// Responds with `201 Created` and a `Location` header if a new link was created, or `200 OK`
+/// if the link already existed and its existing key is returned. Invalid requests fail with
+/// `400 Bad Request`, or `409 Conflict` if the key is already in use. Existing links the caller
+/// may not access are never returned; the request fails with `409 Conflict` instead.
+#[tracing::instrument(skip_all, fields(key = req.key, outcome))]
 async fn add_link(
+    _: Writable,
     State(state): State<AppState>,
-    Json(req): Json<AddLinkRequest>,
-) -> Jsend<AddLinkSuccessResponse, AddLinkFailResponse> {
-    if let Some(fail) = req.validate(&state).await {
-        return Jsend::Fail(fail);
+    caller: Caller,
+    view: ClickView,
+    JsonBody(mut req): JsonBody<AddLinkRequest>,
+) -> Response {
+    let mut created = false;
+    let res = async {
+        req.prepare(&state.config);
+
+        if let Some(fail) = req.validate(&state).await {
+            let in_use = match &req.key {
+                Some(key) if fail.key.is_some() => state.links.read().await.contains_key(key),
+                _ => false,
+            };
+            let status = if in_use { StatusCode::CONFLICT } else { StatusCode::BAD_REQUEST };
+            return Jsend::Fail(fail).with_status(status);
+        }
+
+        let reachable = match check_reachable(&state, &req).await {
+            Some(false) if state.config.verify_reachable == VerifyReachable::Reject => {
+                return Jsend::Fail(AddLinkFailResponse {
+                    link: Some("Link target is unreachable".to_string()),
+                    ..Default::default()
+                }).with_status(StatusCode::BAD_REQUEST);
+            },
+            reachable => reachable.filter(|_| state.config.verify_reachable == VerifyReachable::Warn),
+        };
+
+        let mut links = state.links.write().await;
+        // Checked again under the write lock, since concurrent requests may have added keys.
+        if req.key.is_some() {
+            if let Some(fail) = alias_limit_fail(&links, &req.link, state.config.max_aliases_per_url) {
+                return Jsend::Fail(AddLinkFailResponse { link: Some(fail), ..Default::default() })
+                    .with_status(StatusCode::BAD_REQUEST);
+            }
+        }
+        
+        let result = match req.key {
+            Some(key) => AddResult::Created(key.clone(), links.add_named(key, req.link)
+                .map_err(|_| "Duplicate key after validation (unreachable state)".to_string())?),  
+            None => match alias_at_limit(&links, &req.link, state.config.max_aliases_per_url) {
+                // Reuse an existing key rather than adding one more.
+                Some((key, entry)) => AddResult::Existing(key, entry),
+                None => links.add(req.link)?
+            }
+        };
+        if let AddResult::Existing(_, entry) = &result {
+            if !caller.may_access(&entry.acl) {
+                return Jsend::Fail(AddLinkFailResponse {
+                    link: Some("Link already exists; choose a key to add it under".to_string()),
+                    ..Default::default()
+                }).with_status(StatusCode::CONFLICT);
+            }
+        }
+        created = result.is_created();
+        let (key, mut entry) = result.into_parts();
+        tracing::Span::current().record("key", &key);
+
+        if created {
+            let stored = links.get_mut(&key)
+                .ok_or("Missing link after insertion (unreachable state)".to_string())?;
+            stored.metadata.source = Some(SOURCE_API.to_string());
+            stored.reachable = reachable;
+            entry.metadata.source = Some(SOURCE_API.to_string());
+            entry.reachable = reachable;
+        }
+        if req.password.is_some() || req.alt_target.is_some() || req.expires_at.is_some() || !req.acl.is_empty() {
+            let stored = links.get_mut(&key)
+                .ok_or("Missing link after insertion (unreachable state)".to_string())?;
+            if let Some(password) = &req.password {
+                stored.set_password(password)?;
+            }
+            stored.alt_target.clone_from(&req.alt_target);
+            entry.alt_target.clone_from(&req.alt_target);
+            stored.expires_at = req.expires_at;
+            entry.expires_at = req.expires_at;
+            stored.on_expire = req.on_expire.clone().unwrap_or_default();
+            entry.on_expire = req.on_expire.clone().unwrap_or_default();
+            stored.acl.clone_from(&req.acl);
+            entry.acl.clone_from(&req.acl);
+        }
+        // Never send the password hash back to the client
+        entry.password = None;
+        entry.metadata.used = view.apply(entry.metadata.used);
+        
+        state.save_links(links).await
+            .map_err(|_| "Could not create link: IO error".to_string())?;
+
+        if state.config.fetch_titles && entry.title.is_none() {
+            tokio::spawn(update_title(state.clone(), key.clone(), entry.link.clone()));
+        }
+        if created {
+            webhook::notify(&state, webhook::Event::new(&state, webhook::EventKind::Created, &key, &entry.link));
+        }
+
+        Jsend::Success(AddLinkSuccessResponse { key, entry, existing: !created }).into()
+    }.await.record_outcome();
+
+    match &res.1 {
+        Jsend::Success(data) if created => {
+            let location = format!("/api/links/{}", crate::links::percent_encode(&data.key));
+            (StatusCode::CREATED, [(header::LOCATION, location)], res.1).into_response()
+        },
+        _ => res.into_response()
     }
+}
 
-    let mut links = state.links.write().await;
-    
-    let (key, entry) = match req.key {
-        Some(key) => (key.clone(), links.add_named(key, req.link)
-            .map_err(|_| "Duplicate key after validation (unreachable state)".to_string())?),  
-        None => links.add(req.link)
+/// The first key of a link that already has the maximum number of keys, if it has.
+fn alias_at_limit(links: &Links, link: &str, max: Option<usize>) -> Option<(String, Entry)> {
+    let max = max?;
+    let keys = links.find_by_link(link).filter(|keys| keys.len() >= max)?;
+    let key = keys.into_iter().next()?;
+    let entry = links.get(&key)?.clone();
+    Some((key, entry))
+}
+
+/// Why no more keys may be added for a link, if it already has the maximum number of keys.
+fn alias_limit_fail(links: &Links, link: &str, max: Option<usize>) -> Option<String> {
+    let max = max?;
+    alias_at_limit(links, link, Some(max)).map(|_| format!("Link already has the maximum of {max} keys"))
+}
+
+/// Check whether the destination of a link to be added responds, if configured.
+/// 
+/// Internal links and links that are already stored, in which case no link is created, are
+/// never checked.
+async fn check_reachable(state: &AppState, req: &AddLinkRequest) -> Option<bool> {
+    if state.config.verify_reachable == VerifyReachable::Off
+        || crate::links::internal_target(&req.link).is_some()
+        || req.key.is_none() && state.links.read().await.find_by_link(&req.link).is_some()
+    {
+        return None;
+    }
+    Some(state.prober.is_reachable(&req.link).await)
+}
+
+/// Fetch the title of a link's destination and store it on the entry.
+/// 
+/// Runs in the background after creation, so a slow destination never delays `add_link`.
+async fn update_title(state: AppState, key: String, link: String) {
+    let Some(title) = state.prober.fetch_title(&link).await else {
+        return;
     };
-    
-    links.save(&state.config.link_data_path)
-        .map_err(|_| "Could not create link: IO error".to_string())?;
 
-    Jsend::Success(AddLinkSuccessResponse { key, entry })
+    let mut links = state.links.write().await;
+    match links.get_mut(&key) {
+        // The link may have been removed or replaced in the meantime.
+        Some(entry) if entry.link == link => entry.title = Some(title),
+        _ => return
+    }
+    if let Err(e) = state.save_links(links).await {
+        tracing::error!("Failed to save title for link '{key}': {e}");
+    }
 }
 
 type GetLinkResponse = ResponseEntry;
+#[tracing::instrument(skip_all, fields(key = %*key, outcome))]
 async fn get_link(
     State(state): State<AppState>,
+    caller: Caller,
+    view: ClickView,
     key: axum::extract::Path<String>
-) -> Jsend<GetLinkResponse, String> {
+) -> WithStatus<GetLinkResponse, String> {
     let links = state.links.read().await;
-    links.get(&key)
-        .map(|entry| (key.clone(), entry.clone()).into())
-        .ok_or("Link not found".to_string())
-        .into()
+    let res = match links.get(&key) {
+        None => not_found(),
+        Some(entry) if !caller.may_access(&entry.acl) => forbidden(),
+        Some(entry) => Jsend::Success(view.entry(key.clone(), entry.clone())).into(),
+    };
+    res.record_outcome()
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ForceQuery {
+    /// Whether protected links are removed too.
+    #[serde(default)]
+    force: bool,
 }
 
+/// Delete a link. Protected links are only deleted with `?force=true`.
+#[tracing::instrument(skip_all, fields(key = %*key, outcome))]
 async fn delete_link(
+    _: Writable,
     State(state): State<AppState>,
-    key: axum::extract::Path<String>
-) -> Jsend<(), String> {
+    caller: Caller,
+    key: axum::extract::Path<String>,
+    Query(query): Query<ForceQuery>,
+) -> WithStatus<(), String> {
     let mut links = state.links.write().await;
-    links.remove(key.as_str())
-        .map(|_| ())    
-        .ok_or("Link not found".to_string())
-        .into()
+    if links.get(&key).is_some_and(|entry| !caller.may_access(&entry.acl)) {
+        return forbidden().record_outcome();
+    }
+    if links.get(&key).is_some_and(|entry| entry.protected && !query.force) {
+        return Jsend::Fail("Link is protected, delete it with ?force=true".to_string())
+            .with_status(StatusCode::CONFLICT)
+            .record_outcome();
+    }
+    let res = match links.remove(key.as_str()) {
+        Some(entry) => {
+            state.bury(&key).await;
+            webhook::notify(&state, webhook::Event::new(&state, webhook::EventKind::Deleted, &key, &entry.link));
+            Jsend::Success(()).into()
+        },
+        None => not_found(),
+    };
+    res.record_outcome()
 }
 
+#[derive(Serialize, Deserialize)]
+struct LinkStatsResponse {
+    used: u64,
+    created: DateTime<Utc>,
+    last_used: DateTime<Utc>,
+}
 
-
-type GetLinksResponse = Vec<ResponseEntry>;
-async fn get_links(
-    State(state): State<AppState>
-) -> Jsend<GetLinksResponse, ()> {
+/// Get only the usage statistics of a link, for clients polling them often.
+#[tracing::instrument(skip_all, fields(key = %*key, outcome))]
+async fn get_link_stats(
+    State(state): State<AppState>,
+    caller: Caller,
+    view: ClickView,
+    key: axum::extract::Path<String>
+) -> WithStatus<LinkStatsResponse, String> {
     let links = state.links.read().await;
-    let res = links.iter()
-        .map(|(k, v)| (k.clone(), v.clone()).into())
-        .collect::<Vec<_>>();
-    Jsend::Success(res)
+    let res = match links.get(&key) {
+        None => not_found(),
+        Some(entry) if !caller.may_access(&entry.acl) => forbidden(),
+        Some(entry) => Jsend::Success(LinkStatsResponse {
+            used: view.apply(entry.metadata.used),
+            created: entry.metadata.created,
+            last_used: entry.metadata.last_used,
+        }).into(),
+    };
+    res.record_outcome()
 }
 
-async fn validate_add_link(
-    State(state): State<AppState>,
-    Json(req): Json<AddLinkRequest>,
-) -> Jsend<(), AddLinkFailResponse> {
-    match req.validate(&state).await {
-        Some(fail) => Jsend::Fail(fail),
-        None => Jsend::Success(())
-    }
+/// Response for a key without a link.
+fn not_found<T>() -> WithStatus<T, String> {
+    Jsend::Fail("Link not found".to_string()).with_status(StatusCode::NOT_FOUND)
 }
 
-#[cfg(test)]
-mod tests {
-    use std::path::{Path, PathBuf};
-    use std::{env::temp_dir, sync::Arc};
+/// Response for a link whose access control list doesn't include the caller.
+fn forbidden<T>() -> WithStatus<T, String> {
+    Jsend::Fail("Not allowed to access this link".to_string()).with_status(StatusCode::FORBIDDEN)
+}
 
-    use rand::{RngCore, SeedableRng};
-    use tokio::net::TcpListener;
-    use tokio::sync::mpsc;    
-    use crate::Config;
+/// Whether the link with the given key exists and its access control list doesn't include
+/// the caller. Keys that don't exist (anymore) aren't restricted.
+async fn denied(state: &AppState, caller: &Caller, key: &str) -> bool {
+    state.links.read().await.get(key).is_some_and(|entry| !caller.may_access(&entry.acl))
+}
 
-    use super::*; 
+#[derive(Serialize, Deserialize, Default)]
+struct BatchGetRequest {
+    keys: Vec<String>,
+    /// Whether keys without a link are included in the response as `null`, instead of being
+    /// left out.
+    #[serde(default)]
+    include_missing: bool,
+}
 
-    fn cleanup(path: &Path) {
-        std::fs::remove_file(path)
-            .unwrap_or(());
-    }
-    fn random_links_path() -> PathBuf {        
-        let mut rng = rand::rngs::SmallRng::seed_from_u64(1);
-        let suffix = rng.next_u64();
-        temp_dir().join(format!("links-{}.toml", suffix))
-    }
+type BatchGetResponse = BTreeMap<String, Option<ResponseEntry>>;
 
-    async fn setup_test_api(links_path: &Path) -> (String, mpsc::Sender<()>) {
-        let state = AppState {
-            config: Arc::new(Config { 
-                link_data_path: PathBuf::from(links_path),
-                bind_address: "".to_string(),
-                server_base_url: "".to_string(),
-                key_blacklist: vec![],
-            }),
-            links: std::sync::Arc::new(tokio::sync::RwLock::new(crate::Links::default())),
-            access_event_queue: std::sync::Arc::new(concurrent_queue::ConcurrentQueue::unbounded())
-        };
+/// Get the links with the given keys at once.
+/// 
+/// Links whose access control list doesn't include the caller are treated like missing ones.
+#[tracing::instrument(skip_all, fields(count = req.keys.len(), outcome))]
+async fn batch_get_links(
+    State(state): State<AppState>,
+    caller: Caller,
+    view: ClickView,
+    JsonBody(req): JsonBody<BatchGetRequest>,
+) -> Jsend<BatchGetResponse, ()> {
+    let links = state.links.read().await;
+    let res = req.keys.into_iter()
+        .filter_map(|key| {
+            let entry = links.get(&key)
+                .filter(|entry| caller.may_access(&entry.acl))
+                .map(|entry| view.entry(key.clone(), entry.clone()));
+            (entry.is_some() || req.include_missing).then_some((key, entry))
+        })
+        .collect();
+    Jsend::Success(res).record_outcome()
+}
 
-        let router = router().with_state(state);
-        
-        let port = 54500;
-        let mut listener = TcpListener::bind(format!("127.0.0.1:{port}")).await;
-        while listener.is_err() {
-            let port = port + 1;
-            listener = TcpListener::bind(format!("127.0.0.1:{port}")).await;            
-        }
-        let listener = listener.unwrap();
 
-        let addr = format!("http://{}", listener.local_addr().unwrap());        
 
-        let (sender, mut receiver) = mpsc::channel(1);              
+#[derive(Serialize, Deserialize)]
+struct RotateLinkResponse {
+    key: String,
+    short_url: String,
+}
+
+/// Move a link to a newly generated key, invalidating the old one.
+/// 
+/// Protected links are refused with `409`, unless `?force=true` is given.
+#[tracing::instrument(skip_all, fields(key = %*key, new_key, outcome))]
+async fn rotate_link(
+    _: Writable,
+    State(state): State<AppState>,
+    caller: Caller,
+    key: axum::extract::Path<String>,
+    Query(query): Query<ForceQuery>,
+) -> WithStatus<RotateLinkResponse, String> {
+    async {
+        let mut links = state.links.write().await;
+        if links.get(&key).is_some_and(|entry| !caller.may_access(&entry.acl)) {
+            return forbidden();
+        }
+        if links.get(&key).is_some_and(|entry| entry.protected && !query.force) {
+            return Jsend::Fail("Link is protected, rotate it with ?force=true".to_string())
+                .with_status(StatusCode::CONFLICT);
+        }
+        let Some(new_key) = links.rotate(&key)? else {
+            return not_found();
+        };
+        tracing::Span::current().record("new_key", &new_key);
+
+        state.save_links(links).await
+            .map_err(|_| "Could not rotate link: IO error".to_string())?;
+        state.bury(&key).await;
+
+        let short_url = state.config.short_url(&new_key);
+        Jsend::Success(RotateLinkResponse { key: new_key, short_url }).into()
+    }.await.record_outcome()
+}
+
+#[derive(Serialize, Deserialize)]
+struct MergeLinksRequest {
+    from: String,
+    into: String,
+}
+
+/// Merge two keys of the same link, adding the statistics of `from` to `into` and removing
+/// `from`.
+/// 
+/// Refused with `403` unless the caller may access both links.
+#[tracing::instrument(skip_all, fields(from = req.from, into = req.into, outcome))]
+async fn merge_links(
+    _: Writable,
+    State(state): State<AppState>,
+    caller: Caller,
+    JsonBody(req): JsonBody<MergeLinksRequest>,
+) -> WithStatus<ResponseEntry, String> {
+    async {
+        let mut links = state.links.write().await;
+        let denied = [&req.from, &req.into].into_iter()
+            .any(|key| links.get(key).is_some_and(|entry| !caller.may_access(&entry.acl)));
+        if denied {
+            return forbidden();
+        }
+        let removed = match links.merge(&req.from, &req.into, state.config.trailing_slash) {
+            Ok(removed) => removed,
+            Err(e) => return Jsend::Fail(e).with_status(StatusCode::BAD_REQUEST),
+        };
+        let entry = links.get(&req.into)
+            .ok_or("Missing link after merging (unreachable state)".to_string())?
+            .clone();
+        state.save_links(links).await
+            .map_err(|_| "Could not merge links: IO error".to_string())?;
+        state.bury(&req.from).await;
+
+        webhook::notify(&state, webhook::Event::new(&state, webhook::EventKind::Deleted, &req.from, &removed.link));
+        Jsend::Success(ResponseEntry::from((req.into.clone(), entry))).into()
+    }.await.record_outcome()
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct UpdateTagsRequest {
+    keys: Vec<String>,
+    #[serde(default)]
+    add: BTreeSet<String>,
+    #[serde(default)]
+    remove: BTreeSet<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct UpdateTagsResponse {
+    /// Keys of the links that now have the requested tags.
+    updated: Vec<String>,
+    /// Keys that don't exist, or whose links the caller may not access.
+    missing: Vec<String>,
+}
+
+/// Add and remove tags across many links at once, saving them once.
+/// 
+/// Links whose access control list doesn't include the caller are treated like missing ones.
+#[tracing::instrument(skip_all, fields(count = req.keys.len(), outcome))]
+async fn update_tags(
+    _: Writable,
+    State(state): State<AppState>,
+    caller: Caller,
+    JsonBody(req): JsonBody<UpdateTagsRequest>,
+) -> WithStatus<UpdateTagsResponse, String> {
+    async {
+        if let Some(tag) = req.add.iter().chain(&req.remove).find(|t| t.is_empty() || t.chars().any(char::is_whitespace)) {
+            return Jsend::Fail(format!("Invalid tag '{tag}'")).with_status(StatusCode::BAD_REQUEST);
+        }
+        if let Some(tag) = req.add.intersection(&req.remove).next() {
+            return Jsend::Fail(format!("Tag '{tag}' cannot be both added and removed")).with_status(StatusCode::BAD_REQUEST);
+        }
+
+        let mut links = state.links.write().await;
+        let (mut updated, mut missing) = (Vec::new(), Vec::new());
+        let mut changed = false;
+        for key in req.keys {
+            let Some(entry) = links.get_mut(&key).filter(|entry| caller.may_access(&entry.acl)) else {
+                missing.push(key);
+                continue;
+            };
+            let tags = entry.tags.union(&req.add)
+                .filter(|t| !req.remove.contains(*t))
+                .cloned()
+                .collect();
+            changed |= entry.tags != tags;
+            entry.tags = tags;
+            updated.push(key);
+        }
+        if changed {
+            state.save_links(links).await
+                .map_err(|_| "Could not update tags: IO error".to_string())?;
+        }
+        Jsend::Success(UpdateTagsResponse { updated, missing }).into()
+    }.await.record_outcome()
+}
+
+type MakePrimaryResponse = RotateLinkResponse;
+
+/// Mark a key as the canonical alias of its link, e.g. the one to share.
+#[tracing::instrument(skip_all, fields(key = %*key, outcome))]
+async fn make_primary(
+    _: Writable,
+    State(state): State<AppState>,
+    caller: Caller,
+    key: axum::extract::Path<String>
+) -> WithStatus<MakePrimaryResponse, String> {
+    async {
+        let mut links = state.links.write().await;
+        if links.get(&key).is_some_and(|entry| !caller.may_access(&entry.acl)) {
+            return forbidden();
+        }
+        if links.make_primary(&key).is_err() {
+            return not_found();
+        }
+        state.save_links(links).await
+            .map_err(|_| "Could not update link: IO error".to_string())?;
+
+        let short_url = state.config.short_url(&key);
+        Jsend::Success(MakePrimaryResponse { key: key.to_string(), short_url }).into()
+    }.await.record_outcome()
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct CopyLinkRequest {
+    /// Key of the copy, generated if unset.
+    new_key: Option<String>,
+}
+
+type CopyLinkResponse = RotateLinkResponse;
+
+/// Store a copy of a link under a new key, with its own statistics.
+/// 
+/// Unlike aliases created by adding the same link twice, the copy starts out unused.
+#[tracing::instrument(skip_all, fields(key = %*key, new_key, outcome))]
+async fn copy_link(
+    _: Writable,
+    State(state): State<AppState>,
+    caller: Caller,
+    key: axum::extract::Path<String>,
+    OptionalJsonBody(req): OptionalJsonBody<CopyLinkRequest>,
+) -> Response {
+    let res = async {
+        let mut links = state.links.write().await;
+        match links.get(&key) {
+            None => return not_found(),
+            Some(entry) if !caller.may_access(&entry.acl) => return forbidden(),
+            Some(_) => {},
+        }
+        if let Some(fail) = req.new_key.as_deref().and_then(|k| key_error(k, &state.config, &links)) {
+            return Jsend::Fail(fail).with_status(StatusCode::BAD_REQUEST);
+        }
+        let link = links.get(&key).map(|entry| entry.link.clone()).unwrap_or_default();
+        if let Some(fail) = alias_limit_fail(&links, &link, state.config.max_aliases_per_url) {
+            return Jsend::Fail(fail).with_status(StatusCode::BAD_REQUEST);
+        }
+        let new_key = links.copy(&key, req.new_key)?;
+        tracing::Span::current().record("new_key", &new_key);
+        let link = links.get(&new_key)
+            .ok_or("Missing link after copy (unreachable state)".to_string())?
+            .link.clone();
+
+        state.save_links(links).await
+            .map_err(|_| "Could not copy link: IO error".to_string())?;
+
+        webhook::notify(&state, webhook::Event::new(&state, webhook::EventKind::Created, &new_key, &link));
+        let short_url = state.config.short_url(&new_key);
+        Jsend::Success(CopyLinkResponse { key: new_key, short_url }).into()
+    }.await.record_outcome();
+
+    match res {
+        WithStatus(_, Jsend::Success(data)) => {
+            let location = format!("/api/links/{}", crate::links::percent_encode(&data.key));
+            let res = Jsend::<_, String>::Success(data).with_status(StatusCode::CREATED);
+            ([(header::LOCATION, location)], res).into_response()
+        },
+        _ => res.into_response()
+    }
+}
+
+/// Maximum number of clicks reported in a single `add_clicks` request.
+const MAX_CLICKS_PER_REQUEST: u64 = 1000;
+
+#[derive(Serialize, Deserialize, Default)]
+struct AddClicksRequest {
+    /// Number of clicks to record, 1 by default.
+    count: Option<u64>,
+    /// Time of the clicks, now by default.
+    timestamp: Option<DateTime<Utc>>,
+}
+
+/// Record clicks on a link that were served elsewhere, e.g. by redirects at the edge.
+/// 
+/// The clicks go through the same queue as redirects, so they are accounted for and logged
+/// in the same way. Refused with `403` if analytics are disabled.
+#[tracing::instrument(skip_all, fields(key = %*key, outcome))]
+async fn add_clicks(
+    _: Authorized,
+    _: Writable,
+    State(state): State<AppState>,
+    key: axum::extract::Path<String>,
+    OptionalJsonBody(req): OptionalJsonBody<AddClicksRequest>,
+) -> Response {
+    if state.config.disable_analytics {
+        return Jsend::<(), _>::Fail("Analytics are disabled".to_string())
+            .with_status(StatusCode::FORBIDDEN).record_outcome().into_response();
+    }
+    let count = req.count.unwrap_or(1);
+    if count == 0 || count > MAX_CLICKS_PER_REQUEST {
+        let fail = format!("Count must be between 1 and {MAX_CLICKS_PER_REQUEST}");
+        return Jsend::<(), _>::Fail(fail).with_status(StatusCode::BAD_REQUEST).record_outcome().into_response();
+    }
+
+    if !state.links.read().await.contains_key(&key) {
+        return not_found::<()>().record_outcome().into_response();
+    }
+
+    let timestamp = req.timestamp
+        .map(std::time::SystemTime::from)
+        .unwrap_or_else(|| state.clock.now().into());
+    for _ in 0..count {
+        let event = LinkAccessEvent { key: key.clone(), timestamp, visitor: None };
+        if let Err(e) = state.access_event_queue.push(event) {
+            return Jsend::<(), String>::Error(format!("Could not record clicks: {e}"))
+                .record_outcome().into_response();
+        }
+    }
+    Jsend::<(), String>::Success(()).record_outcome().into_response()
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProtectLinkResponse {
+    key: String,
+    protected: bool,
+}
+
+/// Toggle whether a link is protected from being deleted, see [`Entry::protected`].
+#[tracing::instrument(skip_all, fields(key = %*key, outcome))]
+async fn protect_link(
+    _: Authorized,
+    _: Writable,
+    State(state): State<AppState>,
+    caller: Caller,
+    key: axum::extract::Path<String>,
+) -> WithStatus<ProtectLinkResponse, String> {
+    async {
+        let mut links = state.links.write().await;
+        if links.get(&key).is_some_and(|entry| !caller.may_access(&entry.acl)) {
+            return forbidden();
+        }
+        let Some(entry) = links.get_mut(&key) else {
+            return not_found();
+        };
+        entry.protected = !entry.protected;
+        let protected = entry.protected;
+        state.save_links(links).await
+            .map_err(|_| "Could not update link: IO error".to_string())?;
+        Jsend::Success(ProtectLinkResponse { key: key.to_string(), protected }).into()
+    }.await.record_outcome()
+}
+
+#[derive(Serialize, Deserialize)]
+struct TouchLinkRequest {
+    /// Number of uses to add.
+    count: u64,
+    /// Time of the latest of these uses, now by default. `last_used` never moves back.
+    last_used: Option<DateTime<Utc>>,
+}
+
+/// Add uses to the statistics of a link directly, e.g. to backfill counts when migrating
+/// from another shortener.
+/// 
+/// Unlike `add_clicks`, the uses bypass the access queue, so they are neither logged nor
+/// limited in number, and are saved before responding.
+#[tracing::instrument(skip_all, fields(key = %*key, outcome))]
+async fn touch_link(
+    _: Authorized,
+    _: Writable,
+    State(state): State<AppState>,
+    key: axum::extract::Path<String>,
+    JsonBody(req): JsonBody<TouchLinkRequest>,
+) -> WithStatus<LinkStatsResponse, String> {
+    async {
+        let mut links = state.links.write().await;
+        let Some(entry) = links.get_mut(&key) else {
+            return not_found();
+        };
+        let metadata = &mut entry.metadata;
+        metadata.used = metadata.used.saturating_add(req.count);
+        metadata.last_used = metadata.last_used.max(req.last_used.unwrap_or_else(|| state.clock.now()));
+        let stats = LinkStatsResponse {
+            used: metadata.used,
+            created: metadata.created,
+            last_used: metadata.last_used,
+        };
+        state.save_links(links).await
+            .map_err(|_| "Could not update link: IO error".to_string())?;
+        Jsend::Success(stats).into()
+    }.await.record_outcome()
+}
+
+/// Default number of events returned by `get_link_log` and `get_access_log`.
+const DEFAULT_LOG_PAGE_SIZE: usize = 100;
+/// Maximum number of events returned by `get_link_log` and `get_access_log`.
+const MAX_LOG_PAGE_SIZE: usize = 1000;
+
+#[derive(Serialize, Deserialize, Default)]
+struct LinkLogQuery {
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LinkLogResponse {
+    events: Vec<LoggedAccess>,
+    /// Offset of the next page, if there are more events.
+    next_offset: Option<usize>,
+}
+
+/// Page through the raw access events of a link, oldest first.
+#[tracing::instrument(skip_all, fields(key = %*key, outcome))]
+async fn get_link_log(
+    _: ExactClicks,
+    State(state): State<AppState>,
+    caller: Caller,
+    key: axum::extract::Path<String>,
+    Query(query): Query<LinkLogQuery>,
+) -> WithStatus<LinkLogResponse, String> {
+    async {
+        if denied(&state, &caller, &key).await {
+            return forbidden();
+        }
+        let Some(path) = state.config.event_log_path.clone() else {
+            return Jsend::Fail("Access log is not enabled".to_string()).into();
+        };
+        let filter = KeyFilter::new(&key, state.config.case_insensitive_keys).into();
+        read_log_page(path, filter, LogOrder::OldestFirst, query.offset, query.limit).await.into()
+    }.await.record_outcome()
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct AccessLogQuery {
+    /// Only include accesses of this key.
+    key: Option<String>,
+    /// Only include accesses at or after this time (RFC 3339).
+    since: Option<DateTime<Utc>>,
+    /// Only include accesses before this time (RFC 3339).
+    until: Option<DateTime<Utc>>,
+    /// `time` for the oldest accesses first, `-time` for the newest first.
+    #[serde(default)]
+    sort: LogOrder,
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+}
+
+/// Page through the access events of all links, e.g. for an activity feed.
+/// 
+/// Reads the persisted log, so events still waiting in the queue are not included yet.
+#[tracing::instrument(skip_all, fields(outcome))]
+async fn get_access_log(
+    _: Authorized,
+    State(state): State<AppState>,
+    Query(query): Query<AccessLogQuery>,
+) -> Jsend<LinkLogResponse, String> {
+    async {
+        let Some(path) = state.config.event_log_path.clone() else {
+            return Jsend::Fail("Access log is not enabled".to_string());
+        };
+        let filter = LogFilter {
+            key: query.key.map(|key| KeyFilter::new(&key, state.config.case_insensitive_keys)),
+            since: query.since,
+            until: query.until,
+        };
+        read_log_page(path, filter, query.sort, query.offset, query.limit).await
+    }.await.record_outcome()
+}
+
+/// Read a page of the access log on the blocking thread pool.
+async fn read_log_page(
+    path: std::path::PathBuf,
+    filter: LogFilter,
+    order: LogOrder,
+    offset: usize,
+    limit: Option<usize>,
+) -> Jsend<LinkLogResponse, String> {
+    let limit = limit.unwrap_or(DEFAULT_LOG_PAGE_SIZE).min(MAX_LOG_PAGE_SIZE);
+
+    // Read one more than requested to find out whether there is a next page.
+    let mut events = tokio::task::spawn_blocking(move || event_log::read(&path, &filter, order, offset, limit + 1))
+        .await
+        .map_err(|e| format!("Could not read access log: {e}"))?
+        .map_err(|e| format!("Could not read access log: {e}"))?;
+
+    let next_offset = (events.len() > limit).then_some(offset + limit);
+    events.truncate(limit);
+    Jsend::Success(LinkLogResponse { events, next_offset })
+}
+
+/// Stream all access events of a link as newline-delimited JSON.
+#[tracing::instrument(skip_all, fields(key = %*key, outcome))]
+async fn export_link_log(
+    _: ExactClicks,
+    State(state): State<AppState>,
+    caller: Caller,
+    key: axum::extract::Path<String>,
+) -> Response {
+    if denied(&state, &caller, &key).await {
+        return forbidden::<()>().record_outcome().into_response();
+    }
+    let Some(path) = &state.config.event_log_path else {
+        return Jsend::<(), _>::Fail("Access log is not enabled".to_string()).record_outcome().into_response();
+    };
+    let filter = KeyFilter::new(&key, state.config.case_insensitive_keys);
+
+    match event_log::stream(path, filter).await {
+        Ok(stream) => {
+            tracing::Span::current().record("outcome", "success");
+            ([(header::CONTENT_TYPE, "application/x-ndjson")], Body::from_stream(stream)).into_response()
+        },
+        Err(e) => Jsend::<(), ()>::Error(format!("Could not read access log: {e}")).record_outcome().into_response()
+    }
+}
+
+/// Stream the access events of all links as newline-delimited JSON while they happen, for
+/// watching activity live.
+/// 
+/// Only events applied by the metadata worker after connecting are sent, so they arrive up to
+/// a few seconds late and in batches. Each client buffers at most
+/// [`event_log::TAIL_CAPACITY`] events; a client that reads slower than links are followed
+/// skips the oldest ones. The stream ends when the server shuts down.
+#[tracing::instrument(skip_all, fields(outcome))]
+async fn tail_access_log(
+    _: Authorized,
+    State(state): State<AppState>,
+) -> Response {
+    let receiver = state.access_tail.subscribe();
+    let events = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(access) => {
+                    let line = serde_json::to_string(&access).map(|line| line + "\n");
+                    return Some((line, receiver));
+                },
+                Err(RecvError::Lagged(skipped)) => tracing::debug!("Log tail skipped {skipped} events"),
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }).take_until(crate::shutdown_signal());
+
+    tracing::Span::current().record("outcome", "success");
+    ([(header::CONTENT_TYPE, "application/x-ndjson")], Body::from_stream(events)).into_response()
+}
+
+/// Default number of days covered by `get_link_heatmap`.
+const DEFAULT_HEATMAP_DAYS: u64 = 90;
+/// Maximum number of days covered by `get_link_heatmap`.
+const MAX_HEATMAP_DAYS: u64 = 366;
+
+#[derive(Serialize, Deserialize, Default)]
+struct LinkHeatmapQuery {
+    days: Option<u64>,
+}
+
+/// Get the number of clicks of a link on each of the last days, oldest first and ending today (UTC).
+#[tracing::instrument(skip_all, fields(key = %*key, outcome))]
+async fn get_link_heatmap(
+    _: ExactClicks,
+    State(state): State<AppState>,
+    caller: Caller,
+    key: axum::extract::Path<String>,
+    Query(query): Query<LinkHeatmapQuery>,
+) -> WithStatus<Vec<DayCount>, String> {
+    async {
+        if denied(&state, &caller, &key).await {
+            return forbidden();
+        }
+        if state.config.event_log_path.is_none() {
+            return Jsend::Fail("Access log is not enabled".to_string()).into();
+        }
+        let days = query.days.unwrap_or(DEFAULT_HEATMAP_DAYS).clamp(1, MAX_HEATMAP_DAYS);
+        let today = state.clock.now().date_naive();
+
+        Jsend::Success(state.daily_clicks.read().await.last_days(&key, today, days)).into()
+    }.await.record_outcome()
+}
+
+/// Filters, order and paging for `get_links`. All given filters must match for an entry to
+/// be included.
+#[derive(Serialize, Deserialize, Default)]
+struct GetLinksQuery {
+    /// Only include links whose key, destination or title contains this text, ignoring case.
+    q: Option<String>,
+    /// Only include links with this tag.
+    tag: Option<String>,
+    /// Only include links created at or after this time (RFC 3339).
+    created_after: Option<DateTime<Utc>>,
+    /// Only include links created before this time (RFC 3339).
+    created_before: Option<DateTime<Utc>>,
+    /// Only include links that have been used at least this many times.
+    used_min: Option<u64>,
+    /// Only include links created this way, e.g. `import`.
+    source: Option<String>,
+    /// Only include links whose destination did or didn't respond when last checked, see
+    /// [`Entry::is_dead`].
+    dead: Option<bool>,
+    /// Order of the matching links, by key if unset.
+    #[serde(default)]
+    sort: LinkSort,
+    /// Number of matching links to skip.
+    #[serde(default)]
+    offset: usize,
+    /// Maximum number of links to return. All matching links are returned if unset.
+    limit: Option<usize>,
+}
+
+/// Order of the links returned by `get_links`. Statistics are sorted in descending order,
+/// ties are broken by key.
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+enum LinkSort {
+    #[default]
+    Key,
+    Used,
+    Created,
+    LastUsed,
+}
+
+impl GetLinksQuery {
+    /// All links matching the filters, in the requested order.
+    fn select<'a>(&self, links: &'a Links) -> Vec<(&'a String, &'a Entry)> {
+        let q = self.q.as_ref().map(|q| q.to_lowercase());
+        let mut matching = links.iter()
+            .filter(|(k, v)| q.as_ref().is_none_or(|q| Self::contains_text(k, v, q)) && self.matches(v))
+            .collect::<Vec<_>>();
+        // Always ordered by key in the end, so that pages are stable between requests.
+        matching.sort_unstable_by(|(ka, a), (kb, b)| match self.sort {
+            LinkSort::Key => std::cmp::Ordering::Equal,
+            LinkSort::Used => b.metadata.used.cmp(&a.metadata.used),
+            LinkSort::Created => b.metadata.created.cmp(&a.metadata.created),
+            LinkSort::LastUsed => b.metadata.last_used.cmp(&a.metadata.last_used),
+        }.then_with(|| ka.cmp(kb)));
+        matching
+    }
+
+    /// The requested page of the selected links.
+    fn page<T>(&self, selected: impl IntoIterator<Item = T>) -> impl Iterator<Item = T> {
+        selected.into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+    }
+
+    /// Whether the key, destination or title contains the lowercased text.
+    fn contains_text(key: &str, entry: &Entry, q: &str) -> bool {
+        [Some(key), Some(entry.link.as_str()), entry.title.as_deref()].into_iter()
+            .flatten()
+            .any(|s| s.to_lowercase().contains(q))
+    }
+
+    fn matches(&self, entry: &Entry) -> bool {
+        self.tag.as_ref().is_none_or(|tag| entry.tags.contains(tag))
+            && self.created_after.is_none_or(|t| entry.metadata.created >= t)
+            && self.created_before.is_none_or(|t| entry.metadata.created < t)
+            && self.used_min.is_none_or(|n| entry.metadata.used >= n)
+            && self.source.as_ref().is_none_or(|s| entry.metadata.source.as_ref() == Some(s))
+            && self.dead.is_none_or(|dead| entry.is_dead() == dead)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct GetLinksResponse {
+    /// Number of links matching the filters, across all pages.
+    total: usize,
+    /// Sum of the click counts of all matching links.
+    total_clicks: u64,
+    /// Number of distinct destinations of all matching links.
+    distinct_targets: usize,
+    /// The requested page of matching links, in the requested order.
+    items: Vec<ResponseEntry>,
+}
+
+#[tracing::instrument(skip_all, fields(count, outcome))]
+async fn get_links(
+    State(state): State<AppState>,
+    caller: Caller,
+    view: ClickView,
+    Query(query): Query<GetLinksQuery>,
+) -> Jsend<GetLinksResponse, ()> {
+    let links = state.links.read().await;
+    let mut matching = query.select(&links);
+    // Links the caller may not access are left out, including from the totals.
+    matching.retain(|(_, v)| caller.may_access(&v.acl));
+
+    let total_clicks = view.apply(matching.iter().map(|(_, v)| v.metadata.used).sum());
+    let distinct_targets = matching.iter()
+        .map(|(_, v)| v.link.as_str())
+        .collect::<std::collections::HashSet<_>>()
+        .len();
+    let items = query.page(&matching)
+        .map(|&(k, v)| view.entry(k.clone(), v.clone()))
+        .collect::<Vec<_>>();
+
+    tracing::Span::current().record("count", items.len());
+    Jsend::Success(GetLinksResponse {
+        total: matching.len(),
+        total_clicks,
+        distinct_targets,
+        items,
+    }).record_outcome()
+}
+
+#[derive(Serialize, Deserialize)]
+struct TargetResponse {
+    link: String,
+    /// Keys pointing at the link, the primary key first.
+    keys: Vec<String>,
+    /// Sum of the click counts of all keys.
+    used: u64,
+}
+
+/// List every distinct destination with the keys pointing at it, e.g. to find links with
+/// many or duplicate keys. Destinations with the most keys come first.
+#[tracing::instrument(skip_all, fields(count, outcome))]
+async fn get_targets(
+    _: Authorized,
+    State(state): State<AppState>,
+    view: ClickView,
+) -> Jsend<Vec<TargetResponse>, ()> {
+    let links = state.links.read().await;
+    let mut targets = links.aliases_by_link().into_iter()
+        .map(|(link, keys)| TargetResponse {
+            link: link.to_string(),
+            used: view.apply(keys.iter().filter_map(|k| links.get(k)).map(|e| e.metadata.used).sum()),
+            keys: keys.into_iter().map(String::from).collect(),
+        })
+        .collect::<Vec<_>>();
+    drop(links);
+    targets.sort_unstable_by(|a, b| b.keys.len().cmp(&a.keys.len()).then_with(|| a.link.cmp(&b.link)));
+
+    tracing::Span::current().record("count", targets.len());
+    Jsend::Success(targets).record_outcome()
+}
+
+#[derive(Serialize, Deserialize)]
+struct DeadLink {
+    key: String,
+    link: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CheckLinksResponse {
+    /// Number of links checked.
+    checked: usize,
+    /// Links whose destination didn't respond, ordered by key.
+    dead: Vec<DeadLink>,
+}
+
+/// Probe the destinations of links and record whether they are still alive.
+/// 
+/// Takes the same filters and paging as `get_links`, so large instances can be checked in
+/// batches. Internal links are skipped. Probes run concurrently up to the configured limit,
+/// each bounded by the fetch timeout, and the store is not locked while they run.
+#[tracing::instrument(skip_all, fields(checked, dead, outcome))]
+async fn check_links(
+    _: Authorized,
+    _: Writable,
+    State(state): State<AppState>,
+    Query(query): Query<GetLinksQuery>,
+) -> Jsend<CheckLinksResponse, ()> {
+    let targets = {
+        let links = state.links.read().await;
+        let matching = query.select(&links).into_iter()
+            .filter(|(_, v)| v.internal_target().is_none())
+            .map(|(k, v)| (k.clone(), v.link.clone()));
+        query.page(matching).collect::<Vec<_>>()
+    };
+
+    let mut results = futures_util::stream::iter(targets)
+        .map(|(key, link)| async {
+            let url = crate::links::with_default_scheme(&link, &state.config.default_scheme);
+            let alive = state.prober.is_reachable(&url).await;
+            (key, link, alive)
+        })
+        .buffer_unordered(state.prober.concurrency())
+        .collect::<Vec<_>>().await;
+    results.sort_unstable_by(|(a, ..), (b, ..)| a.cmp(b));
+
+    async {
+        let checked_at = state.clock.now();
+        let mut links = state.links.write().await;
+        for (key, link, alive) in &results {
+            match links.get_mut(key) {
+                // The link may have been removed or replaced in the meantime.
+                Some(entry) if entry.link == *link => {
+                    entry.alive = Some(*alive);
+                    entry.last_checked = Some(checked_at);
+                },
+                _ => {}
+            }
+        }
+        state.save_links(links).await
+            .map_err(|_| "Could not save link status: IO error".to_string())?;
+
+        let dead = results.iter()
+            .filter(|(.., alive)| !alive)
+            .map(|(key, link, _)| DeadLink { key: key.clone(), link: link.clone() })
+            .collect::<Vec<_>>();
+        tracing::Span::current().record("checked", results.len());
+        tracing::Span::current().record("dead", dead.len());
+        Jsend::Success(CheckLinksResponse { checked: results.len(), dead })
+    }.await.record_outcome()
+}
+
+/// Full dataset contained in a signed export bundle.
+#[derive(Serialize, Deserialize)]
+struct ExportData {
+    exported_at: DateTime<Utc>,
+    links: HashMap<String, Entry>,
+}
+
+/// Export bundle as returned by `export_signed`.
+/// 
+/// The signature covers the JSON serialization of `data`, see [`crate::signing`]. Object keys
+/// are serialized in sorted order, so the signature can be checked after parsing the bundle.
+#[derive(Serialize, Deserialize)]
+struct SignedBundle {
+    data: serde_json::Value,
+    #[serde(default)]
+    signature: Option<String>,
+}
+
+/// Export all links, signed with the configured export secret.
+#[tracing::instrument(skip_all, fields(count, outcome))]
+async fn export_signed(
+    _: Authorized,
+    State(state): State<AppState>,
+) -> Response {
+    let Some(secret) = &state.config.export_secret else {
+        return Jsend::<(), _>::Fail("Signed export is not enabled".to_string()).record_outcome().into_response();
+    };
+    let data = ExportData {
+        exported_at: state.clock.now(),
+        links: state.links.read().await.iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect(),
+    };
+    tracing::Span::current().record("count", data.links.len());
+
+    let bundle = serde_json::to_value(&data)
+        .and_then(|data| Ok(SignedBundle { signature: Some(crate::signing::sign(secret, &serde_json::to_vec(&data)?)), data }));
+    match bundle {
+        Ok(bundle) => {
+            tracing::Span::current().record("outcome", "success");
+            Json(bundle).into_response()
+        },
+        Err(e) => Jsend::<(), ()>::Error(format!("Could not serialize links: {e}")).record_outcome().into_response()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RestoreResponse {
+    /// Number of links in the store after restoring.
+    restored: usize,
+    /// Number of protected links kept that the bundle doesn't contain.
+    #[serde(default)]
+    kept: usize,
+}
+
+/// Replace all links with those in a bundle from `export_signed`. Protected links missing
+/// from the bundle are kept, unless `?force=true` is given.
+/// 
+/// Bundles without a valid signature are rejected with `400`, leaving the store unchanged.
+#[tracing::instrument(skip_all, fields(outcome))]
+async fn restore_signed(
+    _: Authorized,
+    _: Writable,
+    State(state): State<AppState>,
+    Query(query): Query<ForceQuery>,
+    body: Bytes,
+) -> Response {
+    let Some(secret) = &state.config.export_secret else {
+        return Jsend::<(), _>::Fail("Signed restore is not enabled".to_string()).record_outcome().into_response();
+    };
+    let bad_request = |reason: &str| Jsend::<(), _>::Fail(reason.to_string())
+        .with_status(StatusCode::BAD_REQUEST)
+        .record_outcome()
+        .into_response();
+
+    let Ok(bundle) = serde_json::from_slice::<SignedBundle>(&body) else {
+        return bad_request("Bundle is not valid JSON");
+    };
+    let Some(signature) = &bundle.signature else {
+        return bad_request("Bundle is not signed");
+    };
+    let signed = serde_json::to_vec(&bundle.data).unwrap_or_default();
+    if !crate::signing::verify(secret, &signed, signature) {
+        return bad_request("Bundle signature is invalid");
+    }
+    let Ok(data) = serde_json::from_value::<ExportData>(bundle.data) else {
+        return bad_request("Bundle does not contain an export");
+    };
+
+    let mut links = state.links.write().await;
+    let protected = links.iter()
+        .filter(|(key, entry)| entry.protected && !query.force && !data.links.contains_key(*key))
+        .map(|(key, entry)| (key.clone(), entry.clone()))
+        .collect::<Vec<_>>();
+    let kept = protected.len();
+    if let Err(e) = links.replace_all(data.links.into_iter().chain(protected)) {
+        return bad_request(&e);
+    }
+    let restored = links.len();
+    let res: Jsend<_, ()> = match state.save_links(links).await {
+        Ok(()) => Jsend::Success(RestoreResponse { restored, kept }),
+        Err(_) => Jsend::Error("Could not save restored links: IO error".to_string()),
+    };
+    res.record_outcome().into_response()
+}
+
+/// Value of [`ResetRequest::confirm`] required to reset the store.
+const RESET_CONFIRMATION: &str = "RESET";
+
+#[derive(Serialize, Deserialize, Default)]
+struct ResetRequest {
+    /// Must be [`RESET_CONFIRMATION`], so links aren't deleted by accident.
+    #[serde(default)]
+    confirm: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ResetResponse {
+    /// Number of links removed.
+    removed: usize,
+    /// Number of protected links kept.
+    kept: usize,
+}
+
+/// Remove all links and save the store. Protected links are kept, unless `?force=true` is
+/// given.
+/// 
+/// Requests without the confirmation are rejected with `400`, leaving the store unchanged.
+#[tracing::instrument(skip_all, fields(removed, outcome))]
+async fn reset_links(
+    _: Authorized,
+    _: Writable,
+    State(state): State<AppState>,
+    Query(query): Query<ForceQuery>,
+    JsonBody(req): JsonBody<ResetRequest>,
+) -> Response {
+    if req.confirm != RESET_CONFIRMATION {
+        let fail = format!("Confirm the reset with {{\"confirm\": \"{RESET_CONFIRMATION}\"}}");
+        return Jsend::<(), _>::Fail(fail).with_status(StatusCode::BAD_REQUEST).record_outcome().into_response();
+    }
+
+    let mut links = state.links.write().await;
+    let total = links.len();
+    let protected = links.iter()
+        .filter(|(_, entry)| entry.protected && !query.force)
+        .map(|(key, entry)| (key.clone(), entry.clone()))
+        .collect::<Vec<_>>();
+    let kept = protected.len();
+    if let Err(e) = links.replace_all(protected) {
+        return Jsend::<(), ()>::Error(e).record_outcome().into_response();
+    }
+    let removed = total - kept;
+    tracing::Span::current().record("removed", removed);
+    let res: Jsend<_, ()> = match state.save_links(links).await {
+        Ok(()) => Jsend::Success(ResetResponse { removed, kept }),
+        Err(_) => Jsend::Error("Could not save links after reset: IO error".to_string()),
+    };
+    res.record_outcome().into_response()
+}
+
+#[derive(Serialize, Deserialize)]
+struct UptimeStatsResponse {
+    started_at: DateTime<Utc>,
+    /// Redirects served since startup.
+    redirects: u64,
+    /// Accesses waiting to be applied to the link metadata.
+    queue_depth: usize,
+}
+
+/// Process-level counters for status pages.
+/// 
+/// These start over whenever the server restarts, unlike the persisted `used` count of
+/// each link.
+#[tracing::instrument(skip_all, fields(outcome))]
+async fn get_uptime_stats(
+    State(state): State<AppState>,
+) -> Jsend<UptimeStatsResponse, ()> {
+    Jsend::Success(UptimeStatsResponse {
+        started_at: state.started_at,
+        redirects: state.redirects_served.load(std::sync::atomic::Ordering::Relaxed),
+        queue_depth: state.access_event_queue.len(),
+    }).record_outcome()
+}
+
+type GetLinkCountsResponse = HashMap<String, u64>;
+/// Lightweight snapshot of the `used` counter of every link the caller may access.
+/// 
+/// Served from a snapshot kept outside of the link store, see [`AppState::link_counts`] for
+/// its consistency model, so this neither waits for nor holds up the metadata worker.
+#[tracing::instrument(skip_all, fields(count, outcome))]
+async fn get_link_counts(
+    State(state): State<AppState>,
+    caller: Caller,
+    view: ClickView,
+) -> Jsend<GetLinkCountsResponse, ()> {
+    let res = state.link_counts()
+        .iter()
+        .filter(|(_, v)| caller.may_access(&v.acl))
+        .map(|(k, v)| (k.clone(), view.apply(v.used)))
+        .collect::<HashMap<_, _>>();
+    tracing::Span::current().record("count", res.len());
+    Jsend::Success(res).record_outcome()
+}
+
+#[derive(Serialize, Deserialize)]
+struct LinksMetaResponse {
+    /// When the link data file was last modified, by this server or anyone else.
+    modified: Option<DateTime<Utc>>,
+    /// Size of the link data file in bytes.
+    size: Option<u64>,
+}
+
+/// Describe the link data file on disk, so clients can tell when the data last changed,
+/// including edits made by hand while the server was running.
+/// 
+/// Both fields are null if links are kept in memory or the file hasn't been written yet.
+#[tracing::instrument(skip_all, fields(outcome))]
+async fn get_links_meta(
+    State(state): State<AppState>,
+) -> Jsend<LinksMetaResponse, ()> {
+    async {
+        let path = &state.config.link_data_path;
+        if crate::links::is_in_memory(path) {
+            return Jsend::Success(LinksMetaResponse { modified: None, size: None });
+        }
+        let metadata = match std::fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Jsend::Success(LinksMetaResponse { modified: None, size: None });
+            },
+            Err(e) => return Jsend::Error(format!("Could not read link data file: {e}")),
+        };
+        Jsend::Success(LinksMetaResponse {
+            modified: metadata.modified().ok().map(DateTime::from),
+            size: Some(metadata.len()),
+        })
+    }.await.record_outcome()
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyRules {
+    min_length: usize,
+    max_length: usize,
+    /// Regular expression that valid keys match.
+    pattern: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ReservedKeysResponse {
+    /// Keys reserved by the server itself.
+    reserved: Vec<String>,
+    /// Keys disallowed by the configuration.
+    blacklist: Vec<String>,
+    rules: KeyRules,
+}
+
+/// Keys that can't be chosen and the rules keys must follow, for validation hints.
+#[tracing::instrument(skip_all, fields(outcome))]
+async fn get_reserved_keys(
+    State(state): State<AppState>
+) -> Jsend<ReservedKeysResponse, ()> {
+    Jsend::Success(ReservedKeysResponse {
+        reserved: RESERVED_KEYS.iter().map(|k| k.to_string()).collect(),
+        blacklist: state.config.key_blacklist.clone(),
+        rules: KeyRules {
+            min_length: MIN_KEY_LENGTH,
+            max_length: state.config.key_max_length,
+            pattern: state.config.key_charset.pattern(),
+        },
+    }).record_outcome()
+}
+
+#[tracing::instrument(skip_all, fields(key = req.key, outcome))]
+async fn validate_add_link(
+    State(state): State<AppState>,
+    JsonBody(mut req): JsonBody<AddLinkRequest>,
+) -> Jsend<(), AddLinkFailResponse> {
+    req.prepare(&state.config);
+
+    let res = match req.validate(&state).await {
+        Some(fail) => Jsend::Fail(fail),
+        None => Jsend::Success(())
+    };
+    res.record_outcome()
+}
+
+type ValidateBatchResponse = Vec<Option<AddLinkFailResponse>>;
+#[tracing::instrument(skip_all, fields(count = reqs.len(), outcome))]
+async fn validate_batch(
+    State(state): State<AppState>,
+    JsonBody(mut reqs): JsonBody<Vec<AddLinkRequest>>,
+) -> Jsend<ValidateBatchResponse, ()> {
+    for req in &mut reqs {
+        req.prepare(&state.config);
+    }
+    Jsend::Success(validate_all(&state, &reqs).await).record_outcome()
+}
+
+/// Validate a batch of requests as if they were added in order.
+/// 
+/// On top of the checks for a single request, a key is rejected if an earlier request in 
+/// the batch already claims it.
+async fn validate_all(state: &AppState, reqs: &[AddLinkRequest]) -> Vec<Option<AddLinkFailResponse>> {
+    let mut claimed = std::collections::HashSet::new();
+    let mut result = Vec::with_capacity(reqs.len());
+    for req in reqs {
+        let mut fail = req.validate(state).await;
+        if let Some(key) = &req.key {
+            let folded = if state.config.case_insensitive_keys { key.to_lowercase() } else { key.clone() };
+            if !claimed.insert(folded) {
+                let fail = fail.get_or_insert_with(AddLinkFailResponse::default);
+                fail.key.get_or_insert_with(|| "Key already used earlier in this batch".to_string());
+            }
+        }
+        result.push(fail);
+    }
+    result
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ImportQuery {
+    /// Only report what would happen, without adding or saving anything.
+    #[serde(default)]
+    dry_run: bool,
+    #[serde(default)]
+    format: ImportFormat,
+}
+
+/// Format of the body of `import_links`.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ImportFormat {
+    /// A JSON array of [`ImportLinkRequest`]s.
+    #[default]
+    Json,
+    /// One `key url` or `url` per line. Blank lines and lines starting with `#` are skipped.
+    Text,
+}
+
+/// Parse a plaintext import. Links without a key get a generated one.
+fn parse_text_import(text: &str) -> Vec<ImportLinkRequest> {
+    text.lines().enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line_number, line)| {
+            let (key, link) = match line.split_once(char::is_whitespace) {
+                Some((key, link)) => (Some(key.to_string()), link.trim()),
+                None => (None, line),
+            };
+            ImportLinkRequest {
+                request: AddLinkRequest { key, link: link.to_string(), ..Default::default() },
+                line: Some(line_number),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// A link to import, optionally with the metadata it had elsewhere.
+#[derive(Serialize, Deserialize, Default)]
+struct ImportLinkRequest {
+    #[serde(flatten)]
+    request: AddLinkRequest,
+    /// Metadata to keep instead of stamping fresh metadata, e.g. when migrating from another
+    /// shortener. Ignored if the link already exists and no key was requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<EntryMetadata>,
+    /// Line the entry was read from, for plaintext imports.
+    #[serde(skip)]
+    line: Option<usize>,
+}
+
+/// Outcome of importing a single entry.
+#[derive(Serialize, Deserialize)]
+struct ImportEntryReport {
+    /// Line of a plaintext import the entry was read from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    line: Option<usize>,
+    /// Key the link is stored under. For a dry run this is the requested key, if any.
+    key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fail: Option<AddLinkFailResponse>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ImportResponse {
+    dry_run: bool,
+    /// Number of entries that were (or would be) imported.
+    imported: usize,
+    /// One report per requested entry, in request order.
+    entries: Vec<ImportEntryReport>,
+}
+
+/// Add a batch of links at once.
+/// 
+/// Entries that fail validation are skipped and reported, the rest are added and the store 
+/// is saved once. With `?dry_run=true` only the report is produced. With `?format=text` the
+/// body is parsed as plaintext, see [`ImportFormat::Text`].
+async fn import_links(
+    _: Writable,
+    State(state): State<AppState>,
+    Query(query): Query<ImportQuery>,
+    body: Bytes,
+) -> Response {
+    let reqs = match query.format {
+        ImportFormat::Json => match Json::<Vec<ImportLinkRequest>>::from_bytes(&body) {
+            Ok(Json(reqs)) => reqs,
+            Err(rejection) => return json_rejection(rejection),
+        },
+        ImportFormat::Text => match std::str::from_utf8(&body) {
+            Ok(text) => parse_text_import(text),
+            Err(_) => return Jsend::<(), ()>::Error("Body is not valid UTF-8".to_string())
+                .with_status(StatusCode::BAD_REQUEST)
+                .into_response(),
+        },
+    };
+    import(state, query, reqs).await.into_response()
+}
+
+#[tracing::instrument(skip_all, fields(count = reqs.len(), dry_run = query.dry_run, outcome))]
+async fn import(
+    state: AppState,
+    query: ImportQuery,
+    reqs: Vec<ImportLinkRequest>,
+) -> Jsend<ImportResponse, ()> {
+    async {
+        let lines = reqs.iter().map(|r| r.line).collect::<Vec<_>>();
+        let (mut reqs, metadata): (Vec<_>, Vec<_>) = reqs.into_iter()
+            .map(|r| (r.request, r.metadata))
+            .unzip();
+        for req in &mut reqs {
+            req.prepare(&state.config);
+        }
+
+        let fails = validate_all(&state, &reqs).await;
+        let imported = fails.iter().filter(|f| f.is_none()).count();
+
+        if query.dry_run {
+            let entries = reqs.into_iter().zip(fails).zip(lines)
+                .map(|((req, fail), line)| ImportEntryReport { line, key: req.key, fail })
+                .collect();
+            return Jsend::Success(ImportResponse { dry_run: true, imported, entries });
+        }
+
+        let mut links = state.links.write().await;
+        let mut entries = Vec::with_capacity(reqs.len());
+        for (((req, mut metadata), fail), line) in reqs.into_iter().zip(metadata).zip(fails).zip(lines) {
+            if fail.is_some() {
+                entries.push(ImportEntryReport { line, key: req.key, fail });
+                continue;
+            }
+
+            let result = match req.key {
+                Some(key) => {
+                    let added = match metadata.take() {
+                        Some(metadata) => links.add_with_metadata(key.clone(), req.link, metadata),
+                        None => links.add_named(key.clone(), req.link)
+                    };
+                    AddResult::Created(key, added
+                        .map_err(|_| "Duplicate key after validation (unreachable state)".to_string())?)
+                },
+                None => links.add(req.link)?
+            };
+            let created = result.is_created();
+            let (key, entry) = result.into_parts();
+
+            if created {
+                let stored = links.get_mut(&key)
+                    .ok_or("Missing link after insertion (unreachable state)".to_string())?;
+                // Generated keys are only known after insertion, so their metadata is set afterwards.
+                if let Some(metadata) = metadata {
+                    stored.metadata = metadata;
+                }
+                stored.metadata.source.get_or_insert_with(|| SOURCE_IMPORT.to_string());
+            }
+
+            if req.password.is_some() || req.alt_target.is_some() {
+                let stored = links.get_mut(&key)
+                    .ok_or("Missing link after insertion (unreachable state)".to_string())?;
+                if let Some(password) = &req.password {
+                    stored.set_password(password)?;
+                }
+                stored.alt_target = req.alt_target;
+            }
+            if created && state.config.fetch_titles && entry.title.is_none() {
+                tokio::spawn(update_title(state.clone(), key.clone(), entry.link));
+            }
+            entries.push(ImportEntryReport { line, key: Some(key), fail: None });
+        }
+
+        state.save_links(links).await
+            .map_err(|_| "Could not import links: IO error".to_string())?;
+
+        Jsend::Success(ImportResponse { dry_run: false, imported, entries })
+    }.await.record_outcome()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, Instant};
+    use std::env::temp_dir;
+
+    use rand::{RngCore, SeedableRng};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;    
+    use crate::Config;
+
+    use super::*; 
+
+    fn cleanup(path: &Path) {
+        std::fs::remove_file(path)
+            .unwrap_or(());
+    }
+    fn random_links_path() -> PathBuf {        
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(1);
+        let suffix = rng.next_u64();
+        temp_dir().join(format!("links-{}.toml", suffix))
+    }
+
+    /// Serve a destination that responds with 200 at `/ok` and 500 at `/error`.
+    async fn setup_mock_destination() -> String {
+        let router = Router::new()
+            .route("/ok", routing::get(|| async { "ok" }))
+            .route("/error", routing::get(|| async { StatusCode::INTERNAL_SERVER_ERROR }));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+        addr
+    }
+
+    async fn setup_test_api(links_path: &Path) -> (String, mpsc::Sender<()>) {
+        setup_test_api_with_config(Config { 
+            link_data_path: PathBuf::from(links_path),
+            bind_address: "".to_string(),
+            server_base_url: "".to_string(),
+            ..Config::default()
+        }).await
+    }
+
+    async fn setup_test_api_with_config(config: Config) -> (String, mpsc::Sender<()>) {
+        setup_test_api_with_state(AppState::new(config, crate::Links::new())).await
+    }
+
+    async fn setup_test_api_with_state(state: AppState) -> (String, mpsc::Sender<()>) {
+        let router = router(&state.config).with_state(state);
+        
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let addr = format!("http://{}", listener.local_addr().unwrap());        
+
+        let (sender, mut receiver) = mpsc::channel(1);              
+
+        tokio::spawn(async move {
+            axum::serve(listener, router.into_make_service())
+                .with_graceful_shutdown(async move {
+                    tokio::select! {
+                        _ = tokio::signal::ctrl_c() => {}
+                        _ = receiver.recv() => {}
+                    }
+                })
+                .await.unwrap();
+        });
+
+        (addr, sender)
+    }
+
+    mod add_link {
+        use super::*;
+        #[tokio::test]
+        async fn without_key() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+
+            let client = reqwest::Client::new();
+
+            let res = client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: None, link: 
+                    "https://example.com".to_string(),
+                    ..Default::default()
+                })
+                .send().await.unwrap();
+
+            assert_eq!(res.status(), 201);
+
+            let body: Jsend<AddLinkSuccessResponse, AddLinkFailResponse> = res.json().await.unwrap();
+            assert!(body.is_success());
+
+            let data = body.success().unwrap();
+            assert_eq!(data.entry.link, "https://example.com");
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn with_key() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+
+            let client = reqwest::Client::new();
+
+            let res = client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: Some("test".to_string()), 
+                    link: "https://example.com".to_string(),
+                    ..Default::default()
+                })
+                .send().await.unwrap();
+
+            assert_eq!(res.status(), 201);
+            assert_eq!(res.headers()[header::LOCATION], "/api/links/test");
+
+            let body: Jsend<AddLinkSuccessResponse, AddLinkFailResponse> = res.json().await.unwrap();
+            assert!(body.is_success());
+
+            let data = body.success().unwrap();
+            assert_eq!(data.entry.link, "https://example.com");
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn non_ascii_key() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+
+            let client = reqwest::Client::new();
+
+            let res = client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: Some("café".to_string()), 
+                    link: "https://example.com".to_string(),
+                    ..Default::default()
+                })
+                .send().await.unwrap();
+
+            assert_eq!(res.status(), 201);
+            assert_eq!(res.headers()[header::LOCATION], "/api/links/caf%C3%A9");
+
+            let res = client.get(format!("{addr}/links/caf%C3%A9")).send().await.unwrap();
+            assert_eq!(res.status(), 200);
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn self_link() {
+            for self_links in [SelfLinks::Reject, SelfLinks::Internal] {
+                let (addr, shutdown) = setup_test_api_with_config(Config {
+                    link_data_path: crate::links::IN_MEMORY_PATH.into(),
+                    server_base_url: "https://Short.example/".to_string(),
+                    self_links,
+                    ..Config::default()
+                }).await;
+                let client = reqwest::Client::new();
+                let add = |key: &str, link: &str| client.post(format!("{addr}/links"))
+                    .json(&AddLinkRequest { 
+                        key: Some(key.to_string()), 
+                        link: link.to_string(),
+                        ..Default::default()
+                    })
+                    .send();
+
+                // Pointing a key at itself is rejected either way
+                let res = add("loop", "https://short.example/go/loop").await.unwrap();
+                let body: Jsend<AddLinkSuccessResponse, AddLinkFailResponse> = res.json().await.unwrap();
+                assert!(body.fail().unwrap().link.is_some(), "{self_links:?}");
+
+                add("docs", "https://example.com/docs").await.unwrap();
+                let res = add("manual", "http://short.example/docs?ref=1").await.unwrap();
+                let body: Jsend<AddLinkSuccessResponse, AddLinkFailResponse> = res.json().await.unwrap();
+                match self_links {
+                    SelfLinks::Reject => assert!(body.fail().unwrap().link.is_some()),
+                    SelfLinks::Internal => assert_eq!(body.success().unwrap().entry.link, "go:docs"),
+                }
+
+                // Other paths on the same host are regular links
+                let res = add("api-links", "https://short.example/api/links").await.unwrap();
+                assert_eq!(res.status(), 201);
+
+                shutdown.send(()).await.unwrap();
+            }
+        }
+
+        #[tokio::test]
+        async fn key_exhausted() {
+            let (addr, shutdown) = setup_test_api_with_config(Config {
+                link_data_path: crate::links::IN_MEMORY_PATH.into(),
+                key_strategy: crate::links::KeyStrategy::Slug,
+                key_max_attempts: 1,
+                ..Config::default()
+            }).await;
+            let client = reqwest::Client::new();
+            let add = |link: &str| client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { link: link.to_string(), ..Default::default() })
+                .send();
+
+            let res = add("https://example.com/guide").await.unwrap();
+            assert_eq!(res.status(), 201);
+            let res = add("https://example.org/guide").await.unwrap();
+            assert_eq!(res.status(), 500);
+            let body: Jsend<AddLinkSuccessResponse, AddLinkFailResponse> = res.json().await.unwrap();
+            assert_eq!(body.error().unwrap(), "Could not allocate a unique key");
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn max_aliases_per_url() {
+            let (addr, shutdown) = setup_test_api_with_config(Config {
+                link_data_path: crate::links::IN_MEMORY_PATH.into(),
+                max_aliases_per_url: Some(2),
+                ..Config::default()
+            }).await;
+            let client = reqwest::Client::new();
+            let add = |key: Option<&str>| client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { key: key.map(String::from), link: "https://example.com".to_string(), ..Default::default() })
+                .send();
+
+            assert_eq!(add(Some("first")).await.unwrap().status(), 201);
+            assert_eq!(add(Some("second")).await.unwrap().status(), 201);
+            let fail = add(Some("third")).await.unwrap()
+                .json::<Jsend<AddLinkSuccessResponse, AddLinkFailResponse>>().await.unwrap()
+                .fail().unwrap();
+            assert_eq!(fail.link.as_deref(), Some("Link already has the maximum of 2 keys"));
+
+            // Copies count as keys too
+            let res = client.post(format!("{addr}/links/first/copy")).send().await.unwrap();
+            assert_eq!(res.status(), 400);
+            let fail = res.json::<Jsend<CopyLinkResponse, String>>().await.unwrap().fail().unwrap();
+            assert_eq!(fail, "Link already has the maximum of 2 keys");
+
+            // Adding the link without a key returns an existing alias
+            let data = add(None).await.unwrap()
+                .json::<Jsend<AddLinkSuccessResponse, AddLinkFailResponse>>().await.unwrap()
+                .success().unwrap();
+            assert!(data.existing);
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn key_already_exists() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+
+            let client = reqwest::Client::new();
+
+            client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: Some("test".to_string()), 
+                    link: "https://example1.com".to_string(),
+                    ..Default::default()
+                })
+                .send().await.unwrap();            
+
+            let res = client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: Some("test".to_string()), 
+                    link: "https://example2.com".to_string(),
+                    ..Default::default()
+                })
+                .send().await.unwrap();   
+
+            assert_eq!(res.status(), 409);
+
+            let body: Jsend<AddLinkSuccessResponse, AddLinkFailResponse> = res.json().await.unwrap();
+            assert!(body.is_fail());
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn invalid_url() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+            let client = reqwest::Client::new();
+
+            for (link, expected) in [
+                ("https://example.com/a b", "URL cannot contain whitespace"),
+                ("https://example.com/\nfoo", "URL cannot contain control characters"),
+                ("example.com/path", "URL must start with a scheme, e.g. https://"),
+                ("https://", "Invalid URL"),
+            ] {
+                let res = client.post(format!("{addr}/links"))
+                    .json(&AddLinkRequest { 
+                        link: link.to_string(),
+                        ..Default::default()
+                    })
+                    .send().await.unwrap();
+
+                let body: Jsend<AddLinkSuccessResponse, AddLinkFailResponse> = res.json().await.unwrap();
+                let fail = body.fail().unwrap();
+                assert_eq!(fail.link.as_deref(), Some(expected), "{link:?}");
+            }
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn malformed_json() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+            let client = reqwest::Client::new();
+
+            for (body, status) in [(r#"{"link": "https://example.com""#, 400), (r#"{"key": "test"}"#, 422)] {
+                let res = client.post(format!("{addr}/links"))
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(body)
+                    .send().await.unwrap();
+                assert_eq!(res.status(), status, "{body}");
+
+                let message = res.json::<Jsend<(), ()>>().await.unwrap().error().unwrap();
+                assert!(message.starts_with("Invalid JSON body: "), "{message}");
+            }
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn expiry() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+            let client = reqwest::Client::new();
+            let expires_at = Utc::now() + chrono::Duration::days(1);
+
+            let res = client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: Some("sale".to_string()),
+                    link: "https://example.com/sale".to_string(),
+                    expires_at: Some(expires_at),
+                    on_expire: Some(OnExpire::Redirect("https://example.com".to_string())),
+                    ..Default::default()
+                })
+                .send().await.unwrap();
+            assert_eq!(res.status(), 201);
+
+            let entry = client.get(format!("{addr}/links/sale"))
+                .send().await.unwrap()
+                .json::<Jsend<GetLinkResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(entry.expires_at, Some(expires_at));
+            assert_eq!(entry.on_expire, OnExpire::Redirect("https://example.com".to_string()));
+
+            for (expires_at, on_expire) in [
+                (None, OnExpire::Delete),
+                (Some(expires_at), OnExpire::Redirect("example.com".to_string())),
+            ] {
+                let fail = client.post(format!("{addr}/links"))
+                    .json(&AddLinkRequest { 
+                        key: Some("invalid".to_string()),
+                        link: "https://example.com/invalid".to_string(),
+                        expires_at,
+                        on_expire: Some(on_expire),
+                        ..Default::default()
+                    })
+                    .send().await.unwrap()
+                    .json::<Jsend<AddLinkSuccessResponse, AddLinkFailResponse>>().await.unwrap()
+                    .fail().unwrap();
+                assert!(fail.on_expire.is_some());
+            }
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn verify_reachable_reject() {
+            let destination = setup_mock_destination().await;
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api_with_config(Config {
+                link_data_path: links_path.clone(),
+                verify_reachable: VerifyReachable::Reject,
+                ..Config::default()
+            }).await;
+            let client = reqwest::Client::new();
+
+            let res = client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { link: format!("{destination}/error"), ..Default::default() })
+                .send().await.unwrap();
+            assert_eq!(res.status(), 400);
+            let body: Jsend<AddLinkSuccessResponse, AddLinkFailResponse> = res.json().await.unwrap();
+            assert_eq!(body.fail().unwrap().link.as_deref(), Some("Link target is unreachable"));
+
+            let res = client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { link: format!("{destination}/ok"), ..Default::default() })
+                .send().await.unwrap();
+            assert_eq!(res.status(), 201);
+            let body: Jsend<AddLinkSuccessResponse, AddLinkFailResponse> = res.json().await.unwrap();
+            assert_eq!(body.success().unwrap().entry.reachable, None);
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn verify_reachable_warn() {
+            let destination = setup_mock_destination().await;
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api_with_config(Config {
+                link_data_path: links_path.clone(),
+                verify_reachable: VerifyReachable::Warn,
+                ..Config::default()
+            }).await;
+            let client = reqwest::Client::new();
+
+            for (key, path, reachable) in [("broken", "error", false), ("working", "ok", true)] {
+                let res = client.post(format!("{addr}/links"))
+                    .json(&AddLinkRequest {
+                        key: Some(key.to_string()),
+                        link: format!("{destination}/{path}"),
+                        ..Default::default()
+                    })
+                    .send().await.unwrap();
+                assert_eq!(res.status(), 201);
+
+                let entry = client.get(format!("{addr}/links/{key}"))
+                    .send().await.unwrap()
+                    .json::<Jsend<GetLinkResponse, String>>().await.unwrap()
+                    .success().unwrap();
+                assert_eq!(entry.reachable, Some(reachable));
+            }
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test(flavor = "current_thread")]
+        async fn concurrent_adds_dont_stall_runtime() {
+            const ADDS: usize = 100;
+
+            let links_path = temp_dir().join("landmower_test_concurrent_adds.toml");
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+            let client = reqwest::Client::new();
+
+            // With a single runtime thread, blocking disk I/O on it delays this heartbeat.
+            let heartbeat = tokio::spawn(async {
+                let mut max_gap = Duration::ZERO;
+                let mut last = Instant::now();
+                for _ in 0..100 {
+                    tokio::time::sleep(Duration::from_millis(2)).await;
+                    max_gap = max_gap.max(last.elapsed());
+                    last = Instant::now();
+                }
+                max_gap
+            });
+
+            let responses = futures_util::future::join_all((0..ADDS).map(|i| {
+                client.post(format!("{addr}/links"))
+                    .json(&AddLinkRequest {
+                        key: Some(format!("key{i}")),
+                        link: format!("https://example.com/{i}"),
+                        ..Default::default()
+                    })
+                    .send()
+            })).await;
+            for res in responses {
+                assert_eq!(res.unwrap().status(), 201);
+            }
+            assert!(heartbeat.await.unwrap() < Duration::from_millis(250));
+
+            // The last write wins, so no link is lost.
+            let saved = crate::Links::<EntryMetadata>::load(&links_path).unwrap();
+            assert_eq!(saved.len(), ADDS);
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn webhook() {
+            let (tx, mut rx) = mpsc::channel(4);
+            let receiver = Router::new().route("/hook", routing::post(|headers: axum::http::HeaderMap, body: Bytes| async move {
+                tx.send((headers.get(webhook::SIGNATURE_HEADER).cloned(), body)).await.unwrap();
+                StatusCode::NO_CONTENT
+            }));
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let webhook_url = format!("http://{}/hook", listener.local_addr().unwrap());
+            tokio::spawn(async move {
+                axum::serve(listener, receiver).await.unwrap();
+            });
+
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api_with_config(Config {
+                link_data_path: links_path.clone(),
+                server_base_url: "short.example/".to_string(),
+                webhook_url: Some(webhook_url),
+                webhook_secret: Some("secret".to_string()),
+                ..Config::default()
+            }).await;
+            let client = reqwest::Client::new();
+
+            client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { key: Some("test".to_string()), link: "https://example.com".to_string(), ..Default::default() })
+                .send().await.unwrap();
+
+            let (signature, body) = rx.recv().await.unwrap();
+            assert_eq!(signature.unwrap(), crate::signing::sign("secret", &body).as_str());
+            let event = serde_json::from_slice::<webhook::Event>(&body).unwrap();
+            assert_eq!(event.event, webhook::EventKind::Created);
+            assert_eq!(event.key, "test");
+            assert_eq!(event.link, "https://example.com");
+            assert_eq!(event.short_url, "short.example/test");
+
+            // Deletions are only sent if enabled
+            client.delete(format!("{addr}/links/test")).send().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert!(rx.try_recv().is_err());
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn key_too_long() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api_with_config(Config {
+                link_data_path: links_path.clone(),
+                key_max_length: 8,
+                ..Config::default()
+            }).await;
+
+            let res = reqwest::Client::new().post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: Some("waytoolong".to_string()), 
+                    link: "https://example.com".to_string(),
+                    ..Default::default()
+                })
+                .send().await.unwrap();   
+
+            assert_eq!(res.status(), 400);
+            let body: Jsend<AddLinkSuccessResponse, AddLinkFailResponse> = res.json().await.unwrap();
+            let fail = body.fail().unwrap();
+            assert_eq!(fail.key.as_deref(), Some("Key cannot be more than 8 characters"));
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn custom_key_charset() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api_with_config(Config {
+                link_data_path: links_path.clone(),
+                key_charset: "_.".parse().unwrap(),
+                ..Config::default()
+            }).await;
+            let client = reqwest::Client::new();
+
+            let res = client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: Some("team.docs".to_string()), 
+                    link: "https://example.com/docs".to_string(),
+                    ..Default::default()
+                })
+                .send().await.unwrap();   
+            let body: Jsend<AddLinkSuccessResponse, AddLinkFailResponse> = res.json().await.unwrap();
+            assert!(body.is_success());
+
+            let res = client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: Some("team-docs".to_string()), 
+                    link: "https://example.com/team".to_string(),
+                    ..Default::default()
+                })
+                .send().await.unwrap();   
+            let body: Jsend<AddLinkSuccessResponse, AddLinkFailResponse> = res.json().await.unwrap();
+            let fail = body.fail().unwrap();
+            assert_eq!(fail.key.as_deref(), Some("Key can only contain 0-9, A-Z, a-z, _ or ."));
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn link_already_exists() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+
+            let client = reqwest::Client::new();
+
+            let res = client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: None, 
+                    link: "https://example.com".to_string(),
+                    ..Default::default()
+                })
+                .send().await.unwrap();
+
+            let data = res
+                .json::<Jsend<AddLinkSuccessResponse, AddLinkFailResponse>>().await.unwrap()
+                .success().unwrap();
+            assert!(!data.existing);
+            let key1 = data.key;
+
+            let res = client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: None, 
+                    link: "https://example.com".to_string(),
+                    ..Default::default()
+                })
+                .send().await.unwrap();
+
+            assert_eq!(res.status(), 200);
+
+            let body: Jsend<AddLinkSuccessResponse, AddLinkFailResponse> = res.json().await.unwrap();
+            assert!(body.is_success());
+
+            let data = body.success().unwrap();
+            assert_eq!(data.key, key1);
+            assert!(data.existing);
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }        
+
+        #[tokio::test]
+        async fn normalized_link_already_exists() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api_with_config(Config {
+                link_data_path: links_path.clone(),
+                normalize_urls: true,
+                ..Config::default()
+            }).await;
+
+            let client = reqwest::Client::new();
+
+            let mut keys = vec![];
+            for link in ["https://example.com", "https://Example.com:443/"] {
+                let data = client.post(format!("{addr}/links"))
+                    .json(&AddLinkRequest { key: None, link: link.to_string(), ..Default::default() })
+                    .send().await.unwrap()
+                    .json::<Jsend<AddLinkSuccessResponse, AddLinkFailResponse>>().await.unwrap()
+                    .success().unwrap();
+
+                assert_eq!(data.entry.link, "https://example.com");
+                keys.push(data.key);
+            }
+            assert_eq!(keys[0], keys[1]);
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn with_password() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+
+            let client = reqwest::Client::new();
+
+            let res = client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: Some("secret".to_string()), 
+                    link: "https://example.com".to_string(),
+                    password: Some("hunter2".to_string()),
+                    ..Default::default()
+                })
+                .send().await.unwrap();
+
+            let body = res.text().await.unwrap();
+            assert!(!body.contains("hunter2"));
+            assert!(!body.contains("argon2"));
+
+            let data = serde_json::from_str::<Jsend<AddLinkSuccessResponse, AddLinkFailResponse>>(&body)
+                .unwrap()
+                .success().unwrap();
+            assert_eq!(data.entry.password, None);
+
+            let data = client.get(format!("{addr}/links/secret"))
+                .send().await.unwrap()
+                .json::<Jsend<GetLinkResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert!(data.password_protected);
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn internal_link() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+
+            let client = reqwest::Client::new();
+
+            let add = |key: &str, link: &str| client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: Some(key.to_string()), 
+                    link: link.to_string(),
+                    ..Default::default()
+                })
+                .send();
+
+            let body = add("docs", "https://example.com/docs").await.unwrap()
+                .json::<Jsend<AddLinkSuccessResponse, AddLinkFailResponse>>().await.unwrap();
+            assert!(body.is_success());
+
+            let body = add("alias", "go:docs").await.unwrap()
+                .json::<Jsend<AddLinkSuccessResponse, AddLinkFailResponse>>().await.unwrap();
+            assert!(body.is_success());
+
+            let body = add("dangling", "go:nothing").await.unwrap()
+                .json::<Jsend<AddLinkSuccessResponse, AddLinkFailResponse>>().await.unwrap();
+            assert!(body.fail().unwrap().link.is_some());
+
+            let body = add("self", "go:self").await.unwrap()
+                .json::<Jsend<AddLinkSuccessResponse, AddLinkFailResponse>>().await.unwrap();
+            assert!(body.fail().unwrap().link.is_some());
+
+            // A chain is unlocked with one password, so an alias of a locked key can't add another
+            let add_locked = |key: &str, link: &str, password: &str| client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest {
+                    key: Some(key.to_string()),
+                    link: link.to_string(),
+                    password: Some(password.to_string()),
+                    ..Default::default()
+                })
+                .send();
+
+            let body = add_locked("locked", "https://example.com/locked", "hunter2").await.unwrap()
+                .json::<Jsend<AddLinkSuccessResponse, AddLinkFailResponse>>().await.unwrap();
+            assert!(body.is_success());
+
+            let body = add_locked("locked-alias", "go:locked", "other").await.unwrap()
+                .json::<Jsend<AddLinkSuccessResponse, AddLinkFailResponse>>().await.unwrap();
+            assert!(body.fail().unwrap().password.is_some());
+
+            let body = add_locked("alias-alias", "go:alias", "other").await.unwrap()
+                .json::<Jsend<AddLinkSuccessResponse, AddLinkFailResponse>>().await.unwrap();
+            assert!(body.is_success());
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn with_alt_target() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+            let client = reqwest::Client::new();
+
+            let res = client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: Some("docs".to_string()), 
+                    link: "https://example.com/docs".to_string(),
+                    alt_target: Some("not a url".to_string()),
+                    ..Default::default()
+                })
+                .send().await.unwrap();
+            let body: Jsend<AddLinkSuccessResponse, AddLinkFailResponse> = res.json().await.unwrap();
+            assert!(body.fail().unwrap().alt_target.is_some());
+
+            let res = client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: Some("docs".to_string()), 
+                    link: "https://example.com/docs".to_string(),
+                    alt_target: Some("https://example.com/openapi.json".to_string()),
+                    ..Default::default()
+                })
+                .send().await.unwrap();
+            assert_eq!(res.status(), 201);
+
+            let entry = client.get(format!("{addr}/links/docs"))
+                .send().await.unwrap()
+                .json::<Jsend<GetLinkResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(entry.alt_target.as_deref(), Some("https://example.com/openapi.json"));
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn body_too_large() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api_with_config(Config {
+                link_data_path: links_path.clone(),
+                max_body_bytes: 64,
+                ..Config::default()
+            }).await;
+
+            let client = reqwest::Client::new();
+
+            let res = client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: None, 
+                    link: format!("https://example.com/{}", "a".repeat(128)),
+                    ..Default::default()
+                })
+                .send().await.unwrap();
+
+            assert_eq!(res.status(), 413);
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn existing_acl() {
+            let mut links = crate::Links::new();
+            let (key, _) = links.add("https://example.com".to_string()).unwrap().into_parts();
+            links.get_mut(&key).unwrap().acl = vec!["alice".to_string()];
+            let (addr, shutdown) = setup_test_api_with_state(AppState::new(Config {
+                link_data_path: crate::links::IN_MEMORY_PATH.into(),
+                api_token: Some("admin-secret".to_string()),
+                user_tokens: [("alice", "alice-secret"), ("bob", "bob-secret")].into_iter()
+                    .map(|(name, token)| (name.to_string(), token.to_string()))
+                    .collect(),
+                ..Config::default()
+            }, links)).await;
+            let add = |token: &str| reqwest::Client::new().post(format!("{addr}/links"))
+                .bearer_auth(token)
+                .json(&AddLinkRequest { link: "https://example.com".to_string(), ..Default::default() })
+                .send();
+
+            let res = add("bob-secret").await.unwrap();
+            assert_eq!(res.status(), 409);
+            let body = res.text().await.unwrap();
+            assert!(!body.contains(&key), "{body}");
+
+            for token in ["alice-secret", "admin-secret"] {
+                let res = add(token).await.unwrap();
+                assert_eq!(res.status(), 200, "{token}");
+                let data = res.json::<Jsend<AddLinkSuccessResponse, AddLinkFailResponse>>().await.unwrap()
+                    .success().unwrap();
+                assert_eq!(data.key, key);
+            }
+
+            shutdown.send(()).await.unwrap();
+        }
+    }
+
+    mod get_link {
+        use super::*;
+        #[tokio::test]
+        async fn base_case() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+    
+            let client = reqwest::Client::new();
+
+            client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: Some("test".to_string()), 
+                    link: "https://example.com".to_string(),
+                    ..Default::default()
+                })
+                .send().await.unwrap();
+
+            let res = client.get(format!("{addr}/links/test"))
+                .send().await.unwrap();
+            assert_eq!(res.status(), 200);
+
+            let body = res.json::<Jsend<GetLinkResponse, String>>().await.unwrap();
+            assert!(body.is_success());
+            
+            let data = body.success().unwrap();
+            assert_eq!(data.link, "https://example.com");
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn not_found() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+    
+            let client = reqwest::Client::new();
+    
+            let res = client.get(format!("{addr}/links/test"))
+                .send().await.unwrap();
+            assert_eq!(res.status(), 404);
+
+            let body = res.json::<Jsend<GetLinkResponse, String>>().await.unwrap();
+            assert!(body.is_fail()); 
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        /// Serve a link that only `alice` may access, with authentication enabled.
+        pub(super) async fn setup_acl() -> (String, mpsc::Sender<()>) {
+            let (addr, shutdown) = setup_test_api_with_config(Config {
+                link_data_path: crate::links::IN_MEMORY_PATH.into(),
+                api_token: Some("admin-secret".to_string()),
+                user_tokens: [("alice", "alice-secret"), ("bob", "bob-secret")].into_iter()
+                    .map(|(name, token)| (name.to_string(), token.to_string()))
+                    .collect(),
+                ..Config::default()
+            }).await;
+
+            let res = reqwest::Client::new().post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: Some("private".to_string()), 
+                    link: "https://example.com".to_string(),
+                    acl: vec!["alice".to_string()],
+                    ..Default::default()
+                })
+                .send().await.unwrap();
+            assert_eq!(res.status(), 201);
+            (addr, shutdown)
+        }
+
+        #[tokio::test]
+        async fn acl() {
+            let (addr, shutdown) = setup_acl().await;
+            let client = reqwest::Client::new();
+
+            for token in ["alice-secret", "admin-secret"] {
+                let res = client.get(format!("{addr}/links/private"))
+                    .bearer_auth(token)
+                    .send().await.unwrap();
+                assert_eq!(res.status(), 200, "{token}");
+                let data = res.json::<Jsend<GetLinkResponse, String>>().await.unwrap().success().unwrap();
+                assert_eq!(data.acl, ["alice"]);
+            }
+
+            for token in [Some("bob-secret"), Some("wrong"), None] {
+                let mut req = client.get(format!("{addr}/links/private"));
+                if let Some(token) = token {
+                    req = req.bearer_auth(token);
+                }
+                let res = req.send().await.unwrap();
+                assert_eq!(res.status(), 403, "{token:?}");
+                assert!(res.json::<Jsend<GetLinkResponse, String>>().await.unwrap().is_fail());
+            }
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn acl_unknown_user() {
+            let (addr, shutdown) = setup_acl().await;
+
+            let res = reqwest::Client::new().post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: Some("other".to_string()), 
+                    link: "https://example.com/other".to_string(),
+                    acl: vec!["carol".to_string()],
+                    ..Default::default()
+                })
+                .send().await.unwrap();
+            let body: Jsend<AddLinkSuccessResponse, AddLinkFailResponse> = res.json().await.unwrap();
+            assert!(body.fail().unwrap().acl.is_some());
+
+            shutdown.send(()).await.unwrap();
+        }
+    }    
+    
+    mod delete_link {
+        use super::*;
+
+        #[tokio::test]
+        async fn protected() {
+            let mut links = crate::Links::new();
+            links.add_named("home".to_string(), "https://example.com".to_string()).unwrap();
+            let state = AppState::new(Config {
+                link_data_path: crate::links::IN_MEMORY_PATH.into(),
+                api_token: Some("secret".to_string()),
+                ..Config::default()
+            }, links);
+            let (addr, shutdown) = setup_test_api_with_state(state.clone()).await;
+            let client = reqwest::Client::new();
+            let protect = || client.post(format!("{addr}/links/home/protect"))
+                .bearer_auth("secret")
+                .send();
+            let delete = |query: &str| client.delete(format!("{addr}/links/home{query}"))
+                .bearer_auth("secret")
+                .send();
+
+            let data = protect().await.unwrap()
+                .json::<Jsend<ProtectLinkResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert!(data.protected);
+
+            let res = delete("").await.unwrap();
+            assert_eq!(res.status(), 409);
+            assert!(res.json::<Jsend<(), String>>().await.unwrap().is_fail());
+            assert!(state.links.read().await.get("home").is_some());
+
+            // Toggling again lifts the protection
+            let data = protect().await.unwrap()
+                .json::<Jsend<ProtectLinkResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert!(!data.protected);
+            protect().await.unwrap();
+
+            let res = delete("?force=true").await.unwrap();
+            assert_eq!(res.status(), 200);
+            assert!(state.links.read().await.get("home").is_none());
+
+            let res = client.post(format!("{addr}/links/home/protect")).bearer_auth("secret").send().await.unwrap();
+            assert_eq!(res.status(), 404);
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn base_case() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+    
+            let client = reqwest::Client::new();
+    
+            client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: Some("test".to_string()), 
+                    link: "https://example.com".to_string(),
+                    ..Default::default()
+                })
+                .send().await.unwrap();
+    
+            let res = client.delete(format!("{addr}/links/test"))
+                .send().await.unwrap();
+    
+            assert_eq!(res.status(), 200);
+
+            let body = res.json::<Jsend<(), String>>().await.unwrap();
+            assert!(body.is_success());
+    
+            let res = client.get(format!("{addr}/links/test"))
+                .send().await.unwrap();
+            assert_eq!(res.status(), 404);
+
+            let body = res.json::<Jsend<GetLinkResponse, String>>().await.unwrap();
+            assert!(body.is_fail());        
+    
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn not_found() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+    
+            let client = reqwest::Client::new();
+    
+            let res = client.delete(format!("{addr}/links/test"))
+                .send().await.unwrap();
+            assert_eq!(res.status(), 404);
+            
+            let body = res.json::<Jsend<(), String>>().await.unwrap();
+            assert!(body.is_fail());
+    
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn acl() {
+            let (addr, shutdown) = super::get_link::setup_acl().await;
+            let client = reqwest::Client::new();
+
+            let res = client.delete(format!("{addr}/links/private"))
+                .bearer_auth("bob-secret")
+                .send().await.unwrap();
+            assert_eq!(res.status(), 403);
+
+            let res = client.delete(format!("{addr}/links/private"))
+                .bearer_auth("alice-secret")
+                .send().await.unwrap();
+            assert_eq!(res.status(), 200);
+            assert!(res.json::<Jsend<(), String>>().await.unwrap().is_success());
+
+            // Admins may delete links restricted to other users
+            client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: Some("private".to_string()), 
+                    link: "https://example.com".to_string(),
+                    acl: vec!["alice".to_string()],
+                    ..Default::default()
+                })
+                .send().await.unwrap();
+            let res = client.delete(format!("{addr}/links/private"))
+                .bearer_auth("admin-secret")
+                .send().await.unwrap();
+            assert_eq!(res.status(), 200);
+
+            shutdown.send(()).await.unwrap();
+        }
+    }
+
+    mod validate_batch {
+        use super::*;
+        #[tokio::test]
+        async fn base_case() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+    
+            let client = reqwest::Client::new();
+
+            client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: Some("taken".to_string()), 
+                    link: "https://example.com".to_string(),
+                    ..Default::default()
+                })
+                .send().await.unwrap();
+
+            let batch = vec![
+                AddLinkRequest { key: Some("new1".to_string()), link: "https://example.com".to_string(), ..Default::default() },
+                AddLinkRequest { key: Some("taken".to_string()), link: "https://example.com".to_string(), ..Default::default() },
+                AddLinkRequest { key: None, link: "".to_string(), ..Default::default() },
+                AddLinkRequest { key: Some("new2".to_string()), link: "https://example.com".to_string(), ..Default::default() },
+                AddLinkRequest { key: Some("new1".to_string()), link: "https://example.com".to_string(), ..Default::default() },
+            ];
+    
+            let res = client.post(format!("{addr}/validate/batch"))
+                .json(&batch)
+                .send().await.unwrap();
+            assert_eq!(res.status(), 200);
+
+            let data = res.json::<Jsend<ValidateBatchResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(data.len(), 5);
+            assert!(data[0].is_none());
+            assert!(data[1].as_ref().unwrap().key.is_some());
+            assert!(data[2].as_ref().unwrap().link.is_some());
+            assert!(data[3].is_none());
+            assert!(data[4].as_ref().unwrap().key.is_some());
+    
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+    }
+
+    mod import_links {
+        use super::*;
+
+        fn batch() -> Vec<AddLinkRequest> {
+            vec![
+                AddLinkRequest { key: Some("new1".to_string()), link: "https://example.com".to_string(), ..Default::default() },
+                AddLinkRequest { key: Some("taken".to_string()), link: "https://example.com".to_string(), ..Default::default() },
+                AddLinkRequest { key: None, link: "".to_string(), ..Default::default() },
+                AddLinkRequest { key: None, link: "https://example.org".to_string(), ..Default::default() },
+            ]
+        }
+
+        async fn add_taken(client: &reqwest::Client, addr: &str) {
+            client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: Some("taken".to_string()), 
+                    link: "https://example.net".to_string(),
+                    ..Default::default()
+                })
+                .send().await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn base_case() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+            let client = reqwest::Client::new();
+            add_taken(&client, &addr).await;
+
+            let res = client.post(format!("{addr}/links/import"))
+                .json(&batch())
+                .send().await.unwrap();
+            assert_eq!(res.status(), 200);
+
+            let data = res.json::<Jsend<ImportResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert!(!data.dry_run);
+            assert_eq!(data.imported, 2);
+            assert_eq!(data.entries[0].key.as_deref(), Some("new1"));
+            assert!(data.entries[0].fail.is_none());
+            assert!(data.entries[1].fail.as_ref().unwrap().key.is_some());
+            assert!(data.entries[2].fail.as_ref().unwrap().link.is_some());
+            let generated = data.entries[3].key.clone().unwrap();
+
+            let res = client.get(format!("{addr}/links/{generated}")).send().await.unwrap();
+            let entry = res.json::<Jsend<GetLinkResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(entry.link, "https://example.org");
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn text_format() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+            let client = reqwest::Client::new();
+            add_taken(&client, &addr).await;
+
+            let body = "# Team links\n\
+                docs https://example.com/docs\n\
+                \n\
+                https://example.org\n  \
+                taken   https://example.com/taken\n\
+                bad not a url\n";
+            let res = client.post(format!("{addr}/links/import?format=text"))
+                .body(body)
+                .send().await.unwrap();
+            assert_eq!(res.status(), 200);
+
+            let data = res.json::<Jsend<ImportResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(data.imported, 2);
+            let lines = data.entries.iter().map(|e| e.line.unwrap()).collect::<Vec<_>>();
+            assert_eq!(lines, [2, 4, 5, 6]);
+            assert_eq!(data.entries[0].key.as_deref(), Some("docs"));
+            assert!(data.entries[0].fail.is_none());
+            assert!(data.entries[1].key.is_some());
+            assert!(data.entries[1].fail.is_none());
+            assert!(data.entries[2].fail.as_ref().unwrap().key.is_some());
+            assert!(data.entries[3].fail.as_ref().unwrap().link.is_some());
+
+            let res = client.get(format!("{addr}/links/docs")).send().await.unwrap();
+            let entry = res.json::<Jsend<GetLinkResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(entry.link, "https://example.com/docs");
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn dry_run() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+            let client = reqwest::Client::new();
+            add_taken(&client, &addr).await;
+
+            let res = client.post(format!("{addr}/links/import?dry_run=true"))
+                .json(&batch())
+                .send().await.unwrap();
+            assert_eq!(res.status(), 200);
+
+            let data = res.json::<Jsend<ImportResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert!(data.dry_run);
+            assert_eq!(data.imported, 2);
+            assert!(data.entries[0].fail.is_none());
+            assert!(data.entries[1].fail.as_ref().unwrap().key.is_some());
+            assert!(data.entries[2].fail.as_ref().unwrap().link.is_some());
+            assert!(data.entries[3].fail.is_none());
+
+            let res = client.get(format!("{addr}/links/new1")).send().await.unwrap();
+            assert!(res.json::<Jsend<GetLinkResponse, String>>().await.unwrap().is_fail());
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+        #[tokio::test]
+        async fn with_metadata() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+            let client = reqwest::Client::new();
+
+            let created = "2020-01-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+            let metadata = EntryMetadata { used: 42, last_used: created, created, source: Some("legacy".to_string()) };
+            let batch = vec![
+                ImportLinkRequest {
+                    request: AddLinkRequest { key: Some("old1".to_string()), link: "https://example.com".to_string(), ..Default::default() },
+                    metadata: Some(metadata.clone()),
+                    ..Default::default()
+                },
+                ImportLinkRequest {
+                    request: AddLinkRequest { key: None, link: "https://example.org".to_string(), ..Default::default() },
+                    metadata: Some(metadata.clone()),
+                    ..Default::default()
+                },
+            ];
+
+            let data = client.post(format!("{addr}/links/import"))
+                .json(&batch)
+                .send().await.unwrap()
+                .json::<Jsend<ImportResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(data.imported, 2);
+
+            for report in data.entries {
+                let key = report.key.unwrap();
+                let entry = client.get(format!("{addr}/links/{key}"))
+                    .send().await.unwrap()
+                    .json::<Jsend<GetLinkResponse, String>>().await.unwrap()
+                    .success().unwrap();
+                assert_eq!(entry.metadata, metadata);
+            }
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+    }
+
+    mod rotate_link {
+        use super::*;
+
+        #[tokio::test]
+        async fn base_case() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api_with_config(Config {
+                link_data_path: links_path.clone(),
+                server_base_url: "short.example/".to_string(),
+                ..Config::default()
+            }).await;
+            let client = reqwest::Client::new();
+
+            client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { key: Some("test".to_string()), link: "https://example.com".to_string(), ..Default::default() })
+                .send().await.unwrap();
+
+            let data = client.post(format!("{addr}/links/test/rotate"))
+                .send().await.unwrap()
+                .json::<Jsend<RotateLinkResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert_ne!(data.key, "test");
+            assert_eq!(data.short_url, format!("short.example/{}", data.key));
+
+            let res = client.get(format!("{addr}/links/test")).send().await.unwrap();
+            assert!(res.json::<Jsend<GetLinkResponse, String>>().await.unwrap().is_fail());
+
+            let entry = client.get(format!("{addr}/links/{}", data.key))
+                .send().await.unwrap()
+                .json::<Jsend<GetLinkResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(entry.link, "https://example.com");
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn not_found() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+
+            let res = reqwest::Client::new().post(format!("{addr}/links/nope/rotate"))
+                .send().await.unwrap();
+            assert_eq!(res.status(), 404);
+            assert!(res.json::<Jsend<RotateLinkResponse, String>>().await.unwrap().is_fail());
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn protected() {
+            let mut links = crate::Links::new();
+            links.add_named("home".to_string(), "https://example.com".to_string()).unwrap();
+            links.get_mut("home").unwrap().protected = true;
+            let state = AppState::new(Config {
+                link_data_path: crate::links::IN_MEMORY_PATH.into(),
+                ..Config::default()
+            }, links);
+            let (addr, shutdown) = setup_test_api_with_state(state.clone()).await;
+            let client = reqwest::Client::new();
+
+            let res = client.post(format!("{addr}/links/home/rotate")).send().await.unwrap();
+            assert_eq!(res.status(), 409);
+            assert!(res.json::<Jsend<RotateLinkResponse, String>>().await.unwrap().is_fail());
+            assert!(state.links.read().await.get("home").is_some());
+
+            let data = client.post(format!("{addr}/links/home/rotate?force=true"))
+                .send().await.unwrap()
+                .json::<Jsend<RotateLinkResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert!(state.links.read().await.get("home").is_none());
+            assert!(state.links.read().await.get(&data.key).unwrap().protected);
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn acl() {
+            let (addr, shutdown) = super::get_link::setup_acl().await;
+            let rotate = |key: &str, token: &str| reqwest::Client::new().post(format!("{addr}/links/{key}/rotate"))
+                .bearer_auth(token)
+                .send();
+
+            let res = rotate("private", "bob-secret").await.unwrap();
+            assert_eq!(res.status(), 403);
+            assert!(res.json::<Jsend<RotateLinkResponse, String>>().await.unwrap().is_fail());
+
+            let data = rotate("private", "alice-secret").await.unwrap()
+                .json::<Jsend<RotateLinkResponse, String>>().await.unwrap()
+                .success().unwrap();
+            let res = rotate(&data.key, "admin-secret").await.unwrap();
+            assert_eq!(res.status(), 200);
+
+            shutdown.send(()).await.unwrap();
+        }
+    }
+
+    mod merge_links {
+        use super::*;
+
+        #[tokio::test]
+        async fn base_case() {
+            let mut links = crate::Links::new();
+            links.add_named("first".to_string(), "https://example.com".to_string()).unwrap();
+            links.add_named("second".to_string(), "https://example.com".to_string()).unwrap();
+            links.add_named("other".to_string(), "https://example.com/other".to_string()).unwrap();
+            links.get_mut("first").unwrap().metadata.used = 3;
+            links.get_mut("second").unwrap().metadata.used = 4;
+            let state = AppState::new(Config {
+                link_data_path: crate::links::IN_MEMORY_PATH.into(),
+                ..Config::default()
+            }, links);
+            let (addr, shutdown) = setup_test_api_with_state(state).await;
+            let client = reqwest::Client::new();
+
+            let merge = |from: &str, into: &str| client.post(format!("{addr}/links/merge"))
+                .json(&MergeLinksRequest { from: from.to_string(), into: into.to_string() })
+                .send();
+
+            let entry = merge("first", "second").await.unwrap()
+                .json::<Jsend<ResponseEntry, String>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(entry.key, "second");
+            assert_eq!(entry.metadata.used, 7);
+
+            let res = client.get(format!("{addr}/links/first")).send().await.unwrap()
+                .json::<Jsend<GetLinkResponse, String>>().await.unwrap();
+            assert!(!res.is_success());
+
+            for (from, into) in [("second", "other"), ("missing", "second"), ("second", "second")] {
+                let res = merge(from, into).await.unwrap();
+                assert_eq!(res.status(), 400, "{from} into {into}");
+                let res = res.json::<Jsend<ResponseEntry, String>>().await.unwrap();
+                assert!(res.is_fail(), "{from} into {into}");
+            }
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn acl() {
+            let mut links = crate::Links::new();
+            links.add_named("private".to_string(), "https://example.com".to_string()).unwrap();
+            links.add_named("public".to_string(), "https://example.com".to_string()).unwrap();
+            links.get_mut("private").unwrap().acl = vec!["alice".to_string()];
+            let state = AppState::new(Config {
+                link_data_path: crate::links::IN_MEMORY_PATH.into(),
+                api_token: Some("admin-secret".to_string()),
+                user_tokens: [("alice", "alice-secret"), ("bob", "bob-secret")].into_iter()
+                    .map(|(name, token)| (name.to_string(), token.to_string()))
+                    .collect(),
+                ..Config::default()
+            }, links);
+            let (addr, shutdown) = setup_test_api_with_state(state.clone()).await;
+            let client = reqwest::Client::new();
+            let merge = |token: &str, from: &str, into: &str| client.post(format!("{addr}/links/merge"))
+                .bearer_auth(token)
+                .json(&MergeLinksRequest { from: from.to_string(), into: into.to_string() })
+                .send();
+
+            for (from, into) in [("private", "public"), ("public", "private")] {
+                let res = merge("bob-secret", from, into).await.unwrap();
+                assert_eq!(res.status(), 403);
+            }
+            assert_eq!(state.links.read().await.len(), 2);
+
+            let res = merge("alice-secret", "public", "private").await.unwrap();
+            assert_eq!(res.status(), 200);
+            assert!(state.links.read().await.get("public").is_none());
+
+            shutdown.send(()).await.unwrap();
+        }
+    }
+
+    mod make_primary {
+        use super::*;
+
+        #[tokio::test]
+        async fn base_case() {
+            let mut links = crate::Links::new();
+            links.add_named("first".to_string(), "https://example.com".to_string()).unwrap();
+            links.add_named("second".to_string(), "https://example.com".to_string()).unwrap();
+            let state = AppState::new(Config {
+                link_data_path: crate::links::IN_MEMORY_PATH.into(),
+                server_base_url: "short.example/".to_string(),
+                ..Config::default()
+            }, links);
+            let (addr, shutdown) = setup_test_api_with_state(state).await;
+            let client = reqwest::Client::new();
+
+            let data = client.post(format!("{addr}/links/second/make-primary"))
+                .send().await.unwrap()
+                .json::<Jsend<MakePrimaryResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(data.short_url, "short.example/second");
+
+            let entry = client.get(format!("{addr}/links/second"))
+                .send().await.unwrap()
+                .json::<Jsend<GetLinkResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert!(entry.primary);
+
+            // Adding the link again shares the primary key
+            let data = client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { link: "https://example.com".to_string(), ..Default::default() })
+                .send().await.unwrap()
+                .json::<Jsend<AddLinkSuccessResponse, AddLinkFailResponse>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(data.key, "second");
+
+            let res = client.post(format!("{addr}/links/missing/make-primary"))
+                .send().await.unwrap();
+            assert_eq!(res.status(), 404);
+            let res = res.json::<Jsend<MakePrimaryResponse, String>>().await.unwrap();
+            assert!(res.is_fail());
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn acl() {
+            let (addr, shutdown) = super::get_link::setup_acl().await;
+            let make_primary = |token: &str| reqwest::Client::new().post(format!("{addr}/links/private/make-primary"))
+                .bearer_auth(token)
+                .send();
+
+            let res = make_primary("bob-secret").await.unwrap();
+            assert_eq!(res.status(), 403);
+            assert!(res.json::<Jsend<MakePrimaryResponse, String>>().await.unwrap().is_fail());
+
+            for token in ["alice-secret", "admin-secret"] {
+                let res = make_primary(token).await.unwrap();
+                assert_eq!(res.status(), 200, "{token}");
+            }
+
+            shutdown.send(()).await.unwrap();
+        }
+    }
+
+    mod copy_link {
+        use super::*;
+
+        #[tokio::test]
+        async fn base_case() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api_with_config(Config {
+                link_data_path: links_path.clone(),
+                server_base_url: "short.example/".to_string(),
+                ..Config::default()
+            }).await;
+            let client = reqwest::Client::new();
+
+            client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { key: Some("test".to_string()), link: "https://example.com".to_string(), ..Default::default() })
+                .send().await.unwrap();
+            client.post(format!("{addr}/links/test/clicks")).send().await.unwrap();
+
+            let res = client.post(format!("{addr}/links/test/copy"))
+                .json(&CopyLinkRequest { new_key: Some("test2".to_string()) })
+                .send().await.unwrap();
+            assert_eq!(res.status(), 201);
+            assert_eq!(res.headers()[header::LOCATION], "/api/links/test2");
+            let data = res.json::<Jsend<CopyLinkResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(data.key, "test2");
+            assert_eq!(data.short_url, "short.example/test2");
+
+            let entry = client.get(format!("{addr}/links/test2"))
+                .send().await.unwrap()
+                .json::<Jsend<GetLinkResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(entry.link, "https://example.com");
+            assert_eq!(entry.metadata.used, 0);
+
+            // Without a body, a key is generated
+            let data = client.post(format!("{addr}/links/test/copy"))
+                .send().await.unwrap()
+                .json::<Jsend<CopyLinkResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert!(data.key != "test" && data.key != "test2");
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn invalid() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+            let client = reqwest::Client::new();
+
+            client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { key: Some("test".to_string()), link: "https://example.com".to_string(), ..Default::default() })
+                .send().await.unwrap();
+
+            for (key, new_key, status, expected) in [
+                ("nope", None, 404, "Link not found"),
+                ("test", Some("test"), 400, "Key already in use"),
+                ("test", Some("import"), 400, "Key 'import' is reserved"),
+            ] {
+                let res = client.post(format!("{addr}/links/{key}/copy"))
+                    .json(&CopyLinkRequest { new_key: new_key.map(String::from) })
+                    .send().await.unwrap();
+                assert_eq!(res.status(), status);
+                let fail = res.json::<Jsend<CopyLinkResponse, String>>().await.unwrap()
+                    .fail().unwrap();
+                assert_eq!(fail, expected);
+            }
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn acl() {
+            let (addr, shutdown) = super::get_link::setup_acl().await;
+            let copy = |token: &str, new_key: &str| reqwest::Client::new().post(format!("{addr}/links/private/copy"))
+                .bearer_auth(token)
+                .json(&CopyLinkRequest { new_key: Some(new_key.to_string()) })
+                .send();
+
+            let res = copy("bob-secret", "bobs-copy").await.unwrap();
+            assert_eq!(res.status(), 403);
+            assert!(res.json::<Jsend<CopyLinkResponse, String>>().await.unwrap().is_fail());
+
+            for (token, new_key) in [("alice-secret", "alices-copy"), ("admin-secret", "admins-copy")] {
+                let res = copy(token, new_key).await.unwrap();
+                assert_eq!(res.status(), 201, "{token}");
+            }
+
+            shutdown.send(()).await.unwrap();
+        }
+    }
+
+    mod reset_links {
+        use super::*;
+
+        #[tokio::test]
+        async fn base_case() {
+            let mut links = crate::Links::new();
+            links.add_named("key1".to_string(), "https://example.com/1".to_string()).unwrap();
+            links.add_named("key2".to_string(), "https://example.com/2".to_string()).unwrap();
+            let links_path = random_links_path();
+            let state = AppState::new(Config {
+                link_data_path: links_path.clone(),
+                api_token: Some("secret".to_string()),
+                ..Config::default()
+            }, links);
+            let (addr, shutdown) = setup_test_api_with_state(state.clone()).await;
+            let client = reqwest::Client::new();
+            let reset = |token: &str, confirm: &str| client.post(format!("{addr}/maintenance/reset"))
+                .bearer_auth(token)
+                .json(&ResetRequest { confirm: confirm.to_string() })
+                .send();
+
+            let res = reset("wrong", "RESET").await.unwrap();
+            assert_eq!(res.status(), 401);
+            let res = reset("secret", "reset").await.unwrap();
+            assert_eq!(res.status(), 400);
+            assert_eq!(state.links.read().await.len(), 2);
+
+            let data = reset("secret", "RESET").await.unwrap()
+                .json::<Jsend<ResetResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(data.removed, 2);
+            assert!(state.links.read().await.is_empty());
+            assert!(crate::Links::<crate::links::EntryMetadata>::load(&links_path).unwrap().is_empty());
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn keeps_protected() {
+            let mut links = crate::Links::new();
+            links.add_named("home".to_string(), "https://example.com".to_string()).unwrap();
+            links.add_named("other".to_string(), "https://example.com/other".to_string()).unwrap();
+            links.get_mut("home").unwrap().protected = true;
+            let state = AppState::new(Config {
+                link_data_path: crate::links::IN_MEMORY_PATH.into(),
+                api_token: Some("secret".to_string()),
+                ..Config::default()
+            }, links);
+            let (addr, shutdown) = setup_test_api_with_state(state.clone()).await;
+            let client = reqwest::Client::new();
+            let reset = |query: &str| client.post(format!("{addr}/maintenance/reset{query}"))
+                .bearer_auth("secret")
+                .json(&ResetRequest { confirm: "RESET".to_string() })
+                .send();
+
+            let data = reset("").await.unwrap()
+                .json::<Jsend<ResetResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!((data.removed, data.kept), (1, 1));
+            assert!(state.links.read().await.get("home").is_some_and(|entry| entry.protected));
+
+            let data = reset("?force=true").await.unwrap()
+                .json::<Jsend<ResetResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!((data.removed, data.kept), (1, 0));
+            assert!(state.links.read().await.is_empty());
+
+            shutdown.send(()).await.unwrap();
+        }
+    }
+
+    mod check_links {
+        use super::*;
+
+        #[tokio::test]
+        async fn base_case() {
+            let destination = setup_mock_destination().await;
+            let mut links = crate::Links::new();
+            links.add_named("working".to_string(), format!("{destination}/ok")).unwrap();
+            links.add_named("broken".to_string(), format!("{destination}/error")).unwrap();
+            links.add_named("internal".to_string(), "go:broken".to_string()).unwrap();
+            let links_path = random_links_path();
+            let state = AppState::new(Config {
+                link_data_path: links_path.clone(),
+                ..Config::default()
+            }, links);
+            let (addr, shutdown) = setup_test_api_with_state(state).await;
+            let client = reqwest::Client::new();
+
+            let data = client.post(format!("{addr}/maintenance/check-links"))
+                .send().await.unwrap()
+                .json::<Jsend<CheckLinksResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(data.checked, 2);
+            assert_eq!(data.dead.len(), 1);
+            assert_eq!(data.dead[0].key, "broken");
+
+            let broken = client.get(format!("{addr}/links/broken"))
+                .send().await.unwrap()
+                .json::<Jsend<GetLinkResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(broken.alive, Some(false));
+            assert!(broken.last_checked.is_some());
+
+            let dead = client.get(format!("{addr}/links?dead=true"))
+                .send().await.unwrap()
+                .json::<Jsend<GetLinksResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(dead.items.len(), 1);
+            assert_eq!(dead.items[0].key, "broken");
+
+            // Filters select the links to check
+            let data = client.post(format!("{addr}/maintenance/check-links?dead=false"))
+                .send().await.unwrap()
+                .json::<Jsend<CheckLinksResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(data.checked, 1);
+            assert!(data.dead.is_empty());
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn read_only() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api_with_config(Config {
+                link_data_path: links_path.clone(),
+                read_only: true,
+                ..Config::default()
+            }).await;
+
+            let res = reqwest::Client::new().post(format!("{addr}/maintenance/check-links"))
+                .send().await.unwrap();
+            assert_eq!(res.status(), 403);
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+    }
 
-        tokio::spawn(async move {
-            axum::serve(listener, router.into_make_service())
-                .with_graceful_shutdown(async move {
-                    tokio::select! {
-                        _ = tokio::signal::ctrl_c() => {}
-                        _ = receiver.recv() => {}
-                    }
+    mod signed_export {
+        use super::*;
+
+        async fn setup() -> (String, mpsc::Sender<()>) {
+            let mut links = crate::Links::new();
+            links.add_named("docs".to_string(), "https://example.com/docs".to_string()).unwrap();
+            links.add_named("home".to_string(), "https://example.com".to_string()).unwrap();
+            let state = AppState::new(Config {
+                link_data_path: crate::links::IN_MEMORY_PATH.into(),
+                export_secret: Some("secret".to_string()),
+                ..Config::default()
+            }, links);
+            setup_test_api_with_state(state).await
+        }
+
+        #[tokio::test]
+        async fn round_trip() {
+            let (addr, shutdown) = setup().await;
+            let client = reqwest::Client::new();
+
+            let bundle = client.get(format!("{addr}/export/signed"))
+                .send().await.unwrap()
+                .bytes().await.unwrap();
+
+            client.delete(format!("{addr}/links/docs")).send().await.unwrap();
+            client.post(format!("{addr}/links"))
+                .json(&AddLinkRequest { link: "https://example.com/new".to_string(), key: Some("new".to_string()), ..Default::default() })
+                .send().await.unwrap();
+
+            let data = client.post(format!("{addr}/restore/signed"))
+                .body(bundle)
+                .send().await.unwrap()
+                .json::<Jsend<RestoreResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(data.restored, 2);
+
+            let keys = client.get(format!("{addr}/links"))
+                .send().await.unwrap()
+                .json::<Jsend<GetLinksResponse, ()>>().await.unwrap()
+                .success().unwrap()
+                .items.into_iter().map(|e| e.key).collect::<Vec<_>>();
+            assert_eq!(keys, ["docs", "home"]);
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn keeps_protected() {
+            let (addr, shutdown) = setup().await;
+            let client = reqwest::Client::new();
+
+            let bundle = client.get(format!("{addr}/export/signed"))
+                .send().await.unwrap()
+                .bytes().await.unwrap();
+            for key in ["blog", "wiki"] {
+                client.post(format!("{addr}/links"))
+                    .json(&AddLinkRequest { link: format!("https://example.com/{key}"), key: Some(key.to_string()), ..Default::default() })
+                    .send().await.unwrap();
+            }
+            client.post(format!("{addr}/links/blog/protect")).send().await.unwrap();
+
+            let data = client.post(format!("{addr}/restore/signed"))
+                .body(bundle.clone())
+                .send().await.unwrap()
+                .json::<Jsend<RestoreResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!((data.restored, data.kept), (3, 1));
+
+            let data = client.post(format!("{addr}/restore/signed?force=true"))
+                .body(bundle)
+                .send().await.unwrap()
+                .json::<Jsend<RestoreResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!((data.restored, data.kept), (2, 0));
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn rejects_tampered() {
+            let (addr, shutdown) = setup().await;
+            let client = reqwest::Client::new();
+
+            let mut bundle = client.get(format!("{addr}/export/signed"))
+                .send().await.unwrap()
+                .json::<SignedBundle>().await.unwrap();
+            bundle.data["links"]["docs"]["link"] = "https://evil.example.com".into();
+
+            let res = client.post(format!("{addr}/restore/signed"))
+                .json(&bundle)
+                .send().await.unwrap();
+            assert_eq!(res.status(), 400);
+
+            bundle.signature = None;
+            let res = client.post(format!("{addr}/restore/signed"))
+                .json(&bundle)
+                .send().await.unwrap();
+            assert_eq!(res.status(), 400);
+
+            let docs = client.get(format!("{addr}/links/docs"))
+                .send().await.unwrap()
+                .json::<Jsend<GetLinkResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(docs.link, "https://example.com/docs");
+
+            shutdown.send(()).await.unwrap();
+        }
+    }
+
+    mod add_clicks {
+        use super::*;
+
+        async fn setup() -> (AppState, String, mpsc::Sender<()>) {
+            let mut links = crate::Links::new();
+            links.add_named("key1".to_string(), "https://example.com".to_string()).unwrap();
+            let state = AppState::new(Config {
+                link_data_path: random_links_path(),
+                api_token: Some("secret".to_string()),
+                ..Config::default()
+            }, links);
+            let (addr, shutdown) = setup_test_api_with_state(state.clone()).await;
+            (state, addr, shutdown)
+        }
+
+        #[tokio::test]
+        async fn base_case() {
+            let (state, addr, shutdown) = setup().await;
+            let client = reqwest::Client::new();
+
+            let res = client.post(format!("{addr}/links/key1/clicks"))
+                .bearer_auth("secret")
+                .send().await.unwrap();
+            assert_eq!(res.status(), 200);
+            assert_eq!(state.access_event_queue.len(), 1);
+
+            let res = client.post(format!("{addr}/links/key1/clicks"))
+                .bearer_auth("secret")
+                .json(&AddClicksRequest { count: Some(5), timestamp: None })
+                .send().await.unwrap();
+            assert_eq!(res.status(), 200);
+            assert_eq!(state.access_event_queue.len(), 6);
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn malformed_body() {
+            let (state, addr, shutdown) = setup().await;
+
+            let res = reqwest::Client::new().post(format!("{addr}/links/key1/clicks"))
+                .bearer_auth("secret")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(r#"{"count": "five"}"#)
+                .send().await.unwrap();
+            assert_eq!(res.status(), 422);
+            assert!(res.json::<Jsend<(), ()>>().await.unwrap().is_error());
+            assert!(state.access_event_queue.is_empty());
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn unauthorized() {
+            let (state, addr, shutdown) = setup().await;
+            let client = reqwest::Client::new();
+
+            let res = client.post(format!("{addr}/links/key1/clicks"))
+                .send().await.unwrap();
+            assert_eq!(res.status(), 401);
+
+            let res = client.post(format!("{addr}/links/key1/clicks"))
+                .bearer_auth("wrong")
+                .send().await.unwrap();
+            assert_eq!(res.status(), 401);
+            assert!(state.access_event_queue.is_empty());
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn not_found() {
+            let (state, addr, shutdown) = setup().await;
+
+            let res = reqwest::Client::new().post(format!("{addr}/links/nope/clicks"))
+                .bearer_auth("secret")
+                .send().await.unwrap();
+            assert_eq!(res.status(), 404);
+            assert!(res.json::<Jsend<(), String>>().await.unwrap().is_fail());
+            assert!(state.access_event_queue.is_empty());
+
+            shutdown.send(()).await.unwrap();
+        }
+    }
+
+    mod touch_link {
+        use super::*;
+
+        #[tokio::test]
+        async fn base_case() {
+            let mut links = crate::Links::new();
+            links.add_named("key1".to_string(), "https://example.com".to_string()).unwrap();
+            links.get_mut("key1").unwrap().metadata.used = 3;
+            let links_path = random_links_path();
+            let state = AppState::new(Config {
+                link_data_path: links_path.clone(),
+                api_token: Some("secret".to_string()),
+                ..Config::default()
+            }, links);
+            let (addr, shutdown) = setup_test_api_with_state(state.clone()).await;
+            let client = reqwest::Client::new();
+            let last_used = Utc::now() - chrono::Duration::days(30);
+            let touch = |key: &str| client.post(format!("{addr}/links/{key}/touch"))
+                .bearer_auth("secret")
+                .json(&TouchLinkRequest { count: 5000, last_used: Some(last_used) })
+                .send();
+
+            let stats = touch("key1").await.unwrap()
+                .json::<Jsend<LinkStatsResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(stats.used, 5003);
+            assert!(state.access_event_queue.is_empty());
+            assert_eq!(crate::Links::<EntryMetadata>::load(&links_path).unwrap().get("key1").unwrap().metadata.used, 5003);
+
+            assert_eq!(touch("nope").await.unwrap().status(), 404);
+            let res = client.post(format!("{addr}/links/key1/touch"))
+                .json(&TouchLinkRequest { count: 1, last_used: None })
+                .send().await.unwrap();
+            assert_eq!(res.status(), 401);
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+    }
+
+    mod read_only {
+        use super::*;
+
+        #[tokio::test]
+        async fn rejects_mutations() {
+            let mut links = crate::Links::new();
+            links.add_named("key1".to_string(), "https://example.com".to_string()).unwrap();
+            let state = AppState::new(Config {
+                link_data_path: random_links_path(),
+                read_only: true,
+                ..Config::default()
+            }, links);
+            let (addr, shutdown) = setup_test_api_with_state(state.clone()).await;
+            let client = reqwest::Client::new();
+            let req = AddLinkRequest { key: None, link: "https://example.org".to_string(), ..Default::default() };
+
+            let res = client.post(format!("{addr}/links")).json(&req).send().await.unwrap();
+            assert_eq!(res.status(), 403);
+            assert!(res.json::<Jsend<(), ()>>().await.unwrap().is_error());
+
+            let res = client.post(format!("{addr}/links/import")).json(&vec![req]).send().await.unwrap();
+            assert_eq!(res.status(), 403);
+
+            let res = client.delete(format!("{addr}/links/key1")).send().await.unwrap();
+            assert_eq!(res.status(), 403);
+
+            let res = client.post(format!("{addr}/links/key1/clicks")).send().await.unwrap();
+            assert_eq!(res.status(), 403);
+            assert!(state.access_event_queue.is_empty());
+
+            let res = client.get(format!("{addr}/links/key1")).send().await.unwrap();
+            assert_eq!(res.status(), 200);
+            let res = client.get(format!("{addr}/links")).send().await.unwrap();
+            let data = res.json::<Jsend<GetLinksResponse, ()>>().await.unwrap().success().unwrap();
+            assert_eq!(data.items.len(), 1);
+
+            shutdown.send(()).await.unwrap();
+        }
+    }
+
+    mod get_link_log {
+        use super::*;
+
+        async fn setup_with_log(log_path: &Path) -> (String, mpsc::Sender<()>) {
+            let _ = std::fs::remove_file(log_path);
+            let accesses = (0..5)
+                .flat_map(|i| ["key1", "key2"].map(|key| LoggedAccess {
+                    key: key.to_string(),
+                    timestamp: DateTime::from_timestamp(i, 0).unwrap(),
+                }))
+                .collect::<Vec<_>>();
+            event_log::append(log_path, &accesses).unwrap();
+
+            setup_test_api_with_config(Config {
+                link_data_path: random_links_path(),
+                event_log_path: Some(log_path.to_path_buf()),
+                ..Config::default()
+            }).await
+        }
+
+        #[tokio::test]
+        async fn pagination() {
+            let log_path = temp_dir().join("landmower_test_api_log_pagination.ndjson");
+            let (addr, shutdown) = setup_with_log(&log_path).await;
+            let client = reqwest::Client::new();
+
+            let data = client.get(format!("{addr}/links/key1/log?limit=3"))
+                .send().await.unwrap()
+                .json::<Jsend<LinkLogResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(data.events.len(), 3);
+            assert!(data.events.iter().all(|e| e.key == "key1"));
+            assert_eq!(data.events[0].timestamp.timestamp(), 0);
+            assert_eq!(data.next_offset, Some(3));
+
+            let data = client.get(format!("{addr}/links/key1/log?offset=3&limit=3"))
+                .send().await.unwrap()
+                .json::<Jsend<LinkLogResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(data.events.len(), 2);
+            assert_eq!(data.events[0].timestamp.timestamp(), 3);
+            assert_eq!(data.next_offset, None);
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&log_path);
+        }
+
+        #[tokio::test]
+        async fn all_keys() {
+            let log_path = temp_dir().join("landmower_test_api_log_all_keys.ndjson");
+            let (addr, shutdown) = setup_with_log(&log_path).await;
+            let client = reqwest::Client::new();
+            let get = |query: &'static str| client.get(format!("{addr}/log?{query}")).send();
+
+            let data = get("limit=4&sort=-time").await.unwrap()
+                .json::<Jsend<LinkLogResponse, String>>().await.unwrap()
+                .success().unwrap();
+            let times = data.events.iter().map(|e| e.timestamp.timestamp()).collect::<Vec<_>>();
+            assert_eq!(times, [4, 4, 3, 3]);
+            assert_eq!(data.next_offset, Some(4));
+
+            let data = get("key=key2&since=1970-01-01T00:00:01Z&until=1970-01-01T00:00:04Z&offset=1").await.unwrap()
+                .json::<Jsend<LinkLogResponse, String>>().await.unwrap()
+                .success().unwrap();
+            let times = data.events.iter().map(|e| e.timestamp.timestamp()).collect::<Vec<_>>();
+            assert_eq!(times, [2, 3]);
+            assert!(data.events.iter().all(|e| e.key == "key2"));
+            assert_eq!(data.next_offset, None);
+
+            assert_eq!(get("sort=size").await.unwrap().status(), 400);
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&log_path);
+        }
+
+        #[tokio::test]
+        async fn export() {
+            let log_path = temp_dir().join("landmower_test_api_log_export.ndjson");
+            let (addr, shutdown) = setup_with_log(&log_path).await;
+
+            let res = reqwest::get(format!("{addr}/links/key2/log/export")).await.unwrap();
+            assert_eq!(res.status(), 200);
+            assert_eq!(res.headers()[header::CONTENT_TYPE], "application/x-ndjson");
+
+            let body = res.text().await.unwrap();
+            let events = body.lines()
+                .map(|line| serde_json::from_str::<LoggedAccess>(line).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(events.len(), 5);
+            assert!(events.iter().all(|e| e.key == "key2"));
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&log_path);
+        }
+
+        #[tokio::test]
+        async fn disabled() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+
+            let res = reqwest::get(format!("{addr}/links/key1/log")).await.unwrap()
+                .json::<Jsend<LinkLogResponse, String>>().await.unwrap();
+            assert!(res.is_fail());
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn acl() {
+            let log_path = temp_dir().join("landmower_test_api_log_acl.ndjson");
+            let _ = std::fs::remove_file(&log_path);
+            let mut links = crate::Links::new();
+            links.add_named("private".to_string(), "https://example.com".to_string()).unwrap();
+            links.get_mut("private").unwrap().acl = vec!["alice".to_string()];
+            let (addr, shutdown) = setup_test_api_with_state(AppState::new(Config {
+                link_data_path: crate::links::IN_MEMORY_PATH.into(),
+                event_log_path: Some(log_path.clone()),
+                api_token: Some("admin-secret".to_string()),
+                user_tokens: [("alice", "alice-secret"), ("bob", "bob-secret")].into_iter()
+                    .map(|(name, token)| (name.to_string(), token.to_string()))
+                    .collect(),
+                ..Config::default()
+            }, links)).await;
+            let client = reqwest::Client::new();
+
+            for path in ["log", "log/export", "heatmap"] {
+                let res = client.get(format!("{addr}/links/private/{path}"))
+                    .bearer_auth("bob-secret")
+                    .send().await.unwrap();
+                assert_eq!(res.status(), 403, "{path}");
+                for token in ["alice-secret", "admin-secret"] {
+                    let res = client.get(format!("{addr}/links/private/{path}"))
+                        .bearer_auth(token)
+                        .send().await.unwrap();
+                    assert_eq!(res.status(), 200, "{path} {token}");
+                }
+            }
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&log_path);
+        }
+    }
+
+    mod tail_access_log {
+        use super::*;
+
+        #[tokio::test]
+        async fn base_case() {
+            let state = AppState::new(Config {
+                link_data_path: crate::links::IN_MEMORY_PATH.into(),
+                api_token: Some("secret".to_string()),
+                ..Config::default()
+            }, crate::Links::new());
+            let (addr, shutdown) = setup_test_api_with_state(state.clone()).await;
+            let client = reqwest::Client::new();
+
+            let res = client.get(format!("{addr}/log/tail")).send().await.unwrap();
+            assert_eq!(res.status(), 401);
+
+            let mut res = client.get(format!("{addr}/log/tail"))
+                .bearer_auth("secret")
+                .send().await.unwrap();
+            assert_eq!(res.status(), 200);
+            assert_eq!(res.headers()[header::CONTENT_TYPE], "application/x-ndjson");
+
+            // Subscribed before the response headers were sent
+            let access = LoggedAccess { key: "key1".to_string(), timestamp: Utc::now() };
+            state.access_tail.send(access.clone()).unwrap();
+            let chunk = res.chunk().await.unwrap().unwrap();
+            let line = std::str::from_utf8(&chunk).unwrap();
+            assert!(line.ends_with('\n'));
+            assert_eq!(serde_json::from_str::<LoggedAccess>(line).unwrap(), access);
+
+            shutdown.send(()).await.unwrap();
+        }
+    }
+
+    mod get_link_heatmap {
+        use super::*;
+
+        #[tokio::test]
+        async fn base_case() {
+            let log_path = temp_dir().join("landmower_test_api_heatmap.ndjson");
+            let _ = std::fs::remove_file(&log_path);
+            let now = Utc::now();
+            let accesses = [now, now, now - chrono::Days::new(2)]
+                .map(|timestamp| LoggedAccess { key: "key1".to_string(), timestamp });
+            event_log::append(&log_path, &accesses).unwrap();
+
+            let (addr, shutdown) = setup_test_api_with_config(Config {
+                link_data_path: random_links_path(),
+                event_log_path: Some(log_path.clone()),
+                ..Config::default()
+            }).await;
+
+            let days = reqwest::get(format!("{addr}/links/key1/heatmap?days=4")).await.unwrap()
+                .json::<Jsend<Vec<DayCount>, String>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(days.iter().map(|d| d.clicks).collect::<Vec<_>>(), [0, 1, 0, 2]);
+            assert_eq!(days[3].date, now.date_naive());
+
+            let res = reqwest::get(format!("{addr}/links/key1/heatmap")).await.unwrap()
+                .text().await.unwrap();
+            let days = serde_json::from_str::<Jsend<Vec<serde_json::Value>, String>>(&res).unwrap()
+                .success().unwrap();
+            assert_eq!(days.len(), DEFAULT_HEATMAP_DAYS as usize);
+            assert_eq!(days[89]["date"], now.date_naive().format("%Y-%m-%d").to_string());
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&log_path);
+        }
+
+        #[tokio::test]
+        async fn log_disabled() {
+            let (addr, shutdown) = setup_test_api(&random_links_path()).await;
+
+            let res = reqwest::get(format!("{addr}/links/key1/heatmap")).await.unwrap()
+                .json::<Jsend<Vec<DayCount>, String>>().await.unwrap();
+            assert!(matches!(res, Jsend::Fail(_)));
+
+            shutdown.send(()).await.unwrap();
+        }
+    }
+
+    mod get_reserved_keys {
+        use super::*;
+
+        #[tokio::test]
+        async fn base_case() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api_with_config(Config {
+                link_data_path: links_path.clone(),
+                key_blacklist: vec!["admin".to_string()],
+                ..Config::default()
+            }).await;
+            let client = reqwest::Client::new();
+
+            let data = client.get(format!("{addr}/keys/reserved"))
+                .send().await.unwrap()
+                .json::<Jsend<ReservedKeysResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert!(data.reserved.contains(&"import".to_string()));
+            assert_eq!(data.blacklist, ["admin"]);
+            assert_eq!(data.rules.min_length, 4);
+            assert_eq!(data.rules.max_length, 64);
+
+            // Reserved keys are rejected
+            let res = client.post(format!("{addr}/validate/add_link"))
+                .json(&AddLinkRequest { key: Some("Counts".to_string()), link: "https://example.com".to_string(), ..Default::default() })
+                .send().await.unwrap()
+                .json::<Jsend<(), AddLinkFailResponse>>().await.unwrap();
+            assert!(res.fail().unwrap().key.is_some());
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+    }
+
+    mod click_buckets {
+        use super::*;
+
+        #[test]
+        fn bucket_clicks_rounds_down() {
+            assert_eq!(bucket_clicks(0, 10), 0);
+            assert_eq!(bucket_clicks(9, 10), 0);
+            assert_eq!(bucket_clicks(10, 10), 10);
+            assert_eq!(bucket_clicks(17, 10), 10);
+            assert_eq!(bucket_clicks(1234, 100), 1200);
+            assert_eq!(bucket_clicks(17, 0), 17);
+        }
+
+        #[tokio::test]
+        async fn raw_for_authenticated() {
+            let mut links = crate::Links::new();
+            links.add_named("key1".to_string(), "https://example.com".to_string()).unwrap();
+            links.get_mut("key1").unwrap().metadata.used = 17;
+            let state = AppState::new(Config {
+                link_data_path: random_links_path(),
+                click_bucket_size: Some(10),
+                api_token: Some("secret".to_string()),
+                ..Config::default()
+            }, links);
+            let (addr, shutdown) = setup_test_api_with_state(state).await;
+            let client = reqwest::Client::new();
+
+            let entry = client.get(format!("{addr}/links/key1"))
+                .send().await.unwrap()
+                .json::<Jsend<GetLinkResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(entry.metadata.used, 10);
+
+            let counts = client.get(format!("{addr}/links/counts"))
+                .send().await.unwrap()
+                .json::<Jsend<GetLinkCountsResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(counts["key1"], 10);
+
+            let entry = client.get(format!("{addr}/links/key1"))
+                .bearer_auth("secret")
+                .send().await.unwrap()
+                .json::<Jsend<GetLinkResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(entry.metadata.used, 17);
+
+            // Events and daily counts would add up to the exact count
+            for path in ["log", "log/export", "heatmap"] {
+                let res = client.get(format!("{addr}/links/key1/{path}")).send().await.unwrap();
+                assert_eq!(res.status(), 403, "{path}");
+                let res = client.get(format!("{addr}/links/key1/{path}"))
+                    .bearer_auth("secret")
+                    .send().await.unwrap();
+                assert_ne!(res.status(), 403, "{path}");
+            }
+
+            shutdown.send(()).await.unwrap();
+        }
+    }
+
+    mod get_link_stats {
+        use super::*;
+
+        #[tokio::test]
+        async fn base_case() {
+            let mut links = crate::Links::new();
+            links.add_named("docs".to_string(), "https://example.com/docs".to_string()).unwrap();
+            links.get_mut("docs").unwrap().metadata.used = 7;
+            let state = AppState::new(Config {
+                link_data_path: crate::links::IN_MEMORY_PATH.into(),
+                ..Config::default()
+            }, links);
+            let (addr, shutdown) = setup_test_api_with_state(state).await;
+            let client = reqwest::Client::new();
+
+            let res = client.get(format!("{addr}/links/docs/stats")).send().await.unwrap();
+            assert_eq!(res.status(), 200);
+            let body = res.json::<serde_json::Value>().await.unwrap();
+            let data = body["data"].as_object().unwrap();
+            let mut fields = data.keys().collect::<Vec<_>>();
+            fields.sort();
+            assert_eq!(fields, ["created", "last_used", "used"]);
+            assert_eq!(data["used"], 7);
+
+            let res = client.get(format!("{addr}/links/missing/stats")).send().await.unwrap();
+            assert_eq!(res.status(), 404);
+            assert!(res.json::<Jsend<LinkStatsResponse, String>>().await.unwrap().is_fail());
+
+            shutdown.send(()).await.unwrap();
+        }
+    }
+
+    mod batch_get_links {
+        use super::*;
+
+        #[tokio::test]
+        async fn present_and_absent() {
+            let mut links = crate::Links::new();
+            for key in ["key1", "key2", "key3"] {
+                links.add_named(key.to_string(), format!("https://example.com/{key}")).unwrap();
+            }
+            let state = AppState::new(Config {
+                link_data_path: crate::links::IN_MEMORY_PATH.into(),
+                ..Config::default()
+            }, links);
+            let (addr, shutdown) = setup_test_api_with_state(state).await;
+            let client = reqwest::Client::new();
+            let batch_get = |keys: &[&str], include_missing: bool| client.post(format!("{addr}/links/batch-get"))
+                .json(&BatchGetRequest {
+                    keys: keys.iter().map(|k| k.to_string()).collect(),
+                    include_missing,
                 })
-                .await.unwrap();
-        });
+                .send();
+
+            let data = batch_get(&["key3", "missing", "key1"], false).await.unwrap()
+                .json::<Jsend<BatchGetResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(data.keys().collect::<Vec<_>>(), ["key1", "key3"]);
+            assert_eq!(data["key3"].as_ref().unwrap().link, "https://example.com/key3");
 
-        (addr, sender)
+            let data = batch_get(&["key2", "missing"], true).await.unwrap()
+                .json::<Jsend<BatchGetResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(data.len(), 2);
+            assert_eq!(data["key2"].as_ref().unwrap().key, "key2");
+            assert!(data["missing"].is_none());
+
+            let data = batch_get(&[], true).await.unwrap()
+                .json::<Jsend<BatchGetResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert!(data.is_empty());
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn acl() {
+            let (addr, shutdown) = super::get_link::setup_acl().await;
+            let batch_get = |token: &str| reqwest::Client::new().post(format!("{addr}/links/batch-get"))
+                .bearer_auth(token)
+                .json(&BatchGetRequest { keys: vec!["private".to_string()], include_missing: true })
+                .send();
+
+            let data = batch_get("alice-secret").await.unwrap()
+                .json::<Jsend<BatchGetResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert!(data["private"].is_some());
+            let data = batch_get("bob-secret").await.unwrap()
+                .json::<Jsend<BatchGetResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert!(data["private"].is_none());
+
+            shutdown.send(()).await.unwrap();
+        }
     }
 
-    mod add_link {
+    mod update_tags {
         use super::*;
-        #[tokio::test]
-        async fn without_key() {
-            let links_path = random_links_path();
-            let (addr, shutdown) = setup_test_api(&links_path).await;
 
+        #[tokio::test]
+        async fn add_and_remove() {
+            let mut links = crate::Links::new();
+            for key in ["key1", "key2", "key3"] {
+                links.add_named(key.to_string(), format!("https://example.com/{key}")).unwrap();
+            }
+            let state = AppState::new(Config {
+                link_data_path: crate::links::IN_MEMORY_PATH.into(),
+                ..Config::default()
+            }, links);
+            let (addr, shutdown) = setup_test_api_with_state(state.clone()).await;
             let client = reqwest::Client::new();
-
-            let res = client.post(format!("{addr}/links"))
-                .json(&AddLinkRequest { 
-                    key: None, link: 
-                    "https://example.com".to_string() 
+            let update = |keys: &[&str], add: &[&str], remove: &[&str]| client.post(format!("{addr}/links/tags"))
+                .json(&UpdateTagsRequest {
+                    keys: keys.iter().map(|k| k.to_string()).collect(),
+                    add: add.iter().map(|t| t.to_string()).collect(),
+                    remove: remove.iter().map(|t| t.to_string()).collect(),
                 })
-                .send().await.unwrap();
+                .send();
+            let tags = |key: &'static str| {
+                let state = state.clone();
+                async move { state.links.read().await.get(key).unwrap().tags.iter().cloned().collect::<Vec<_>>() }
+            };
 
-            assert_eq!(res.status(), 200);
+            let data = update(&["key1", "key2", "missing"], &["docs", "team"], &[]).await.unwrap()
+                .json::<Jsend<UpdateTagsResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(data.updated, ["key1", "key2"]);
+            assert_eq!(data.missing, ["missing"]);
+            assert_eq!(tags("key1").await, ["docs", "team"]);
+            assert!(tags("key3").await.is_empty());
 
-            let body: Jsend<AddLinkSuccessResponse, AddLinkFailResponse> = res.json().await.unwrap();
-            assert!(body.is_success());
+            let data = update(&["key2", "key3"], &["archive"], &["team"]).await.unwrap()
+                .json::<Jsend<UpdateTagsResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(data.updated, ["key2", "key3"]);
+            assert_eq!(tags("key1").await, ["docs", "team"]);
+            assert_eq!(tags("key2").await, ["archive", "docs"]);
+            assert_eq!(tags("key3").await, ["archive"]);
 
-            let data = body.success().unwrap();
-            assert_eq!(data.entry.link, "https://example.com");
+            let res = update(&["key1"], &["docs"], &["docs"]).await.unwrap();
+            assert_eq!(res.status(), 400);
+            let res = res.json::<Jsend<UpdateTagsResponse, String>>().await.unwrap();
+            assert!(res.is_fail());
+
+            let res = client.get(format!("{addr}/links/key2")).send().await.unwrap()
+                .json::<Jsend<GetLinkResponse, String>>().await.unwrap();
+            assert_eq!(res.success().unwrap().tags, BTreeSet::from(["archive".to_string(), "docs".to_string()]));
 
             shutdown.send(()).await.unwrap();
-            cleanup(&links_path);
         }
 
         #[tokio::test]
-        async fn with_key() {
-            let links_path = random_links_path();
-            let (addr, shutdown) = setup_test_api(&links_path).await;
+        async fn acl() {
+            let (addr, shutdown) = super::get_link::setup_acl().await;
+            let update = |token: &str| reqwest::Client::new().post(format!("{addr}/links/tags"))
+                .bearer_auth(token)
+                .json(&UpdateTagsRequest { keys: vec!["private".to_string()], add: ["work".to_string()].into(), ..Default::default() })
+                .send();
 
-            let client = reqwest::Client::new();
+            let data = update("bob-secret").await.unwrap()
+                .json::<Jsend<UpdateTagsResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert!(data.updated.is_empty());
+            assert_eq!(data.missing, ["private"]);
 
-            let res = client.post(format!("{addr}/links"))
-                .json(&AddLinkRequest { 
-                    key: Some("test".to_string()), 
-                    link: "https://example.com".to_string() 
-                })
-                .send().await.unwrap();
+            let data = update("alice-secret").await.unwrap()
+                .json::<Jsend<UpdateTagsResponse, String>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(data.updated, ["private"]);
 
-            assert_eq!(res.status(), 200);
+            shutdown.send(()).await.unwrap();
+        }
+    }
 
-            let body: Jsend<AddLinkSuccessResponse, AddLinkFailResponse> = res.json().await.unwrap();
-            assert!(body.is_success());
+    mod get_targets {
+        use super::*;
 
-            let data = body.success().unwrap();
-            assert_eq!(data.entry.link, "https://example.com");
+        #[tokio::test]
+        async fn base_case() {
+            let mut links = crate::Links::new();
+            for (key, link, used) in [
+                ("docs", "https://example.com/docs", 3),
+                ("documentation", "https://example.com/docs", 4),
+                ("manual", "https://example.com/docs", 5),
+                ("home", "https://example.com", 1),
+            ] {
+                links.add_named(key.to_string(), link.to_string()).unwrap();
+                links.get_mut(key).unwrap().metadata.used = used;
+            }
+            links.make_primary("manual").unwrap();
+            let state = AppState::new(Config {
+                link_data_path: crate::links::IN_MEMORY_PATH.into(),
+                api_token: Some("secret".to_string()),
+                ..Config::default()
+            }, links);
+            let (addr, shutdown) = setup_test_api_with_state(state).await;
+            let client = reqwest::Client::new();
+
+            let targets = client.get(format!("{addr}/targets"))
+                .bearer_auth("secret")
+                .send().await.unwrap()
+                .json::<Jsend<Vec<TargetResponse>, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(targets.len(), 2);
+            assert_eq!(targets[0].link, "https://example.com/docs");
+            assert_eq!(targets[0].keys[0], "manual");
+            assert_eq!(BTreeSet::from_iter(&targets[0].keys[1..]), BTreeSet::from([&"docs".to_string(), &"documentation".to_string()]));
+            assert_eq!(targets[0].used, 12);
+            assert_eq!(targets[1].keys, ["home"]);
+            assert_eq!(targets[1].used, 1);
+
+            let res = client.get(format!("{addr}/targets")).send().await.unwrap();
+            assert_eq!(res.status(), 401);
 
             shutdown.send(()).await.unwrap();
-            cleanup(&links_path);
         }
+    }
 
+    mod get_links_meta {
+        use super::*;
 
         #[tokio::test]
-        async fn key_already_exists() {
+        async fn base_case() {
             let links_path = random_links_path();
             let (addr, shutdown) = setup_test_api(&links_path).await;
-
             let client = reqwest::Client::new();
 
+            let data = client.get(format!("{addr}/links/meta")).send().await.unwrap()
+                .json::<Jsend<LinksMetaResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert!(data.modified.is_none() && data.size.is_none());
+
+            let before = Utc::now() - chrono::Duration::seconds(1);
             client.post(format!("{addr}/links"))
                 .json(&AddLinkRequest { 
                     key: Some("test".to_string()), 
-                    link: "https://example1.com".to_string() 
+                    link: "https://example.com".to_string(),
+                    ..Default::default()
                 })
-                .send().await.unwrap();            
+                .send().await.unwrap();
 
-            let res = client.post(format!("{addr}/links"))
-                .json(&AddLinkRequest { 
-                    key: Some("test".to_string()), 
-                    link: "https://example2.com".to_string() 
-                })
-                .send().await.unwrap();   
+            let data = client.get(format!("{addr}/links/meta")).send().await.unwrap()
+                .json::<Jsend<LinksMetaResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert!(data.modified.unwrap() >= before);
+            assert_eq!(data.size, Some(std::fs::metadata(&links_path).unwrap().len()));
 
-            assert_eq!(res.status(), 200);
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
 
-            let body: Jsend<AddLinkSuccessResponse, AddLinkFailResponse> = res.json().await.unwrap();
-            assert!(body.is_fail());
+        #[tokio::test]
+        async fn in_memory() {
+            let (addr, shutdown) = setup_test_api_with_config(Config {
+                link_data_path: crate::links::IN_MEMORY_PATH.into(),
+                ..Config::default()
+            }).await;
+
+            let res = reqwest::get(format!("{addr}/links/meta")).await.unwrap();
+            let body: serde_json::Value = res.json().await.unwrap();
+            assert_eq!(body["data"], serde_json::json!({ "modified": null, "size": null }));
 
             shutdown.send(()).await.unwrap();
-            cleanup(&links_path);
         }
+    }
 
+    mod get_link_counts {
+        use super::*;
         #[tokio::test]
-        async fn link_already_exists() {
+        async fn base_case() {
             let links_path = random_links_path();
             let (addr, shutdown) = setup_test_api(&links_path).await;
-
+    
             let client = reqwest::Client::new();
-
-            let res = client.post(format!("{addr}/links"))
-                .json(&AddLinkRequest { 
-                    key: None, 
-                    link: "https://example.com".to_string() 
-                })
-                .send().await.unwrap();
-
-            let key1 = res
-                .json::<Jsend<AddLinkSuccessResponse, AddLinkFailResponse>>().await.unwrap()
-                .success().unwrap()
-                .key;
-
-            let res = client.post(format!("{addr}/links"))
-                .json(&AddLinkRequest { 
-                    key: None, 
-                    link: "https://example.com".to_string() 
-                })
+    
+            for key in ["test1", "test2"] {
+                client.post(format!("{addr}/links"))
+                    .json(&AddLinkRequest { 
+                        key: Some(key.to_string()), 
+                        link: "https://example.com".to_string(),
+                        ..Default::default()
+                    })
+                    .send().await.unwrap();
+            }
+    
+            let res = client.get(format!("{addr}/links/counts"))
                 .send().await.unwrap();
-
             assert_eq!(res.status(), 200);
+                        
+            let data = res.json::<Jsend<GetLinkCountsResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(data.len(), 2);
+            assert_eq!(data["test1"], 0);
+            assert_eq!(data["test2"], 0);
+    
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
 
-            let body: Jsend<AddLinkSuccessResponse, AddLinkFailResponse> = res.json().await.unwrap();
-            assert!(body.is_success());
+        #[tokio::test]
+        async fn acl() {
+            let (addr, shutdown) = super::get_link::setup_acl().await;
+            let counts = |token: &str| reqwest::Client::new().get(format!("{addr}/links/counts"))
+                .bearer_auth(token)
+                .send();
 
-            let data = body.success().unwrap();
-            assert_eq!(data.key, key1);
+            for token in ["alice-secret", "admin-secret"] {
+                let data = counts(token).await.unwrap()
+                    .json::<Jsend<GetLinkCountsResponse, ()>>().await.unwrap()
+                    .success().unwrap();
+                assert!(data.contains_key("private"), "{token}");
+            }
+            let data = counts("bob-secret").await.unwrap()
+                .json::<Jsend<GetLinkCountsResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert!(data.is_empty());
 
             shutdown.send(()).await.unwrap();
-            cleanup(&links_path);
-        }        
+        }
     }
 
-    mod get_link {
+    mod get_links {
         use super::*;
         #[tokio::test]
         async fn base_case() {
@@ -451,152 +4952,280 @@ mod tests {
             let (addr, shutdown) = setup_test_api(&links_path).await;
     
             let client = reqwest::Client::new();
-
+    
             client.post(format!("{addr}/links"))
                 .json(&AddLinkRequest { 
                     key: Some("test".to_string()), 
-                    link: "https://example.com".to_string() 
+                    link: "https://example.com".to_string(),
+                    ..Default::default()
                 })
                 .send().await.unwrap();
-
-            let res = client.get(format!("{addr}/links/test"))
+    
+            let res = client.get(format!("{addr}/links"))
                 .send().await.unwrap();
             assert_eq!(res.status(), 200);
-
-            let body = res.json::<Jsend<GetLinkResponse, String>>().await.unwrap();
+                        
+            let body = res.json::<Jsend<GetLinksResponse, ()>>().await.unwrap();
             assert!(body.is_success());
-            
-            let data = body.success().unwrap();
-            assert_eq!(data.link, "https://example.com");
 
+            let data = body.success().unwrap();
+            assert_eq!(data.items.len(), 1);
+            assert_eq!(data.items[0].key, "test");
+            assert_eq!(data.items[0].link, "https://example.com");
+    
             shutdown.send(()).await.unwrap();
             cleanup(&links_path);
         }
-
+    
         #[tokio::test]
-        async fn not_found() {
+        async fn empty_table() {
             let links_path = random_links_path();
             let (addr, shutdown) = setup_test_api(&links_path).await;
     
             let client = reqwest::Client::new();
     
-            let res = client.get(format!("{addr}/links/test"))
+            let res = client.get(format!("{addr}/links"))
                 .send().await.unwrap();
             assert_eq!(res.status(), 200);
 
-            let body = res.json::<Jsend<GetLinkResponse, String>>().await.unwrap();
-            assert!(body.is_fail()); 
+            let body = res.json::<Jsend<GetLinksResponse, ()>>().await.unwrap();
+            assert!(body.is_success());
 
+            let data = body.success().unwrap();
+            assert_eq!(data.items.len(), 0);
+    
             shutdown.send(()).await.unwrap();
             cleanup(&links_path);
         }
-    }    
-    
-    mod delete_link {
-        use super::*;
+
+        async fn get_links_with_query(addr: &str, query: &[(&str, String)]) -> GetLinksResponse {
+            reqwest::Client::new()
+                .get(format!("{addr}/links"))
+                .query(query)
+                .send().await.unwrap()
+                .json::<Jsend<GetLinksResponse, ()>>().await.unwrap()
+                .success().unwrap()
+        }
+
+        fn rfc3339(time: DateTime<Utc>) -> String {
+            time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+        }
+
         #[tokio::test]
-        async fn base_case() {
+        async fn created_after() {
             let links_path = random_links_path();
             let (addr, shutdown) = setup_test_api(&links_path).await;
     
-            let client = reqwest::Client::new();
-    
-            client.post(format!("{addr}/links"))
+            reqwest::Client::new().post(format!("{addr}/links"))
                 .json(&AddLinkRequest { 
                     key: Some("test".to_string()), 
-                    link: "https://example.com".to_string() 
+                    link: "https://example.com".to_string(),
+                    ..Default::default()
                 })
                 .send().await.unwrap();
-    
-            let res = client.delete(format!("{addr}/links/test"))
-                .send().await.unwrap();
-    
-            assert_eq!(res.status(), 200);
 
-            let body = res.json::<Jsend<(), String>>().await.unwrap();
-            assert!(body.is_success());
+            let hour_ago = Utc::now() - chrono::Duration::hours(1);
+            let hour_ahead = Utc::now() + chrono::Duration::hours(1);
+
+            let data = get_links_with_query(&addr, &[("created_after", rfc3339(hour_ago))]).await;
+            assert_eq!(data.items.len(), 1);
+            let data = get_links_with_query(&addr, &[("created_after", rfc3339(hour_ahead))]).await;
+            assert_eq!(data.items.len(), 0);
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn created_before() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
     
-            let res = client.get(format!("{addr}/links/test"))
+            reqwest::Client::new().post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: Some("test".to_string()), 
+                    link: "https://example.com".to_string(),
+                    ..Default::default()
+                })
                 .send().await.unwrap();
-            assert_eq!(res.status(), 200);
 
-            let body = res.json::<Jsend<GetLinkResponse, String>>().await.unwrap();
-            assert!(body.is_fail());        
-    
+            let hour_ago = Utc::now() - chrono::Duration::hours(1);
+            let hour_ahead = Utc::now() + chrono::Duration::hours(1);
+
+            let data = get_links_with_query(&addr, &[("created_before", rfc3339(hour_ago))]).await;
+            assert_eq!(data.items.len(), 0);
+            let data = get_links_with_query(&addr, &[("created_before", rfc3339(hour_ahead))]).await;
+            assert_eq!(data.items.len(), 1);
+
             shutdown.send(()).await.unwrap();
             cleanup(&links_path);
         }
 
         #[tokio::test]
-        async fn not_found() {
+        async fn used_min() {
             let links_path = random_links_path();
             let (addr, shutdown) = setup_test_api(&links_path).await;
     
-            let client = reqwest::Client::new();
-    
-            let res = client.delete(format!("{addr}/links/test"))
+            reqwest::Client::new().post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: Some("test".to_string()), 
+                    link: "https://example.com".to_string(),
+                    ..Default::default()
+                })
                 .send().await.unwrap();
-            assert_eq!(res.status(), 200);
-            
-            let body = res.json::<Jsend<(), String>>().await.unwrap();
-            assert!(body.is_fail());
-    
+
+            let data = get_links_with_query(&addr, &[("used_min", "0".to_string())]).await;
+            assert_eq!(data.items.len(), 1);
+            let data = get_links_with_query(&addr, &[("used_min", "1".to_string())]).await;
+            assert_eq!(data.items.len(), 0);
+
             shutdown.send(()).await.unwrap();
             cleanup(&links_path);
         }
-    }
 
-    mod get_links {
-        use super::*;
         #[tokio::test]
-        async fn base_case() {
+        async fn source() {
             let links_path = random_links_path();
             let (addr, shutdown) = setup_test_api(&links_path).await;
-    
             let client = reqwest::Client::new();
     
             client.post(format!("{addr}/links"))
                 .json(&AddLinkRequest { 
-                    key: Some("test".to_string()), 
-                    link: "https://example.com".to_string() 
+                    key: Some("added".to_string()), 
+                    link: "https://example.com".to_string(),
+                    ..Default::default()
                 })
                 .send().await.unwrap();
-    
-            let res = client.get(format!("{addr}/links"))
+            client.post(format!("{addr}/links/import?format=text"))
+                .body("imported https://example.org")
                 .send().await.unwrap();
-            assert_eq!(res.status(), 200);
-                        
-            let body = res.json::<Jsend<GetLinksResponse, ()>>().await.unwrap();
-            assert!(body.is_success());
 
-            let data = body.success().unwrap();
-            assert_eq!(data.len(), 1);
-            assert_eq!(data[0].key, "test");
-            assert_eq!(data[0].link, "https://example.com");
-    
+            let data = get_links_with_query(&addr, &[("source", "import".to_string())]).await;
+            assert_eq!(data.items.len(), 1);
+            assert_eq!(data.items[0].key, "imported");
+            assert_eq!(data.items[0].metadata.source.as_deref(), Some("import"));
+            let data = get_links_with_query(&addr, &[("source", "api".to_string())]).await;
+            assert_eq!(data.items.len(), 1);
+            assert_eq!(data.items[0].key, "added");
+            let data = get_links_with_query(&addr, &[("source", "cli".to_string())]).await;
+            assert!(data.items.is_empty());
+
             shutdown.send(()).await.unwrap();
             cleanup(&links_path);
         }
-    
+
         #[tokio::test]
-        async fn empty_table() {
+        async fn combined_filters() {
             let links_path = random_links_path();
             let (addr, shutdown) = setup_test_api(&links_path).await;
     
-            let client = reqwest::Client::new();
-    
-            let res = client.get(format!("{addr}/links"))
+            reqwest::Client::new().post(format!("{addr}/links"))
+                .json(&AddLinkRequest { 
+                    key: Some("test".to_string()), 
+                    link: "https://example.com".to_string(),
+                    ..Default::default()
+                })
                 .send().await.unwrap();
-            assert_eq!(res.status(), 200);
 
-            let body = res.json::<Jsend<GetLinksResponse, ()>>().await.unwrap();
-            assert!(body.is_success());
+            let hour_ago = Utc::now() - chrono::Duration::hours(1);
+
+            let data = get_links_with_query(&addr, &[
+                ("created_after", rfc3339(hour_ago)),
+                ("used_min", "1".to_string()),
+            ]).await;
+            assert_eq!(data.items.len(), 0);
+
+            shutdown.send(()).await.unwrap();
+            cleanup(&links_path);
+        }
+
+        #[tokio::test]
+        async fn search_tag_and_sort() {
+            let mut links = crate::Links::new();
+            for (key, link, used, tag) in [
+                ("docs-api", "https://example.com/docs/api", 5, "internal"),
+                ("docs-guide", "https://example.com/docs/guide", 9, "internal"),
+                ("handbook", "https://example.com/DOCS/handbook", 7, "internal"),
+                ("docs-public", "https://example.com/docs/public", 20, "public"),
+                ("wiki", "https://example.com/wiki", 30, "internal"),
+            ] {
+                links.add_named(key.to_string(), link.to_string()).unwrap();
+                let entry = links.get_mut(key).unwrap();
+                entry.metadata.used = used;
+                entry.tags.insert(tag.to_string());
+            }
+            let state = AppState::new(Config {
+                link_data_path: crate::links::IN_MEMORY_PATH.into(),
+                ..Config::default()
+            }, links);
+            let (addr, shutdown) = setup_test_api_with_state(state).await;
+            let query = |extra: &[(&'static str, &str)]| {
+                let mut query = vec![("q", "docs".to_string()), ("tag", "internal".to_string()), ("sort", "used".to_string())];
+                query.extend(extra.iter().map(|&(k, v)| (k, v.to_string())));
+                query
+            };
+            let keys = |data: &GetLinksResponse| data.items.iter().map(|e| e.key.clone()).collect::<Vec<_>>();
+
+            let data = get_links_with_query(&addr, &query(&[])).await;
+            assert_eq!(data.total, 3);
+            assert_eq!(data.total_clicks, 21);
+            assert_eq!(keys(&data), ["docs-guide", "handbook", "docs-api"]);
+
+            let data = get_links_with_query(&addr, &query(&[("offset", "1"), ("limit", "1")])).await;
+            assert_eq!(data.total, 3);
+            assert_eq!(keys(&data), ["handbook"]);
+
+            let data = get_links_with_query(&addr, &[("q", "DOCS-".to_string()), ("sort", "key".to_string())]).await;
+            assert_eq!(keys(&data), ["docs-api", "docs-guide", "docs-public"]);
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn pagination_and_stats() {
+            let links_path = random_links_path();
+            let (addr, shutdown) = setup_test_api(&links_path).await;
+            let client = reqwest::Client::new();
+
+            for (key, link) in [("key3", "https://example.com"), ("key1", "https://example.com"), ("key2", "https://example.org")] {
+                client.post(format!("{addr}/links"))
+                    .json(&AddLinkRequest { key: Some(key.to_string()), link: link.to_string(), ..Default::default() })
+                    .send().await.unwrap();
+            }
+
+            let data = get_links_with_query(&addr, &[("limit", "2".to_string())]).await;
+            assert_eq!(data.total, 3);
+            assert_eq!(data.total_clicks, 0);
+            assert_eq!(data.distinct_targets, 2);
+            assert_eq!(data.items.iter().map(|e| e.key.as_str()).collect::<Vec<_>>(), ["key1", "key2"]);
+
+            let data = get_links_with_query(&addr, &[("offset", "2".to_string()), ("limit", "2".to_string())]).await;
+            assert_eq!(data.total, 3);
+            assert_eq!(data.items.iter().map(|e| e.key.as_str()).collect::<Vec<_>>(), ["key3"]);
 
-            let data = body.success().unwrap();
-            assert_eq!(data.len(), 0);
-    
             shutdown.send(()).await.unwrap();
             cleanup(&links_path);
         }
+
+        #[tokio::test]
+        async fn acl() {
+            let (addr, shutdown) = super::get_link::setup_acl().await;
+            let get = |token: &str| reqwest::Client::new().get(format!("{addr}/links"))
+                .bearer_auth(token)
+                .send();
+
+            let data = get("alice-secret").await.unwrap()
+                .json::<Jsend<GetLinksResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(data.total, 1);
+            assert_eq!(data.items[0].key, "private");
+            let data = get("bob-secret").await.unwrap()
+                .json::<Jsend<GetLinksResponse, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(data.total, 0);
+            assert!(data.items.is_empty());
+
+            shutdown.send(()).await.unwrap();
+        }
     }
 }
\ No newline at end of file
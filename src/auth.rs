@@ -0,0 +1,119 @@
+//! Access control for the API.
+//!
+//! Handlers that need authentication take an [`Authorized`] argument. When no token is
+//! configured, authentication is disabled and every request is authorized.
+//!
+//! Handlers that modify the store take a [`Writable`] argument, which rejects the request
+//! when the instance is in read-only mode.
+//!
+//! Handlers for a single link take a [`Caller`] argument to check the link's access control
+//! list. The API token identifies an admin, who may access every link, and the configured
+//! user tokens identify users.
+
+use std::convert::Infallible;
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+
+use crate::{api::jsend::Jsend, AppState};
+
+/// Proof that the request carried the configured API token.
+pub struct Authorized;
+
+#[async_trait]
+impl FromRequestParts<AppState> for Authorized {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let Some(expected) = &state.config.api_token else {
+            return Ok(Authorized);
+        };
+
+        match bearer_token(parts) {
+            Some(token) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => Ok(Authorized),
+            _ => Err((
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+                Jsend::<(), ()>::Error("Missing or invalid API token".to_string()).with_status(StatusCode::UNAUTHORIZED),
+            ).into_response())
+        }
+    }
+}
+
+/// Who made the request, for checking the access control list of a link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Caller {
+    /// Authenticated with the API token, or authentication is disabled.
+    Admin,
+    /// Authenticated with the token of the named user.
+    User(String),
+    /// Sent no or an unknown token.
+    Anonymous,
+}
+
+impl Caller {
+    /// Whether the caller may access a link with the given access control list. An empty
+    /// list doesn't restrict access.
+    pub fn may_access(&self, acl: &[String]) -> bool {
+        match self {
+            Caller::Admin => true,
+            Caller::User(name) => acl.is_empty() || acl.contains(name),
+            Caller::Anonymous => acl.is_empty(),
+        }
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for Caller {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let Some(admin_token) = &state.config.api_token else {
+            return Ok(Caller::Admin);
+        };
+
+        let Some(token) = bearer_token(parts) else {
+            return Ok(Caller::Anonymous);
+        };
+        if constant_time_eq(token.as_bytes(), admin_token.as_bytes()) {
+            return Ok(Caller::Admin);
+        }
+        let user = state.config.user_tokens.iter()
+            .find(|(_, expected)| constant_time_eq(token.as_bytes(), expected.as_bytes()))
+            .map(|(name, _)| name.clone());
+        Ok(user.map_or(Caller::Anonymous, Caller::User))
+    }
+}
+
+/// The token sent in the `Authorization` header, if any.
+fn bearer_token(parts: &Parts) -> Option<&str> {
+    parts.headers.get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+}
+
+/// Compare two byte strings without leaking the position of the first difference.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Proof that the instance accepts modifications, i.e. is not in read-only mode.
+pub struct Writable;
+
+#[async_trait]
+impl FromRequestParts<AppState> for Writable {
+    type Rejection = Response;
+
+    async fn from_request_parts(_parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        if state.config.read_only {
+            Err(Jsend::<(), ()>::Error("This instance is read-only".to_string())
+                .with_status(StatusCode::FORBIDDEN)
+                .into_response())
+        } else {
+            Ok(Writable)
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Typed client for the landmower API.
+//!
+//! Only available with the `client` feature. The types mirror the request and response bodies
+//! of [`crate::api`], and every call returns the [`Jsend`] envelope the server responded with.
+//! Transport errors and bodies that aren't JSend are reported as [`reqwest::Error`].
+
+use reqwest::{header, Client, RequestBuilder, Url};
+use serde::{Deserialize, Serialize};
+
+use crate::{api::jsend::Jsend, links::{EntryMetadata, OnExpire}};
+
+/// A link to add.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct AddLinkRequest {
+    /// Key to store the link under. A key is generated if unset.
+    pub key: Option<String>,
+    pub link: String,
+    /// Password required to follow the link.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// Destination for clients that don't accept HTML.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alt_target: Option<String>,
+    /// When the link stops redirecting to its destination.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// What following the link does once it has expired. Defaults to `410 Gone`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_expire: Option<OnExpire>,
+}
+
+/// Why a link was rejected, per field.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct AddLinkFail {
+    pub key: Option<String>,
+    pub link: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub alt_target: Option<String>,
+    #[serde(default)]
+    pub expires_at: Option<String>,
+    #[serde(default)]
+    pub on_expire: Option<String>,
+}
+
+/// A stored link, as returned by the API.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LinkEntry {
+    pub key: String,
+    pub link: String,
+    pub metadata: EntryMetadata,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub password_protected: bool,
+    #[serde(default)]
+    pub alt_target: Option<String>,
+    /// Estimated number of distinct visitors.
+    #[serde(default)]
+    pub unique: u64,
+    /// Whether the destination responded when the link was added, if it was checked.
+    #[serde(default)]
+    pub reachable: Option<bool>,
+    /// Whether the destination responded when it was last checked for link rot.
+    #[serde(default)]
+    pub alive: Option<bool>,
+    #[serde(default)]
+    pub last_checked: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub on_expire: OnExpire,
+    /// Whether this is the canonical key of its link.
+    #[serde(default)]
+    pub primary: bool,
+    /// Whether the link is kept when deleting links, unless forced.
+    #[serde(default)]
+    pub protected: bool,
+    /// Users that may view or delete the link, everyone if empty.
+    #[serde(default)]
+    pub acl: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// A newly added link.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AddLinkSuccess {
+    pub key: String,
+    /// Whether the link was already stored, in which case `key` is its existing key.
+    #[serde(default)]
+    pub existing: bool,
+}
+
+/// A page of links with statistics about all links.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LinkPage {
+    pub total: usize,
+    pub total_clicks: u64,
+    pub distinct_targets: usize,
+    pub items: Vec<LinkEntry>,
+}
+
+/// Client for a landmower instance.
+#[derive(Clone)]
+pub struct LandmowerClient {
+    http: Client,
+    /// Base URL of the API, e.g. `https://short.example/api`.
+    base_url: Url,
+    token: Option<String>,
+}
+
+impl LandmowerClient {
+    /// Create a client for the API at the given base URL, e.g. `https://short.example/api`.
+    pub fn new(base_url: Url) -> Self {
+        Self { http: Client::new(), base_url, token: None }
+    }
+
+    /// Authenticate requests with the given API token.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub async fn add_link(&self, req: &AddLinkRequest) -> reqwest::Result<Jsend<AddLinkSuccess, AddLinkFail>> {
+        self.send(self.http.post(self.url(&["links"])).json(req)).await
+    }
+
+    pub async fn get_link(&self, key: &str) -> reqwest::Result<Jsend<LinkEntry, String>> {
+        self.send(self.http.get(self.url(&["links", key]))).await
+    }
+
+    pub async fn delete_link(&self, key: &str) -> reqwest::Result<Jsend<(), String>> {
+        self.send(self.http.delete(self.url(&["links", key]))).await
+    }
+
+    /// Delete a link even if it is protected.
+    pub async fn force_delete_link(&self, key: &str) -> reqwest::Result<Jsend<(), String>> {
+        self.send(self.http.delete(self.url(&["links", key])).query(&[("force", "true")])).await
+    }
+
+    pub async fn get_links(&self) -> reqwest::Result<Jsend<LinkPage, ()>> {
+        self.send(self.http.get(self.url(&["links"]))).await
+    }
+
+    /// Check whether a link would be accepted, without adding it.
+    pub async fn validate(&self, req: &AddLinkRequest) -> reqwest::Result<Jsend<(), AddLinkFail>> {
+        self.send(self.http.post(self.url(&["validate", "add_link"])).json(req)).await
+    }
+
+    /// Append percent-encoded path segments to the base URL.
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base_url.clone();
+        if let Ok(mut path) = url.path_segments_mut() {
+            path.pop_if_empty().extend(segments);
+        }
+        url
+    }
+
+    async fn send<T, F>(&self, req: RequestBuilder) -> reqwest::Result<Jsend<T, F>>
+    where
+        T: serde::de::DeserializeOwned,
+        F: serde::de::DeserializeOwned,
+    {
+        let req = match &self.token {
+            Some(token) => req.header(header::AUTHORIZATION, format!("Bearer {token}")),
+            None => req,
+        };
+        req.send().await?.json().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::temp_dir, path::Path};
+
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::{api, AppState, Config, Links};
+
+    async fn setup_test_client(links_path: &Path) -> LandmowerClient {
+        let state = AppState::new(Config {
+            link_data_path: links_path.to_path_buf(),
+            ..Config::default()
+        }, Links::new());
+        let router = axum::Router::new()
+            .nest("/api", api::router(&state.config))
+            .with_state(state);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+        LandmowerClient::new(format!("http://{addr}/api/").parse().unwrap())
+    }
+
+    #[tokio::test]
+    async fn add_get_delete() {
+        let links_path = temp_dir().join("landmower_test_client_add_get_delete.toml");
+        let client = setup_test_client(&links_path).await;
+        let req = AddLinkRequest {
+            key: Some("docs".to_string()),
+            link: "https://example.com/docs".to_string(),
+            ..Default::default()
+        };
+
+        assert!(client.validate(&req).await.unwrap().is_success());
+        let added = client.add_link(&req).await.unwrap().success().unwrap();
+        assert_eq!(added.key, "docs");
+        assert!(!added.existing);
+
+        let fail = client.validate(&req).await.unwrap().fail().unwrap();
+        assert!(fail.key.is_some());
+
+        let entry = client.get_link("docs").await.unwrap().success().unwrap();
+        assert_eq!(entry.link, "https://example.com/docs");
+        assert_eq!(client.get_links().await.unwrap().success().unwrap().items, [entry]);
+
+        assert!(client.delete_link("docs").await.unwrap().is_success());
+        assert!(client.get_link("docs").await.unwrap().is_fail());
+
+        let _ = std::fs::remove_file(&links_path);
+    }
+
+    #[tokio::test]
+    async fn keys_are_encoded() {
+        let client = setup_test_client(&temp_dir().join("landmower_test_client_keys.toml")).await;
+        assert!(client.get_link("../links").await.unwrap().is_fail());
+        assert!(client.get_link("a key/with?odd#chars").await.unwrap().is_fail());
+    }
+}
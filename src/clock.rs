@@ -0,0 +1,73 @@
+//! The source of the current time for everything time-dependent, like link expiry and the
+//! grace window of deleted keys.
+//!
+//! [`AppState`](crate::AppState) and [`Links`](crate::links::Links) hold a [`Clock`], which is
+//! the [`SystemClock`] unless replaced, so tests can control time with a [`MockClock`].
+//! Monotonic intervals, e.g. of the metadata worker, are measured with
+//! [`std::time::Instant`] instead.
+
+use std::{fmt, sync::{Arc, Mutex}};
+
+use chrono::{DateTime, Duration, Utc};
+
+/// A source of the current time.
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// A clock shared by the parts of the server that read it.
+pub type SharedClock = Arc<dyn Clock>;
+
+/// The system's wall clock.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when told to.
+#[derive(Debug)]
+pub struct MockClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl MockClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self { now: Mutex::new(now) }
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_clock() {
+        let start = Utc::now();
+        let clock = MockClock::new(start);
+        assert_eq!(clock.now(), start);
+
+        clock.advance(Duration::hours(1));
+        assert_eq!(clock.now(), start + Duration::hours(1));
+
+        clock.set(start);
+        assert_eq!(clock.now(), start);
+    }
+}
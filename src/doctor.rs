@@ -0,0 +1,149 @@
+//! Diagnosing a deployment without starting the server, run as `landmower doctor`.
+//!
+//! The checks never modify the link data file, so they are safe to run next to a live
+//! instance. Checking whether the data directory is writable creates and removes a probe file
+//! in it.
+
+use std::{fmt, path::Path};
+
+use crate::{api::RESERVED_KEYS, links::{self, Links}, Config};
+
+/// Outcome of all checks.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// What was checked successfully.
+    pub notes: Vec<String>,
+    /// Problems that need attention.
+    pub problems: Vec<String>,
+}
+
+impl Report {
+    pub fn is_healthy(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for note in &self.notes {
+            writeln!(f, "ok: {note}")?;
+        }
+        for problem in &self.problems {
+            writeln!(f, "problem: {problem}")?;
+        }
+        match self.problems.len() {
+            0 => write!(f, "No problems found"),
+            1 => write!(f, "1 problem found"),
+            n => write!(f, "{n} problems found"),
+        }
+    }
+}
+
+/// Run all checks against the configured link data.
+pub fn run(config: &Config) -> Report {
+    let mut report = Report::default();
+    let path = &config.link_data_path;
+    if links::is_in_memory(path) {
+        report.notes.push("Links are kept in memory only, nothing to check on disk".to_string());
+        return report;
+    }
+
+    match path.parent() {
+        Some(dir) => match check_writable(dir) {
+            Ok(()) => report.notes.push(format!("Data directory '{}' is writable", dir.display())),
+            Err(e) => report.problems.push(e),
+        },
+        None => report.problems.push(format!("Invalid link data path: '{}'", path.display())),
+    }
+
+    if !path.exists() {
+        report.problems.push(format!("Link data file '{}' does not exist", path.display()));
+        return report;
+    }
+    // The file exists, so loading only reads it.
+    let links: Links = match Links::load(path) {
+        Ok(links) => links,
+        Err(e) => {
+            report.problems.push(e);
+            return report;
+        }
+    };
+    report.notes.push(format!(
+        "Parsed {} links from '{}' in the {:?} storage format",
+        links.len(), path.display(), links.storage_format()
+    ));
+
+    let inconsistencies = links.verify();
+    if inconsistencies.is_empty() {
+        report.notes.push("Reverse lookup is consistent".to_string());
+    }
+    report.problems.extend(inconsistencies);
+
+    let mut disallowed = links.iter()
+        .map(|(key, _)| key)
+        .filter(|key| config.key_blacklist.contains(key) || RESERVED_KEYS.iter().any(|k| k.eq_ignore_ascii_case(key)))
+        .map(|key| format!("Key '{key}' is stored but blacklisted or reserved"))
+        .collect::<Vec<_>>();
+    disallowed.sort_unstable();
+    report.problems.extend(disallowed);
+
+    report
+}
+
+/// Check that files can be created in the given directory.
+fn check_writable(dir: &Path) -> Result<(), String> {
+    let probe = dir.join(format!(".landmower-doctor-{}", std::process::id()));
+    std::fs::write(&probe, "")
+        .map_err(|e| format!("Data directory '{}' is not writable: {e}", dir.display()))?;
+    std::fs::remove_file(&probe)
+        .map_err(|e| format!("Could not remove '{}': {e}", probe.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+
+    use super::*;
+
+    #[test]
+    fn healthy() {
+        let path = temp_dir().join("landmower_test_doctor_healthy.toml");
+        let mut links = Links::new();
+        links.add_named("key1".to_string(), "https://example.com".to_string()).unwrap();
+        links.save(&path).unwrap();
+        let data = std::fs::read_to_string(&path).unwrap();
+
+        let report = run(&Config { link_data_path: path.clone(), ..Config::default() });
+        assert!(report.is_healthy(), "{report}");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), data);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn problems() {
+        let path = temp_dir().join("landmower_test_doctor_problems.toml");
+        let mut links = Links::new();
+        links.add_named("admin".to_string(), "https://example.com/admin".to_string()).unwrap();
+        links.add_named("Import".to_string(), "https://example.com/import".to_string()).unwrap();
+        links.save(&path).unwrap();
+
+        let report = run(&Config {
+            link_data_path: path.clone(),
+            key_blacklist: vec!["admin".to_string()],
+            ..Config::default()
+        });
+        assert_eq!(report.problems.len(), 2, "{report}");
+
+        std::fs::write(&path, "not toml").unwrap();
+        let report = run(&Config { link_data_path: path.clone(), ..Config::default() });
+        assert!(!report.is_healthy());
+
+        std::fs::remove_file(&path).unwrap();
+
+        // A missing file is reported instead of created
+        let report = run(&Config { link_data_path: path.clone(), ..Config::default() });
+        assert!(!report.is_healthy());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,419 @@
+//! Persistent log of link accesses.
+//!
+//! Access events are appended as newline-delimited JSON by the metadata worker. Reading the
+//! log only touches the file, never the link store or the event queue used by redirects.
+//!
+//! Daily click counts are aggregated from the log once at startup and kept up to date by the
+//! metadata worker, see [`DailyCounts`].
+//!
+//! The same format is used to carry events that are still queued over a restart, see
+//! [`persist_queue`] and [`restore_queue`].
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fs::OpenOptions,
+    io::{self, BufRead as _, BufReader, Write as _},
+    path::Path,
+};
+
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use concurrent_queue::ConcurrentQueue;
+use futures_util::{stream, Stream};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncBufReadExt as _;
+
+use crate::LinkAccessEvent;
+
+/// Number of access events buffered for each client tailing the log. Clients that fall
+/// further behind skip the oldest events.
+pub const TAIL_CAPACITY: usize = 1024;
+
+/// A single access, as stored in the log.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LoggedAccess {
+    pub key: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl From<LoggedAccess> for LinkAccessEvent {
+    fn from(access: LoggedAccess) -> Self {
+        Self {
+            key: access.key,
+            timestamp: access.timestamp.into(),
+            // Visitors are never logged.
+            visitor: None,
+        }
+    }
+}
+
+impl From<&LinkAccessEvent> for LoggedAccess {
+    fn from(event: &LinkAccessEvent) -> Self {
+        Self {
+            key: event.key.clone(),
+            timestamp: event.timestamp.into(),
+        }
+    }
+}
+
+/// Which entries of the log belong to a key.
+#[derive(Clone)]
+pub struct KeyFilter {
+    key: String,
+    case_insensitive: bool,
+}
+
+impl KeyFilter {
+    pub fn new(key: &str, case_insensitive: bool) -> Self {
+        Self { key: key.to_string(), case_insensitive }
+    }
+
+    pub fn matches(&self, key: &str) -> bool {
+        if self.case_insensitive {
+            key.to_lowercase() == self.key.to_lowercase()
+        } else {
+            key == self.key
+        }
+    }
+}
+
+/// Which entries of the log to read, see [`read`]. All given conditions must match.
+#[derive(Clone, Default)]
+pub struct LogFilter {
+    pub key: Option<KeyFilter>,
+    /// Only accesses at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only accesses before this time.
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl LogFilter {
+    pub fn matches(&self, access: &LoggedAccess) -> bool {
+        self.key.as_ref().is_none_or(|key| key.matches(&access.key))
+            && self.since.is_none_or(|t| access.timestamp >= t)
+            && self.until.is_none_or(|t| access.timestamp < t)
+    }
+}
+
+impl From<KeyFilter> for LogFilter {
+    fn from(key: KeyFilter) -> Self {
+        Self { key: Some(key), ..Self::default() }
+    }
+}
+
+/// Order in which [`read`] returns accesses.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum LogOrder {
+    #[default]
+    #[serde(rename = "time")]
+    OldestFirst,
+    #[serde(rename = "-time")]
+    NewestFirst,
+}
+
+/// Append the given accesses to the log, creating it if needed.
+pub fn append(path: &Path, accesses: &[LoggedAccess]) -> io::Result<()> {
+    if accesses.is_empty() {
+        return Ok(());
+    }
+
+    let mut buf = Vec::new();
+    for access in accesses {
+        serde_json::to_writer(&mut buf, access)?;
+        buf.push(b'\n');
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    OpenOptions::new().create(true).append(true).open(path)?
+        .write_all(&buf)
+}
+
+/// Read up to `limit` matching accesses in the given order, skipping the first `offset`.
+///
+/// The log is read line by line, holding at most `offset + limit` accesses when reading
+/// newest first. A missing log is treated as empty. Malformed lines are skipped.
+pub fn read(path: &Path, filter: &LogFilter, order: LogOrder, offset: usize, limit: usize) -> io::Result<Vec<LoggedAccess>> {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    let matching = BufReader::new(file).lines()
+        .filter_map(|line| line.map(|line| parse_line(&line)).transpose())
+        .filter(|access| access.as_ref().is_ok_and(|access| filter.matches(access)));
+
+    match order {
+        LogOrder::OldestFirst => matching.skip(offset).take(limit).collect(),
+        LogOrder::NewestFirst => {
+            // Keep only the newest accesses that can end up on the page.
+            let window = offset.saturating_add(limit);
+            let mut newest = VecDeque::new();
+            for access in matching {
+                newest.push_back(access?);
+                if newest.len() > window {
+                    newest.pop_front();
+                }
+            }
+            Ok(newest.into_iter().rev().skip(offset).take(limit).collect())
+        },
+    }
+}
+
+/// Stream all accesses of a key as newline-delimited JSON, oldest first.
+///
+/// A missing log is treated as empty.
+pub async fn stream(path: &Path, filter: KeyFilter) -> io::Result<impl Stream<Item = io::Result<String>>> {
+    let lines = match tokio::fs::File::open(path).await {
+        Ok(file) => Some(tokio::io::BufReader::new(file).lines()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+
+    Ok(stream::try_unfold((lines, filter), |(mut lines, filter)| async move {
+        let Some(reader) = &mut lines else {
+            return Ok(None);
+        };
+        while let Some(line) = reader.next_line().await? {
+            if parse_line(&line).is_some_and(|access| filter.matches(&access.key)) {
+                return Ok(Some((line + "\n", (lines, filter))));
+            }
+        }
+        Ok(None)
+    }))
+}
+
+/// Number of clicks of a link on a single day (UTC).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DayCount {
+    pub date: NaiveDate,
+    pub clicks: u64,
+}
+
+/// Clicks per link and day (UTC), aggregated from the log.
+#[derive(Default)]
+pub struct DailyCounts {
+    case_insensitive: bool,
+    counts: HashMap<String, BTreeMap<NaiveDate, u64>>,
+}
+
+impl DailyCounts {
+    pub fn new(case_insensitive: bool) -> Self {
+        Self { case_insensitive, counts: HashMap::new() }
+    }
+
+    /// Aggregate all accesses in the log. A missing log is treated as empty.
+    pub fn load(path: &Path, case_insensitive: bool) -> io::Result<Self> {
+        let mut counts = Self::new(case_insensitive);
+        let file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(counts),
+            Err(e) => return Err(e),
+        };
+
+        for line in BufReader::new(file).lines() {
+            if let Some(access) = parse_line(&line?) {
+                counts.record(&access);
+            }
+        }
+        Ok(counts)
+    }
+
+    pub fn record(&mut self, access: &LoggedAccess) {
+        *self.counts.entry(self.normalize(&access.key))
+            .or_default()
+            .entry(access.timestamp.date_naive())
+            .or_default() += 1;
+    }
+
+    /// Clicks of a key on each of the `days` days up to and including `end`, oldest first.
+    /// 
+    /// Days without clicks are included with a count of zero.
+    pub fn last_days(&self, key: &str, end: NaiveDate, days: u64) -> Vec<DayCount> {
+        let Some(start) = end.checked_sub_days(Days::new(days.saturating_sub(1))) else {
+            return vec![];
+        };
+        let counts = self.counts.get(&self.normalize(key));
+
+        start.iter_days()
+            .take_while(|date| *date <= end)
+            .map(|date| DayCount {
+                date,
+                clicks: counts.and_then(|c| c.get(&date)).copied().unwrap_or(0),
+            })
+            .collect()
+    }
+
+    fn normalize(&self, key: &str) -> String {
+        if self.case_insensitive { key.to_lowercase() } else { key.to_string() }
+    }
+}
+
+/// Drain the queue into the given file, so the events can be restored after a restart.
+/// 
+/// Returns the number of persisted events.
+pub fn persist_queue(path: &Path, queue: &ConcurrentQueue<LinkAccessEvent>) -> io::Result<usize> {
+    let pending = queue.try_iter()
+        .map(|event| LoggedAccess::from(&event))
+        .collect::<Vec<_>>();
+    append(path, &pending)?;
+    Ok(pending.len())
+}
+
+/// Push the events persisted by [`persist_queue`] back onto the queue and remove the file.
+/// 
+/// The file is removed before any event is queued, so a failed restore never leaves behind
+/// events that would be counted again on the next start.
+/// 
+/// Returns the number of restored events. A missing file restores nothing.
+pub fn restore_queue(path: &Path, queue: &ConcurrentQueue<LinkAccessEvent>) -> io::Result<usize> {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+
+    let mut pending = Vec::new();
+    for line in BufReader::new(file).lines() {
+        pending.extend(parse_line(&line?));
+    }
+    std::fs::remove_file(path)?;
+
+    let restored = pending.len();
+    for access in pending {
+        queue.push(access.into())
+            .map_err(|e| io::Error::other(e.to_string()))?;
+    }
+    Ok(restored)
+}
+
+fn parse_line(line: &str) -> Option<LoggedAccess> {
+    serde_json::from_str(line)
+        .inspect_err(|e| tracing::debug!("Skipping malformed access log line: {e}"))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+
+    use super::*;
+
+    fn access(key: &str, secs: i64) -> LoggedAccess {
+        LoggedAccess { key: key.to_string(), timestamp: DateTime::from_timestamp(secs, 0).unwrap() }
+    }
+
+    #[test]
+    fn append_read() {
+        let path = temp_dir().join("landmower_test_event_log_append_read.ndjson");
+        let _ = std::fs::remove_file(&path);
+
+        append(&path, &[access("key1", 1), access("key2", 2)]).unwrap();
+        append(&path, &[access("Key1", 3), access("key1", 4)]).unwrap();
+
+        let filter = KeyFilter::new("key1", false).into();
+        let oldest = LogOrder::OldestFirst;
+        assert_eq!(read(&path, &filter, oldest, 0, 10).unwrap(), [access("key1", 1), access("key1", 4)]);
+        assert_eq!(read(&path, &filter, oldest, 1, 10).unwrap(), [access("key1", 4)]);
+        assert_eq!(read(&path, &filter, oldest, 0, 1).unwrap(), [access("key1", 1)]);
+
+        let filter = KeyFilter::new("KEY1", true).into();
+        assert_eq!(read(&path, &filter, oldest, 0, 10).unwrap().len(), 3);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn read_time_range_newest_first() {
+        let path = temp_dir().join("landmower_test_event_log_newest_first.ndjson");
+        let _ = std::fs::remove_file(&path);
+
+        append(&path, &(1..=6).map(|secs| access(if secs % 2 == 0 { "even" } else { "odd" }, secs)).collect::<Vec<_>>()).unwrap();
+
+        let all = LogFilter::default();
+        let newest = LogOrder::NewestFirst;
+        assert_eq!(read(&path, &all, newest, 0, 2).unwrap(), [access("even", 6), access("odd", 5)]);
+        assert_eq!(read(&path, &all, newest, 2, 2).unwrap(), [access("even", 4), access("odd", 3)]);
+        assert_eq!(read(&path, &all, newest, 5, 2).unwrap(), [access("odd", 1)]);
+
+        let filter = LogFilter {
+            key: Some(KeyFilter::new("odd", false)),
+            since: DateTime::from_timestamp(2, 0),
+            until: DateTime::from_timestamp(5, 0),
+        };
+        assert_eq!(read(&path, &filter, newest, 0, 10).unwrap(), [access("odd", 3)]);
+        assert_eq!(read(&path, &filter, LogOrder::OldestFirst, 0, 10).unwrap(), [access("odd", 3)]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn persist_restore_queue() {
+        let path = temp_dir().join("landmower_test_event_log_pending.ndjson");
+        let _ = std::fs::remove_file(&path);
+
+        let queue = ConcurrentQueue::unbounded();
+        for (key, secs) in [("key1", 1), ("key2", 2), ("key1", 3)] {
+            queue.push(access(key, secs).into()).unwrap();
+        }
+
+        assert_eq!(persist_queue(&path, &queue).unwrap(), 3);
+        assert!(queue.is_empty());
+
+        let restored = ConcurrentQueue::unbounded();
+        assert_eq!(restore_queue(&path, &restored).unwrap(), 3);
+        assert!(!path.exists());
+
+        let events = restored.try_iter()
+            .map(|event| LoggedAccess::from(&event))
+            .collect::<Vec<_>>();
+        assert_eq!(events, [access("key1", 1), access("key2", 2), access("key1", 3)]);
+
+        // Nothing left to restore
+        assert_eq!(restore_queue(&path, &restored).unwrap(), 0);
+    }
+
+    #[test]
+    fn restore_queue_failed() {
+        let path = temp_dir().join("landmower_test_event_log_pending_failed.ndjson");
+        append(&path, &[access("key1", 1)]).unwrap();
+        // Not valid UTF-8, so reading fails after the first event
+        std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"\xff\n").unwrap();
+
+        // Nothing is queued, so restoring the file again doesn't count anything twice
+        let queue = ConcurrentQueue::unbounded();
+        assert!(restore_queue(&path, &queue).is_err());
+        assert!(queue.is_empty());
+        assert!(path.exists());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn daily_counts() {
+        let path = temp_dir().join("landmower_test_event_log_daily_counts.ndjson");
+        let _ = std::fs::remove_file(&path);
+
+        const DAY: i64 = 24 * 60 * 60;
+        append(&path, &[access("key1", 0), access("key1", 10), access("KEY1", 2 * DAY), access("key2", DAY)]).unwrap();
+
+        let end = NaiveDate::from_ymd_opt(1970, 1, 4).unwrap();
+        let counts = DailyCounts::load(&path, true).unwrap();
+        let clicks = counts.last_days("key1", end, 5).iter().map(|d| d.clicks).collect::<Vec<_>>();
+        assert_eq!(clicks, [0, 2, 0, 1, 0]);
+        assert_eq!(counts.last_days("key1", end, 5)[1].date, NaiveDate::from_ymd_opt(1970, 1, 1).unwrap());
+
+        let mut counts = DailyCounts::load(&path, false).unwrap();
+        counts.record(&access("key1", 3 * DAY));
+        let clicks = counts.last_days("key1", end, 3).iter().map(|d| d.clicks).collect::<Vec<_>>();
+        assert_eq!(clicks, [0, 0, 1]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn read_missing() {
+        let path = temp_dir().join("landmower_test_event_log_missing.ndjson");
+        assert!(read(&path, &KeyFilter::new("key1", false).into(), LogOrder::OldestFirst, 0, 10).unwrap().is_empty());
+    }
+}
@@ -1,57 +1,622 @@
 #![feature(try_trait_v2)]
-use std::{path::PathBuf, sync::Arc};
+use std::{collections::{BTreeMap, HashMap}, path::PathBuf, sync::{atomic::{AtomicU64, Ordering}, Arc}, time::Duration};
 
+pub mod access_log;
 pub mod api;
+pub mod auth;
+pub mod clock;
+pub mod doctor;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod event_log;
 pub mod links;
+pub mod probe;
+pub mod rate_limit;
+pub mod signing;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(unix)]
+pub mod unix_socket;
+pub mod visitors;
+pub mod webhook;
 
 use concurrent_queue::ConcurrentQueue;
-use links::Links;
+use access_log::AccessLogFormat;
+use links::{KeyCharset, KeyStrategy, Links, StorageFormat, TrailingSlash};
+use probe::VerifyReachable;
 use minijinja::context;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, Mutex, RwLock, RwLockWriteGuard};
 
 
 #[derive(Debug)]
 pub struct LinkAccessEvent {
     pub key: String,
     pub timestamp: std::time::SystemTime,
+    /// [`visitors::visitor_hash`] of whoever followed the link, if known.
+    pub visitor: Option<u64>,
 }
 
 #[derive(Clone)]
 pub struct AppState {
     pub config: Arc<Config>,
     pub links: Arc<RwLock<Links>>,
-    pub access_event_queue: Arc<ConcurrentQueue<LinkAccessEvent>>
+    pub access_event_queue: Arc<ConcurrentQueue<LinkAccessEvent>>,
+    /// Client used for outbound requests to link destinations and webhooks.
+    pub http_client: reqwest::Client,
+    /// Probes link destinations with the client above, see [`Config::probe_concurrency`].
+    pub prober: probe::Prober,
+    /// Clicks per link and day, only tracked when the access log is enabled.
+    pub daily_clicks: Arc<RwLock<event_log::DailyCounts>>,
+    /// When this process started serving.
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    /// Redirects served since startup. Unlike the `used` counts of links, this is not
+    /// persisted.
+    pub redirects_served: Arc<AtomicU64>,
+    /// Access events applied by the metadata worker, for clients tailing the access log.
+    /// Only sent while someone is subscribed.
+    pub access_tail: broadcast::Sender<event_log::LoggedAccess>,
+    /// When recently deleted keys were deleted, see [`Config::deleted_grace`].
+    pub tombstones: Arc<RwLock<HashMap<String, chrono::DateTime<chrono::Utc>>>>,
+    /// Limits how often each key is followed, if configured.
+    pub key_rate_limiter: Option<Arc<rate_limit::KeyRateLimiter>>,
+    /// Time source for expiry, grace windows and timestamps, the system clock outside tests.
+    pub clock: clock::SharedClock,
+    /// Where redirects are logged for log analysis tools, if configured.
+    pub access_log: Option<Arc<access_log::AccessLog>>,
+    link_writer: Arc<LinkWriter>,
+    link_counts: Arc<std::sync::Mutex<LinkCounts>>,
+}
+
+/// Snapshot of the `used` counter of every link, see [`AppState::link_counts`].
+#[derive(Default)]
+struct LinkCounts {
+    /// [`Links::revision`] of the links the snapshot was taken from.
+    revision: u64,
+    counts: Arc<HashMap<String, LinkCount>>,
+}
+
+impl LinkCounts {
+    fn take(links: &Links) -> Self {
+        Self {
+            revision: links.revision(),
+            counts: Arc::new(links.iter()
+                .map(|(key, entry)| (key.clone(), LinkCount { used: entry.metadata.used, acl: entry.acl.clone() }))
+                .collect()),
+        }
+    }
+}
+
+/// The `used` counter of a link in [`AppState::link_counts`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkCount {
+    pub used: u64,
+    /// Access control list of the link, so the snapshot can be filtered by caller.
+    pub acl: Vec<String>,
+}
+
+/// Orders writes of the link data file, see [`AppState::save_links`].
+#[derive(Default)]
+struct LinkWriter {
+    /// Generation of the most recently serialized links, only changed under the write lock.
+    serialized: AtomicU64,
+    /// Generation of the links in the file.
+    written: Mutex<u64>,
+    /// [`Links::revision`] of the links in the file.
+    saved_revision: AtomicU64,
+}
+
+impl AppState {
+    pub fn new(config: Config, links: Links) -> Self {
+        Self::with_clock(config, links, Arc::new(clock::SystemClock))
+    }
+
+    /// Like [`AppState::new`], but with the given clock instead of the system clock.
+    pub fn with_clock(config: Config, mut links: Links, clock: clock::SharedClock) -> Self {
+        links.set_clock(clock.clone());
+        links.set_case_insensitive(config.case_insensitive_keys);
+        links.set_reverse_lookup(config.reverse_lookup);
+        links.set_key_strategy(config.key_strategy);
+        links.set_key_max_attempts(config.key_max_attempts);
+        links.set_key_charset(config.key_charset.clone());
+        links.set_key_prefix(config.key_prefix.clone());
+        links.set_storage_format(config.storage_format);
+        links.set_stats_sidecar(config.stats_sidecar);
+        links.set_compressed(config.compress_storage || links::is_compressed_path(&config.link_data_path));
+        links.set_disallowed_keys(config.key_blacklist.iter().map(String::as_str).chain(api::RESERVED_KEYS.iter().copied()));
+        let daily_clicks = match &config.event_log_path {
+            Some(path) => event_log::DailyCounts::load(path, config.case_insensitive_keys)
+                .unwrap_or_else(|e| {
+                    tracing::error!("Failed to aggregate access log: {e}");
+                    event_log::DailyCounts::new(config.case_insensitive_keys)
+                }),
+            None => event_log::DailyCounts::new(config.case_insensitive_keys),
+        };
+        let access_log = access_log::AccessLog::open(config.access_log_format, config.access_log_path.as_deref())
+            .unwrap_or_else(|e| {
+                tracing::error!("Failed to open access log: {e}");
+                None
+            });
+        let http_client = probe::client(&config);
+        Self {
+            prober: probe::Prober::new(http_client.clone(), config.probe_concurrency),
+            http_client,
+            daily_clicks: Arc::new(RwLock::new(daily_clicks)),
+            started_at: clock.now(),
+            redirects_served: Arc::new(AtomicU64::new(0)),
+            access_tail: broadcast::channel(event_log::TAIL_CAPACITY).0,
+            tombstones: Arc::new(RwLock::new(HashMap::new())),
+            key_rate_limiter: config.key_rate_limit
+                .map(|limit| Arc::new(rate_limit::KeyRateLimiter::new(limit, config.key_rate_window))),
+            clock,
+            access_log: access_log.map(Arc::new),
+            link_writer: Arc::new(LinkWriter::default()),
+            link_counts: Arc::new(std::sync::Mutex::new(LinkCounts::take(&links))),
+            config: Arc::new(config),
+            links: Arc::new(RwLock::new(links)),
+            access_event_queue: Arc::new(ConcurrentQueue::unbounded()),
+        }
+    }
+}
+
+impl AppState {
+    /// Save the links without blocking the async runtime on disk I/O.
+    /// 
+    /// The links are serialized under the given write lock, which is released before the file
+    /// is written on the blocking thread pool. Saves may finish out of order, but never
+    /// replace the file with older data than it already holds.
+    pub async fn save_links(&self, links: RwLockWriteGuard<'_, Links>) -> Result<(), String> {
+        if links::is_in_memory(&self.config.link_data_path) {
+            return Ok(());
+        }
+        let data = links.to_toml()?;
+        let generation = self.link_writer.serialized.fetch_add(1, Ordering::Relaxed) + 1;
+        let revision = links.revision();
+        drop(links);
+
+        let mut written = self.link_writer.written.lock().await;
+        if *written > generation {
+            return Ok(());
+        }
+        let path = self.config.link_data_path.clone();
+        tokio::task::spawn_blocking(move || links::write_file(&path, &data))
+            .await
+            .map_err(|e| format!("Could not write links: {e}"))??;
+        *written = generation;
+        self.link_writer.saved_revision.fetch_max(revision, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Save the links if they changed since they were last saved, see [`Links::revision`].
+    /// 
+    /// Returns whether the links were saved.
+    pub async fn save_links_if_changed(&self) -> Result<bool, String> {
+        if links::is_in_memory(&self.config.link_data_path) {
+            return Ok(false);
+        }
+        let links = self.links.write().await;
+        if links.revision() <= self.link_writer.saved_revision.load(Ordering::Relaxed) {
+            return Ok(false);
+        }
+        self.save_links(links).await?;
+        Ok(true)
+    }
+}
+
+impl AppState {
+    /// Snapshot of the `used` counter of every link.
+    /// 
+    /// The snapshot is kept outside of [`AppState::links`] and updated by the metadata worker
+    /// as it applies access events, so reading it doesn't wait for the worker or hold up
+    /// redirects. Other changes, like added or deleted links, are picked up by the next read
+    /// that finds the links unlocked. The snapshot reflects the store at a single point in
+    /// time, but lags behind clicks that are still queued.
+    pub fn link_counts(&self) -> Arc<HashMap<String, LinkCount>> {
+        if let Ok(links) = self.links.try_read() {
+            let mut snapshot = self.link_counts.lock().unwrap();
+            if snapshot.revision != links.revision() {
+                *snapshot = LinkCounts::take(&links);
+            }
+        }
+        self.link_counts.lock().unwrap().counts.clone()
+    }
+
+    /// Update the snapshot of counters after the metadata worker applied access events.
+    /// 
+    /// If the snapshot was up to date at `base_revision`, only the counters of the `changed`
+    /// keys are replaced. Otherwise it is taken again from scratch.
+    pub fn update_link_counts(&self, links: &Links, base_revision: u64, changed: &[(String, u64)]) {
+        let mut snapshot = self.link_counts.lock().unwrap();
+        let in_sync = snapshot.revision == base_revision
+            && changed.iter().all(|(key, _)| snapshot.counts.contains_key(key));
+        if !in_sync {
+            *snapshot = LinkCounts::take(links);
+            return;
+        }
+        let counts = Arc::make_mut(&mut snapshot.counts);
+        for (key, used) in changed {
+            if let Some(count) = counts.get_mut(key) {
+                count.used = *used;
+            }
+        }
+        snapshot.revision = links.revision();
+    }
+
+    /// Remember that the link with the given key was deleted, if deleted keys are kept for a
+    /// grace window.
+    pub async fn bury(&self, key: &str) {
+        if self.config.deleted_grace.is_some() {
+            self.tombstones.write().await.insert(self.tombstone_key(key), self.clock.now());
+        }
+    }
+
+    /// When the link with the given key was deleted, if that was within the grace window.
+    pub async fn deleted_at(&self, key: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+        let grace = self.config.deleted_grace?;
+        let deleted_at = *self.tombstones.read().await.get(&self.tombstone_key(key))?;
+        (self.clock.now() - deleted_at).to_std().is_ok_and(|age| age < grace).then_some(deleted_at)
+    }
+
+    /// Forget keys deleted before the grace window.
+    /// 
+    /// Returns the number of keys forgotten.
+    pub async fn purge_tombstones(&self) -> usize {
+        let Some(grace) = self.config.deleted_grace else {
+            return 0;
+        };
+        let now = self.clock.now();
+        let mut tombstones = self.tombstones.write().await;
+        let before = tombstones.len();
+        tombstones.retain(|_, deleted_at| (now - *deleted_at).to_std().is_ok_and(|age| age < grace));
+        before - tombstones.len()
+    }
+
+    fn tombstone_key(&self, key: &str) -> String {
+        if self.config.case_insensitive_keys { key.to_lowercase() } else { key.to_string() }
+    }
 }
 
 impl Default for AppState {
     fn default() -> Self {
-        Self {
-            config: Arc::new(Config::from_env()),
-            links: Arc::new(RwLock::new(Links::default())),
-            access_event_queue: Arc::new(ConcurrentQueue::unbounded())
+        Self::new(Config::from_env(), Links::new())
+    }
+}
+
+/// Wait until the process is asked to shut down, by Ctrl+C or, on Unix, by `SIGTERM` as sent
+/// by service managers and container runtimes.
+pub async fn shutdown_signal() {
+    let ctrl_c = std::pin::pin!(async {
+        let _ = tokio::signal::ctrl_c().await;
+    });
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                let terminate = std::pin::pin!(terminate.recv());
+                futures_util::future::select(ctrl_c, terminate).await;
+                return;
+            },
+            Err(e) => tracing::error!("Failed to listen for SIGTERM: {e}"),
         }
     }
+    ctrl_c.await;
 }
 
 #[derive(Clone)]
 pub struct Config {
+    /// File the links are stored in, or [`links::IN_MEMORY_PATH`] to never persist them.
     pub link_data_path: PathBuf,
+    /// Address to listen on, either `host:port` or `unix:/path/to.sock` for a Unix socket.
     pub bind_address: String,
+    /// Prefix of the short URLs of links, set with `LANDMOWER_BASE_URL`. This must be set to
+    /// wherever clients reach the redirects, unless `bind_address` is a specific public
+    /// address, in which case the `/go/` route on that address is used by default.
     pub server_base_url: String,
-    pub key_blacklist: Vec<String>
+    pub key_blacklist: Vec<String>,
+    /// Maximum accepted request body size for the API, in bytes.
+    pub max_body_bytes: usize,
+    /// Fetch the page title of new links in the background.
+    pub fetch_titles: bool,
+    /// Timeout for outbound requests to link destinations.
+    pub fetch_timeout: Duration,
+    /// Maximum number of link destinations probed at once, e.g. to check reachability or
+    /// fetch titles.
+    pub probe_concurrency: usize,
+    /// Normalize links before storing them, so trivially equivalent URLs share a key.
+    pub normalize_urls: bool,
+    /// Trailing slash policy used when normalizing links.
+    pub trailing_slash: TrailingSlash,
+    /// Scheme, without `://`, used when redirecting to stored links without one, e.g. from
+    /// data files written before links were validated.
+    pub default_scheme: String,
+    /// Match keys case-insensitively.
+    pub case_insensitive_keys: bool,
+    /// Keep an index from links to their keys. Disabling it saves memory on large instances,
+    /// but adding a link without a key then scans all links, see
+    /// [`Links::set_reverse_lookup`].
+    pub reverse_lookup: bool,
+    /// Redirect `/` to this URL instead of serving the web UI.
+    pub root_redirect: Option<String>,
+    /// Serve the embedded web UI. When disabled, unknown paths return 404.
+    pub serve_ui: bool,
+    /// File that link accesses are logged to. Access logging is disabled if unset.
+    pub event_log_path: Option<PathBuf>,
+    /// Format in which redirect requests are logged for log analysis tools, independent of
+    /// tracing and the event log. Disabled by default.
+    pub access_log_format: AccessLogFormat,
+    /// File the access log is appended to, stdout if unset.
+    pub access_log_path: Option<PathBuf>,
+    /// Maximum number of access events applied per write lock acquisition.
+    pub worker_batch_size: usize,
+    /// Don't record link accesses at all, so redirects never wait for the metadata worker.
+    /// Usage statistics then stay as they are, and adding clicks through the API is refused.
+    /// The worker still purges expired links.
+    pub disable_analytics: bool,
+    /// Save the links this often if they changed since the last save, even if no request
+    /// saved them, e.g. to persist click counts. Disabled if unset.
+    pub autosave_interval: Option<Duration>,
+    /// Token required by protected API endpoints. Authentication is disabled if unset.
+    pub api_token: Option<String>,
+    /// Tokens of users that links can be restricted to, by lowercased user name, see
+    /// [`links::Entry::acl`]. Set with `LANDMOWER_USER_TOKEN_<NAME>`, and only used if
+    /// `api_token` is set.
+    pub user_tokens: BTreeMap<String, String>,
+    /// Reject all API requests that would modify links.
+    pub read_only: bool,
+    /// How keys are generated for links added without one.
+    pub key_strategy: KeyStrategy,
+    /// How many candidate keys are tried before giving up on generating a unique key.
+    pub key_max_attempts: usize,
+    /// File that queued access events are saved to on shutdown and restored from on startup.
+    /// Queued events are dropped on shutdown if unset.
+    pub pending_events_path: Option<PathBuf>,
+    /// Round click counts in API responses down to a multiple of this, except for clients
+    /// authenticated with the API token. Access events and daily counts of a link are then
+    /// only available to those clients.
+    pub click_bucket_size: Option<u64>,
+    /// Maximum number of characters in a custom key.
+    pub key_max_length: usize,
+    /// Maximum number of keys pointing at the same link. Once it has this many, adding it
+    /// under another key is refused, and adding it without a key returns an existing one.
+    /// Unlimited if unset.
+    pub max_aliases_per_url: Option<usize>,
+    /// Characters allowed in keys, both custom and generated ones.
+    pub key_charset: KeyCharset,
+    /// Prefix of generated keys, e.g. `p-`. Ignored unless it only contains characters of
+    /// `key_charset`.
+    pub key_prefix: String,
+    /// Layout of the link data file. Files in the other layout are migrated on startup.
+    pub storage_format: StorageFormat,
+    /// Keep the volatile statistics of links (`used` and `last_used`) in a separate
+    /// `<name>.stats.toml` next to the link data file, so the link data file only changes
+    /// when links do and can be kept under version control. Existing files are migrated on
+    /// startup in either direction.
+    pub stats_sidecar: bool,
+    /// Gzip the link data file. Always done if its name ends in `.gz`; compressed files are
+    /// read either way.
+    pub compress_storage: bool,
+    /// Redirects taking longer than this, including waiting for the link store, are logged
+    /// as warnings.
+    pub slow_redirect_threshold: Duration,
+    /// Redirect unknown keys to this URL instead of returning 404. `{key}` is replaced by the
+    /// requested key.
+    pub fallback_redirect: Option<String>,
+    /// Respond with `410 Gone` instead of 404 to redirects of keys deleted within this
+    /// window, so clients learn that the link is gone for good. Deleted keys are forgotten
+    /// on restart.
+    pub deleted_grace: Option<Duration>,
+    /// Body of the `410 Gone` response for deleted keys.
+    pub deleted_message: Option<String>,
+    /// Maximum number of times a single key may be followed within `key_rate_window`.
+    /// Further redirects are answered with `429 Too Many Requests`. Unlimited if unset.
+    pub key_rate_limit: Option<u32>,
+    /// Window for `key_rate_limit`.
+    pub key_rate_window: Duration,
+    /// What the redirect route serves while no links exist yet, e.g. on a fresh instance.
+    /// Takes precedence over `fallback_redirect`.
+    pub empty_store: EmptyStore,
+    /// What happens to new links that point back at a key of this instance, i.e. start with
+    /// `server_base_url` or its `/go/` route.
+    pub self_links: SelfLinks,
+    /// PEM file with the TLS certificate chain. HTTPS is served if both this and `tls_key`
+    /// are set, which requires the `tls` feature.
+    pub tls_cert: Option<PathBuf>,
+    /// PEM file with the private key for `tls_cert`.
+    pub tls_key: Option<PathBuf>,
+    /// Describe the link in `X-Landmower-*` headers on redirects.
+    pub link_headers: bool,
+    /// Attach security headers like a content security policy to all responses.
+    pub security_headers: bool,
+    /// What `/go/` without a key serves.
+    pub go_index: GoIndex,
+    /// Check that the destination of links added through the API responds, using
+    /// `fetch_timeout`.
+    pub verify_reachable: VerifyReachable,
+    /// URL that link events are POSTed to, see [`webhook`]. Webhooks are disabled if unset.
+    pub webhook_url: Option<String>,
+    /// Secret used to sign webhook requests. Requests are unsigned if unset.
+    pub webhook_secret: Option<String>,
+    /// Also send webhooks for deleted links, not just created ones.
+    pub webhook_on_delete: bool,
+    /// Secret used to sign and verify export bundles. Signed export and restore are disabled
+    /// if unset.
+    pub export_secret: Option<String>,
+    /// Custom variables available to the UI templates as `ui.<name>`, e.g. an instance name
+    /// or contact email. Set with `LANDMOWER_UI_VAR_<NAME>`.
+    pub ui_vars: BTreeMap<String, String>,
+}
+
+/// What `/go/` without a key serves.
+/// 
+/// Except for [`GoIndex::Ui`], the `/go/` route takes precedence over the web UI, which
+/// otherwise handles all paths not matched by the API or redirects.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GoIndex {
+    /// Leave it to the web UI, or 404 if the UI isn't served.
+    #[default]
+    Ui,
+    /// Always respond with 404, so the redirect namespace never serves the UI.
+    NotFound,
+    /// List all keys as JSON.
+    Json,
+}
+
+impl std::str::FromStr for GoIndex {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ui" => Ok(Self::Ui),
+            "not_found" | "404" => Ok(Self::NotFound),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("Invalid /go/ index: '{s}'"))
+        }
+    }
+}
+
+/// What the redirect route serves while the link store is empty.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum EmptyStore {
+    /// Respond with 404, like for any unknown key.
+    #[default]
+    NotFound,
+    /// Serve a page explaining how to add the first link.
+    Setup,
+    /// Redirect to this URL.
+    Redirect(String),
+}
+
+impl std::str::FromStr for EmptyStore {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "not_found" | "404" => Ok(Self::NotFound),
+            "setup" => Ok(Self::Setup),
+            _ if s.starts_with('/') || s.starts_with("http://") || s.starts_with("https://") => {
+                Ok(Self::Redirect(s.to_string()))
+            },
+            _ => Err(format!("Invalid empty store response: '{s}'"))
+        }
+    }
+}
+
+/// How links pointing back at this instance are added, see [`Config::self_links`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SelfLinks {
+    /// Reject them, since following them takes another round trip and may loop forever.
+    #[default]
+    Reject,
+    /// Store them as internal links to the key, which are resolved on the server and
+    /// checked for loops.
+    Internal,
+}
+
+impl std::str::FromStr for SelfLinks {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "reject" => Ok(Self::Reject),
+            "internal" => Ok(Self::Internal),
+            _ => Err(format!("Invalid self link handling: '{s}'"))
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            link_data_path: default_link_data_path(),
+            bind_address: "0.0.0.0:7171".to_string(),
+            server_base_url: "landmow.er/".to_string(),
+            key_blacklist: vec![],
+            max_body_bytes: 1024 * 1024,
+            fetch_titles: false,
+            fetch_timeout: Duration::from_millis(3000),
+            probe_concurrency: 16,
+            normalize_urls: false,
+            trailing_slash: TrailingSlash::default(),
+            default_scheme: "https".to_string(),
+            case_insensitive_keys: false,
+            reverse_lookup: true,
+            root_redirect: None,
+            serve_ui: true,
+            event_log_path: None,
+            access_log_format: AccessLogFormat::default(),
+            access_log_path: None,
+            worker_batch_size: 1024,
+            disable_analytics: false,
+            autosave_interval: Some(Duration::from_secs(300)),
+            api_token: None,
+            user_tokens: BTreeMap::new(),
+            read_only: false,
+            key_strategy: KeyStrategy::default(),
+            key_max_attempts: links::DEFAULT_KEY_MAX_ATTEMPTS,
+            pending_events_path: None,
+            click_bucket_size: None,
+            key_max_length: 64,
+            max_aliases_per_url: None,
+            key_charset: KeyCharset::default(),
+            key_prefix: String::new(),
+            storage_format: StorageFormat::default(),
+            stats_sidecar: false,
+            compress_storage: false,
+            slow_redirect_threshold: Duration::from_millis(100),
+            fallback_redirect: None,
+            deleted_grace: None,
+            deleted_message: None,
+            key_rate_limit: None,
+            key_rate_window: Duration::from_secs(60),
+            empty_store: EmptyStore::default(),
+            self_links: SelfLinks::default(),
+            tls_cert: None,
+            tls_key: None,
+            link_headers: false,
+            security_headers: false,
+            go_index: GoIndex::default(),
+            verify_reachable: VerifyReachable::default(),
+            webhook_url: None,
+            webhook_secret: None,
+            webhook_on_delete: false,
+            export_secret: None,
+            ui_vars: BTreeMap::new(),
+        }
+    }
 }
 
 impl Config {
     pub fn from_env() -> Self {     
-        let link_data_path = std::env::var("LANDMOWER_LINK_DATA_PATH")
-            .map(|s| s.into())
-            .unwrap_or_else(|_| default_link_data_path());
+        let default = Self::default();
+
+        let link_data_path = if env_parse("LANDMOWER_IN_MEMORY").unwrap_or(false) {
+            PathBuf::from(links::IN_MEMORY_PATH)
+        } else {
+            std::env::var("LANDMOWER_LINK_DATA_PATH")
+                .map(|s| s.into())
+                .unwrap_or(default.link_data_path)
+        };
 
         let bind_address = std::env::var("LANDMOWER_BIND_ADDRESS")
-            .unwrap_or_else(|_| "0.0.0.0:7171".to_string());
+            .unwrap_or(default.bind_address);
+
+        let tls_cert = std::env::var("LANDMOWER_TLS_CERT").ok()
+            .filter(|s| !s.is_empty())
+            .map(PathBuf::from);
 
+        let tls_key = std::env::var("LANDMOWER_TLS_KEY").ok()
+            .filter(|s| !s.is_empty())
+            .map(PathBuf::from);
+
+        let tls = tls_cert.is_some() && tls_key.is_some();
         let server_base_url = std::env::var("LANDMOWER_BASE_URL")
-            .unwrap_or_else(|_| "landmow.er/".to_string());
+            .ok()
+            .or_else(|| derive_base_url(&bind_address, tls))
+            .unwrap_or_else(|| if tls {
+                format!("https://{}", default.server_base_url)
+            } else {
+                default.server_base_url
+            });
 
         let key_blacklist: Vec<_> = std::env::var("LANDMOWER_KEY_BLACKLIST")
             .unwrap_or_else(|_| "".to_string())
@@ -59,20 +624,379 @@ impl Config {
             .filter_map(|s| if s.is_empty() { None } else { Some(s.trim().to_string()) })
             .collect();
 
-        Self { link_data_path, bind_address, server_base_url, key_blacklist }
+        let max_body_bytes = env_parse("LANDMOWER_MAX_BODY_BYTES")
+            .unwrap_or(default.max_body_bytes);
+
+        let fetch_titles = env_parse("LANDMOWER_FETCH_TITLES")
+            .unwrap_or(default.fetch_titles);
+
+        let fetch_timeout = env_parse("LANDMOWER_FETCH_TIMEOUT_MS")
+            .map(Duration::from_millis)
+            .unwrap_or(default.fetch_timeout);
+
+        let probe_concurrency = env_parse("LANDMOWER_PROBE_CONCURRENCY")
+            .unwrap_or(default.probe_concurrency);
+
+        let normalize_urls = env_parse("LANDMOWER_NORMALIZE_URLS")
+            .unwrap_or(default.normalize_urls);
+
+        let trailing_slash = env_parse("LANDMOWER_TRAILING_SLASH")
+            .unwrap_or(default.trailing_slash);
+
+        let default_scheme = std::env::var("LANDMOWER_DEFAULT_SCHEME").ok()
+            .map(|s| s.trim_end_matches("://").to_ascii_lowercase())
+            .filter(|s| !s.is_empty())
+            .unwrap_or(default.default_scheme);
+
+        let case_insensitive_keys = env_parse("LANDMOWER_CASE_INSENSITIVE_KEYS")
+            .unwrap_or(default.case_insensitive_keys);
+
+        let reverse_lookup = env_parse("LANDMOWER_REVERSE_LOOKUP")
+            .unwrap_or(default.reverse_lookup);
+
+        let root_redirect = std::env::var("LANDMOWER_ROOT_REDIRECT").ok()
+            .filter(|s| !s.is_empty());
+
+        let serve_ui = env_parse("LANDMOWER_SERVE_UI")
+            .unwrap_or(default.serve_ui);
+
+        let event_log_path = std::env::var("LANDMOWER_EVENT_LOG_PATH").ok()
+            .filter(|s| !s.is_empty())
+            .map(PathBuf::from);
+
+        let access_log_format = env_parse("LANDMOWER_ACCESS_LOG_FORMAT")
+            .unwrap_or(default.access_log_format);
+
+        let access_log_path = std::env::var("LANDMOWER_ACCESS_LOG_PATH").ok()
+            .filter(|s| !s.is_empty())
+            .map(PathBuf::from);
+
+        let worker_batch_size = env_parse("LANDMOWER_WORKER_BATCH_SIZE")
+            .filter(|&n| n > 0)
+            .unwrap_or(default.worker_batch_size);
+
+        let disable_analytics = env_parse("LANDMOWER_DISABLE_ANALYTICS")
+            .unwrap_or(default.disable_analytics);
+
+        let autosave_interval = match env_parse("LANDMOWER_AUTOSAVE_SECS") {
+            Some(0) => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => default.autosave_interval,
+        };
+
+        let api_token = std::env::var("LANDMOWER_API_TOKEN").ok()
+            .filter(|s| !s.is_empty());
+
+        let read_only = env_parse("LANDMOWER_READ_ONLY")
+            .unwrap_or(default.read_only);
+
+        let key_strategy = env_parse("LANDMOWER_KEY_STRATEGY")
+            .unwrap_or(default.key_strategy);
+        let key_max_attempts = env_parse("LANDMOWER_KEY_MAX_ATTEMPTS")
+            .unwrap_or(default.key_max_attempts);
+
+        let pending_events_path = std::env::var("LANDMOWER_PENDING_EVENTS_PATH").ok()
+            .filter(|s| !s.is_empty())
+            .map(PathBuf::from);
+
+        let click_bucket_size = env_parse("LANDMOWER_CLICK_BUCKET_SIZE")
+            .filter(|&n: &u64| n > 1);
+
+        let key_max_length = env_parse("LANDMOWER_KEY_MAX_LENGTH")
+            .filter(|&n| n >= links::MIN_KEY_LENGTH)
+            .unwrap_or(default.key_max_length);
+
+        let max_aliases_per_url = env_parse("LANDMOWER_MAX_ALIASES_PER_URL")
+            .filter(|&n| n > 0);
+
+        let key_charset = env_parse("LANDMOWER_KEY_CHARSET")
+            .unwrap_or(default.key_charset);
+
+        let key_prefix = std::env::var("LANDMOWER_KEY_PREFIX").ok()
+            .filter(|prefix| prefix.chars().all(|c| key_charset.allows(c)))
+            .unwrap_or(default.key_prefix);
+
+        let storage_format = env_parse("LANDMOWER_STORAGE_FORMAT")
+            .unwrap_or(default.storage_format);
+
+        let stats_sidecar = env_parse("LANDMOWER_STATS_SIDECAR")
+            .unwrap_or(default.stats_sidecar);
+
+        let compress_storage = env_parse("LANDMOWER_COMPRESS_STORAGE")
+            .unwrap_or(default.compress_storage);
+
+        let slow_redirect_threshold = env_parse("LANDMOWER_SLOW_REDIRECT_MS")
+            .map(Duration::from_millis)
+            .unwrap_or(default.slow_redirect_threshold);
+
+        let link_headers = env_parse("LANDMOWER_LINK_HEADERS")
+            .unwrap_or(default.link_headers);
+
+        let security_headers = env_parse("LANDMOWER_SECURITY_HEADERS")
+            .unwrap_or(default.security_headers);
+
+        let go_index = env_parse("LANDMOWER_GO_INDEX")
+            .unwrap_or(default.go_index);
+
+        let verify_reachable = env_parse("LANDMOWER_VERIFY_REACHABLE")
+            .unwrap_or(default.verify_reachable);
+
+        let webhook_url = std::env::var("LANDMOWER_WEBHOOK_URL").ok()
+            .filter(|s| !s.is_empty());
+
+        let webhook_secret = std::env::var("LANDMOWER_WEBHOOK_SECRET").ok()
+            .filter(|s| !s.is_empty());
+
+        let webhook_on_delete = env_parse("LANDMOWER_WEBHOOK_ON_DELETE")
+            .unwrap_or(default.webhook_on_delete);
+
+        let export_secret = std::env::var("LANDMOWER_EXPORT_SECRET").ok()
+            .filter(|s| !s.is_empty());
+
+        let ui_vars = prefixed_vars(UI_VAR_PREFIX, std::env::vars());
+
+        let user_tokens = prefixed_vars(USER_TOKEN_PREFIX, std::env::vars()).into_iter()
+            .filter(|(_, token)| !token.is_empty())
+            .collect();
+
+        let fallback_redirect = std::env::var("LANDMOWER_FALLBACK_REDIRECT").ok()
+            .filter(|s| !s.is_empty());
+
+        let deleted_grace = env_parse("LANDMOWER_DELETED_GRACE_SECS")
+            .filter(|&secs| secs > 0)
+            .map(Duration::from_secs);
+
+        let deleted_message = std::env::var("LANDMOWER_DELETED_MESSAGE").ok()
+            .filter(|s| !s.is_empty());
+
+        let key_rate_limit = env_parse("LANDMOWER_KEY_RATE_LIMIT")
+            .filter(|&n| n > 0);
+
+        let key_rate_window = env_parse("LANDMOWER_KEY_RATE_WINDOW_SECS")
+            .filter(|&secs| secs > 0)
+            .map(Duration::from_secs)
+            .unwrap_or(default.key_rate_window);
+
+        let empty_store = env_parse("LANDMOWER_EMPTY_STORE")
+            .unwrap_or(default.empty_store);
+
+        let self_links = env_parse("LANDMOWER_SELF_LINKS")
+            .unwrap_or(default.self_links);
+
+        Self { 
+            link_data_path, 
+            bind_address, 
+            server_base_url, 
+            key_blacklist, 
+            max_body_bytes,
+            fetch_titles,
+            fetch_timeout,
+            probe_concurrency,
+            normalize_urls,
+            trailing_slash,
+            default_scheme,
+            case_insensitive_keys,
+            reverse_lookup,
+            root_redirect,
+            serve_ui,
+            event_log_path,
+            access_log_format,
+            access_log_path,
+            worker_batch_size,
+            disable_analytics,
+            autosave_interval,
+            api_token,
+            user_tokens,
+            read_only,
+            key_strategy,
+            key_max_attempts,
+            pending_events_path,
+            click_bucket_size,
+            key_max_length,
+            max_aliases_per_url,
+            key_charset,
+            key_prefix,
+            storage_format,
+            stats_sidecar,
+            compress_storage,
+            slow_redirect_threshold,
+            fallback_redirect,
+            deleted_grace,
+            deleted_message,
+            key_rate_limit,
+            key_rate_window,
+            empty_store,
+            self_links,
+            tls_cert,
+            tls_key,
+            link_headers,
+            security_headers,
+            go_index,
+            verify_reachable,
+            webhook_url,
+            webhook_secret,
+            webhook_on_delete,
+            export_secret,
+            ui_vars,
+        }
+    }
+
+    /// Whether short URLs still use the placeholder default base URL although the server is
+    /// reachable from other hosts, so shared links likely point nowhere.
+    pub fn uses_placeholder_base_url(&self) -> bool {
+        let placeholder = Self::default().server_base_url;
+        let base_url = self.server_base_url.strip_prefix("https://").unwrap_or(&self.server_base_url);
+        base_url == placeholder && !is_loopback_bind(&self.bind_address)
     }
 
+    /// The short URL redirecting to the link with the given key.
+    pub fn short_url(&self, key: &str) -> String {
+        format!("{}{}", self.server_base_url, links::percent_encode(key))
+    }
+
+    /// Variables available to the UI templates.
+    /// 
+    /// Rendered templates are served to anyone, so this must only hold what the UI needs and
+    /// never internal details like addresses or paths on the server.
     pub fn jinja_context(&self) -> minijinja::Value {
         context! {
             server_base_url => self.server_base_url.clone(),
-            bind_address => self.bind_address.clone(),
-            link_data_path => self.link_data_path.to_string_lossy().to_string()
+            ui => self.ui_vars.clone()
         }
     }
 }
 
+/// Split a `host:port` bind address, without the brackets of IPv6 hosts.
+fn split_bind_address(bind_address: &str) -> Option<(&str, &str)> {
+    let (host, port) = bind_address.rsplit_once(':')?;
+    let host = host.strip_prefix('[').and_then(|h| h.strip_suffix(']')).unwrap_or(host);
+    Some((host, port))
+}
+
+/// Whether the bind address only accepts connections from this host. Unix sockets count as
+/// local, since they are usually behind a reverse proxy.
+fn is_loopback_bind(bind_address: &str) -> bool {
+    if bind_address.starts_with("unix:") {
+        return true;
+    }
+    match split_bind_address(bind_address) {
+        Some((host, _)) => host.eq_ignore_ascii_case("localhost")
+            || host.parse::<std::net::IpAddr>().is_ok_and(|ip| ip.is_loopback()),
+        None => false,
+    }
+}
+
+/// Base URL of the `/go/` route on the bind address, if that is a specific address other
+/// hosts can reach, i.e. neither loopback, a wildcard nor a Unix socket.
+fn derive_base_url(bind_address: &str, tls: bool) -> Option<String> {
+    if is_loopback_bind(bind_address) {
+        return None;
+    }
+    let (host, port) = split_bind_address(bind_address)?;
+    let ip = host.parse::<std::net::IpAddr>().ok();
+    if host.is_empty() || ip.is_some_and(|ip| ip.is_unspecified()) {
+        return None;
+    }
+    let (scheme, default_port) = if tls { ("https", "443") } else { ("http", "80") };
+    let host = if ip.is_some_and(|ip| ip.is_ipv6()) { format!("[{host}]") } else { host.to_string() };
+    if port == default_port {
+        Some(format!("{scheme}://{host}/go/"))
+    } else {
+        Some(format!("{scheme}://{host}:{port}/go/"))
+    }
+}
+
+/// Prefix of environment variables collected into [`Config::ui_vars`].
+const UI_VAR_PREFIX: &str = "LANDMOWER_UI_VAR_";
+
+/// Prefix of environment variables collected into [`Config::user_tokens`].
+const USER_TOKEN_PREFIX: &str = "LANDMOWER_USER_TOKEN_";
+
+/// Collect the environment variables starting with the given prefix, named by the lowercased
+/// remainder of the variable name.
+fn prefixed_vars(prefix: &str, vars: impl IntoIterator<Item = (String, String)>) -> BTreeMap<String, String> {
+    vars.into_iter()
+        .filter_map(|(name, value)| {
+            let name = name.strip_prefix(prefix)?.to_lowercase();
+            (!name.is_empty()).then_some((name, value))
+        })
+        .collect()
+}
+
 fn default_link_data_path() -> PathBuf {
     let mut result = dirs::data_local_dir().unwrap();
     result.push("landmower/links.toml");
     result
-}
\ No newline at end of file
+}
+
+/// Read and parse an environment variable, ignoring it if it is unset or malformed.
+fn env_parse<T: std::str::FromStr>(var: &str) -> Option<T> {
+    let value = std::env::var(var).ok()?;
+    match value.trim().parse() {
+        Ok(v) => Some(v),
+        Err(_) => {
+            tracing::warn!("Ignoring invalid value for {var}: '{value}'");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ui_vars_from_env() {
+        let vars = prefixed_vars(UI_VAR_PREFIX, [
+            ("LANDMOWER_UI_VAR_INSTANCE_NAME", "Acme links"),
+            ("LANDMOWER_UI_VAR_", "unnamed"),
+            ("LANDMOWER_BIND_ADDRESS", "0.0.0.0:8080"),
+        ].map(|(k, v)| (k.to_string(), v.to_string())));
+        assert_eq!(vars, BTreeMap::from([("instance_name".to_string(), "Acme links".to_string())]));
+    }
+
+    #[test]
+    fn base_url_from_bind_address() {
+        assert_eq!(derive_base_url("203.0.113.7:8080", false).as_deref(), Some("http://203.0.113.7:8080/go/"));
+        assert_eq!(derive_base_url("203.0.113.7:443", true).as_deref(), Some("https://203.0.113.7/go/"));
+        assert_eq!(derive_base_url("[2001:db8::1]:80", false).as_deref(), Some("http://[2001:db8::1]/go/"));
+        assert_eq!(derive_base_url("links.example.com:7171", false).as_deref(), Some("http://links.example.com:7171/go/"));
+        for bind_address in ["0.0.0.0:7171", "[::]:7171", "127.0.0.1:7171", "localhost:7171", "unix:/run/landmower.sock"] {
+            assert_eq!(derive_base_url(bind_address, false), None, "{bind_address}");
+        }
+    }
+
+    #[test]
+    fn placeholder_base_url() {
+        assert!(Config::default().uses_placeholder_base_url());
+        let local = Config { bind_address: "127.0.0.1:7171".to_string(), ..Config::default() };
+        assert!(!local.uses_placeholder_base_url());
+        let configured = Config { server_base_url: "https://short.example/".to_string(), ..Config::default() };
+        assert!(!configured.uses_placeholder_base_url());
+    }
+
+    #[test]
+    fn ui_vars_in_context() {
+        let config = Config {
+            ui_vars: BTreeMap::from([("instance_name".to_string(), "Acme links".to_string())]),
+            ..Config::default()
+        };
+        let rendered = minijinja::Environment::new()
+            .render_str("{{ ui.instance_name }}", config.jinja_context())
+            .unwrap();
+        assert_eq!(rendered, "Acme links");
+    }
+
+    #[test]
+    fn context_hides_internals() {
+        let config = Config {
+            link_data_path: PathBuf::from("/srv/landmower/links.toml"),
+            bind_address: "10.0.0.1:8080".to_string(),
+            ..Config::default()
+        };
+        let rendered = minijinja::Environment::new()
+            .render_str("{{ server_base_url }}|{{ bind_address }}|{{ link_data_path }}", config.jinja_context())
+            .unwrap();
+        assert_eq!(rendered, format!("{}||", config.server_base_url));
+    }
+}
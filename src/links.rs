@@ -1,236 +1,2019 @@
-use std::{collections::{hash_map, HashMap}, hash::{Hash as _, Hasher as _}, path::Path};
+use std::{collections::{hash_map, BTreeSet, HashMap, HashSet}, hash::{Hash as _, Hasher as _}, io::{Read as _, Write as _}, path::{Path, PathBuf}, sync::Arc};
 
+use argon2::{Argon2, PasswordHash, PasswordHasher as _, PasswordVerifier as _};
 use chrono::prelude::*;
+use password_hash::{rand_core::{OsRng, RngCore as _}, SaltString};
 use serde::{Deserialize, Serialize};
 use base64::prelude::*;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 
+use crate::clock::{SharedClock, SystemClock};
+use crate::visitors::VisitorSketch;
+
+/// A stored link.
+/// 
+/// Generic over the per-link metadata, so embedders can attach their own data. The server
+/// itself uses [`EntryMetadata`].
+/// 
+/// Fields may be added in any release, so other crates create entries with [`Entry::new`] or
+/// [`Entry::with_metadata`] rather than struct literals.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
-pub struct Entry {
+#[non_exhaustive]
+pub struct Entry<M = EntryMetadata> {
     pub link: String,
-    pub metadata: EntryMetadata
+    /// Key this link follows instead of an external destination, see [`Links::chain`]. Its
+    /// `link` is then that key in the [`INTERNAL_SCHEME`], e.g. `go:docs`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias_of: Option<String>,
+    pub metadata: M,
+    /// Title of the destination page, if it has been fetched.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Argon2 hash of the password required to follow this link, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// Destination for clients that don't accept HTML, e.g. the raw resource behind a 
+    /// documentation page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alt_target: Option<String>,
+    /// Sketch of the distinct visitors that followed this link.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visitors: Option<VisitorSketch>,
+    /// Whether the destination responded when the link was added, if it was checked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reachable: Option<bool>,
+    /// Whether the destination responded when it was last checked for link rot.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alive: Option<bool>,
+    /// When the destination was last checked for link rot.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_checked: Option<DateTime<Utc>>,
+    /// When this link stops redirecting to its destination, if ever.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// What following this link does once it has expired.
+    #[serde(default, skip_serializing_if = "OnExpire::is_default")]
+    pub on_expire: OnExpire,
+    /// Whether this is the canonical key of its link, see [`Links::make_primary`].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub primary: bool,
+    /// Whether this link is kept when deleting or purging links, unless forced.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub protected: bool,
+    /// Names of the users that may view or delete this link through the API, see
+    /// [`crate::auth::Caller`]. Everyone may if it's empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub acl: Vec<String>,
+    /// Labels for organizing links.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+}
+
+/// What following a link does once it has expired, see [`Entry::expires_at`].
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OnExpire {
+    /// Respond with `410 Gone`.
+    #[default]
+    Gone,
+    /// Redirect to the given URL instead of the destination.
+    Redirect(String),
+    /// Respond with `410 Gone`, and remove the link when expired links are purged.
+    Delete,
+}
+
+impl OnExpire {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
 }
 
+/// Usage statistics of a link. Other crates create these with [`Metadata::created_at`] or
+/// [`Default::default`], since fields may be added.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[non_exhaustive]
 pub struct EntryMetadata {
     pub used: u64,
     pub last_used: DateTime<Utc>,    
     pub created: DateTime<Utc>,
+    /// How the link was created, e.g. `api` or `import`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+/// Metadata of a newly created link: unused, and created now.
+impl Default for EntryMetadata {
+    fn default() -> Self {
+        Self::created_at(Utc::now())
+    }
+}
+
+/// Per-link metadata, see [`Links`].
+pub trait Metadata: Clone + Default + Serialize + serde::de::DeserializeOwned {
+    /// Metadata of a link created at the given time, according to the clock of the store.
+    /// 
+    /// Metadata that doesn't record time can rely on the default, [`Default::default`].
+    fn created_at(_now: DateTime<Utc>) -> Self {
+        Self::default()
+    }
+}
+
+impl Metadata for EntryMetadata {
+    fn created_at(now: DateTime<Utc>) -> Self {
+        Self {
+            used: 0,
+            last_used: now,
+            created: now,
+            source: None,
+        }
+    }
+}
+
+impl EntryMetadata {
+    pub fn used(&self) -> u64 {
+        self.used
+    }
+
+    pub fn last_used(&self) -> DateTime<Utc> {
+        self.last_used
+    }
+
+    pub fn created(&self) -> DateTime<Utc> {
+        self.created
+    }
+
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
 }
 
-impl From<String> for Entry {
+impl<M: Default> From<String> for Entry<M> {
     fn from(link: String) -> Self {        
-        let now = DateTime::<Utc>::from(std::time::SystemTime::now());
+        Self::new(link)
+    }
+}
+
+/// Link data path that keeps links in memory only, for tests and throwaway instances.
+/// Nothing is loaded from or written to disk.
+pub const IN_MEMORY_PATH: &str = ":memory:";
+
+/// Whether the link data path is [`IN_MEMORY_PATH`].
+pub fn is_in_memory(path: &Path) -> bool {
+    path == Path::new(IN_MEMORY_PATH)
+}
 
+/// Scheme for links that point at another key instead of an external URL, e.g. `go:docs`.
+pub const INTERNAL_SCHEME: &str = "go:";
+
+/// The key a link in the [`INTERNAL_SCHEME`] points at, e.g. `docs` for `go:docs`.
+pub fn internal_target(link: &str) -> Option<&str> {
+    link.strip_prefix(INTERNAL_SCHEME)
+}
+
+/// Maximum number of internal links followed when resolving a key.
+pub const MAX_CHAIN_DEPTH: usize = 8;
+
+/// Reasons a key can fail to resolve to an external link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolveError {
+    /// The key, or a key further down the chain, does not exist.
+    NotFound(String),
+    /// The chain loops back on itself or is longer than [`MAX_CHAIN_DEPTH`].
+    Loop,
+}
+
+impl<M: Default> Entry<M> {
+    /// An entry for the given link with default metadata and no further settings.
+    pub fn new(link: String) -> Self {
+        Self::with_metadata(link, M::default())
+    }
+}
+
+impl<M> Entry<M> {
+    pub fn with_metadata(link: String, metadata: M) -> Self {
         Self {
+            alias_of: internal_target(&link).map(str::to_string),
             link,
-            metadata: EntryMetadata {
-                used: 0,
-                last_used: now,
-                created: now
-            }
+            metadata,
+            title: None,
+            password: None,
+            alt_target: None,
+            visitors: None,
+            reachable: None,
+            alive: None,
+            last_checked: None,
+            expires_at: None,
+            on_expire: OnExpire::default(),
+            primary: false,
+            protected: false,
+            acl: Vec::new(),
+            tags: BTreeSet::new(),
+        }
+    }
+
+    pub fn link(&self) -> &str {
+        &self.link
+    }
+
+    pub fn metadata(&self) -> &M {
+        &self.metadata
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    pub fn alt_target(&self) -> Option<&str> {
+        self.alt_target.as_deref()
+    }
+
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.expires_at
+    }
+
+    pub fn on_expire(&self) -> &OnExpire {
+        &self.on_expire
+    }
+
+    pub fn is_primary(&self) -> bool {
+        self.primary
+    }
+
+    pub fn is_protected(&self) -> bool {
+        self.protected
+    }
+
+    /// Whether following this link requires a password, see [`Entry::set_password`].
+    pub fn is_password_protected(&self) -> bool {
+        self.password.is_some()
+    }
+
+    pub fn acl(&self) -> &[String] {
+        &self.acl
+    }
+
+    pub fn tags(&self) -> &BTreeSet<String> {
+        &self.tags
+    }
+
+    /// Whether this link has expired at the given time.
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// Whether the destination didn't respond when it was last checked, either for link rot
+    /// or when the link was added.
+    pub fn is_dead(&self) -> bool {
+        self.alive.or(self.reachable) == Some(false)
+    }
+
+    /// The key this entry points at, if it is an internal link.
+    pub fn internal_target(&self) -> Option<&str> {
+        self.alias_of.as_deref()
+    }
+
+    /// Fill in [`Entry::alias_of`] for internal links stored before it was recorded.
+    fn upgrade_alias(&mut self) {
+        if self.alias_of.is_none() {
+            self.alias_of = internal_target(&self.link).map(str::to_string);
+        }
+    }
+
+    /// Protect this link with the given password. Only a hash of the password is stored.
+    pub fn set_password(&mut self, password: &str) -> Result<(), String> {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = Argon2::default().hash_password(password.as_bytes(), &salt)
+            .map_err(|e| format!("Could not hash password: {e}"))?;
+        self.password = Some(hash.to_string());
+        Ok(())
+    }
+
+    /// Check the given password against the one protecting this link.
+    /// 
+    /// Always succeeds for links without a password.
+    pub fn verify_password(&self, password: &str) -> bool {
+        let Some(hash) = &self.password else {
+            return true;
+        };
+        PasswordHash::new(hash)
+            .is_ok_and(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+    }
+}
+
+
+
+/// How trailing slashes on the path of a link are treated by [`normalize_link`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TrailingSlash {
+    /// Leave the path as-is.
+    Keep,
+    /// Always end the path with a slash.
+    Add,
+    /// Strip trailing slashes from the path.
+    #[default]
+    Remove,
+}
+
+impl std::str::FromStr for TrailingSlash {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "keep" => Ok(Self::Keep),
+            "add" => Ok(Self::Add),
+            "remove" => Ok(Self::Remove),
+            _ => Err(format!("Invalid trailing slash policy: '{s}'"))
+        }
+    }
+}
+
+/// How keys are generated for links added without one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyStrategy {
+    /// Use a prefix of the hash of the link.
+    #[default]
+    Hash,
+    /// Use the last path segment or the domain of the link, e.g. `guide` for
+    /// `example.com/docs/guide`. Falls back to [`KeyStrategy::Hash`] if that is too short.
+    Slug,
+}
+
+impl std::str::FromStr for KeyStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "hash" => Ok(Self::Hash),
+            "slug" => Ok(Self::Slug),
+            _ => Err(format!("Invalid key strategy: '{s}'"))
+        }
+    }
+}
+
+/// Characters allowed in keys. Alphanumeric characters are always allowed, in addition to a
+/// configurable set of ASCII symbols.
+/// 
+/// Keys are stored and looked up in their decoded form. Only ASCII letters, digits and `-`,
+/// `.`, `_` and `~` are safe to use in URLs as-is; all other characters, including non-ASCII
+/// letters and most symbols, are percent-encoded in the short URLs built by the server (see
+/// [`percent_encode`]) and decoded again by the redirect route before the lookup, so
+/// `/go/caf%C3%A9` finds the key `café`. Symbols that delimit parts of a URL are never
+/// allowed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyCharset {
+    symbols: String,
+}
+
+impl Default for KeyCharset {
+    /// Alphanumeric characters, `_` and `-`.
+    fn default() -> Self {
+        Self { symbols: "_-".to_string() }
+    }
+}
+
+impl KeyCharset {
+    /// Symbols that can't be allowed in keys, since they would break the short URL.
+    const FORBIDDEN_SYMBOLS: &str = "/?#%\\";
+
+    pub fn allows(&self, c: char) -> bool {
+        c.is_alphanumeric() || self.symbols.contains(c)
+    }
+
+    /// The allowed symbols, in addition to alphanumeric characters.
+    pub fn symbols(&self) -> &str {
+        &self.symbols
+    }
+
+    /// A Unicode-aware regular expression matching valid keys.
+    pub fn pattern(&self) -> String {
+        let symbols = self.symbols.chars()
+            .map(|c| if "\\^$.*+?()[]{}|-/".contains(c) { format!("\\{c}") } else { c.to_string() })
+            .collect::<String>();
+        format!(r"^[\p{{Alphabetic}}\p{{N}}{symbols}]+$")
+    }
+
+    /// Human-readable description of the allowed characters, e.g. `0-9, A-Z, a-z, _ or -`.
+    pub fn describe(&self) -> String {
+        let mut parts = vec!["0-9".to_string(), "A-Z".to_string(), "a-z".to_string()];
+        parts.extend(self.symbols.chars().map(String::from));
+        let last = parts.pop().unwrap_or_default();
+        format!("{} or {last}", parts.join(", "))
+    }
+
+    /// Separator used between words of generated keys.
+    fn separator(&self) -> Option<char> {
+        ['-', '_'].into_iter().find(|&c| self.symbols.contains(c))
+    }
+}
+
+impl std::str::FromStr for KeyCharset {
+    type Err = String;
+
+    /// Parse a named preset (`default` or `alphanumeric`), or the set of allowed symbols,
+    /// e.g. `_-.`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "default" => Ok(Self::default()),
+            "alphanumeric" => Ok(Self { symbols: String::new() }),
+            _ if s.chars().all(|c| c.is_ascii_punctuation() && !Self::FORBIDDEN_SYMBOLS.contains(c)) => {
+                let mut symbols = String::new();
+                for c in s.chars() {
+                    if !symbols.contains(c) {
+                        symbols.push(c);
+                    }
+                }
+                Ok(Self { symbols })
+            },
+            _ => Err(format!("Invalid key charset: '{s}'"))
+        }
+    }
+}
+
+/// Layout of the link data file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StorageFormat {
+    /// One `[key]` table per link.
+    #[default]
+    Map,
+    /// One `[[link]]` table per link with an explicit `key` field, sorted by key.
+    List,
+}
+
+impl std::str::FromStr for StorageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "map" => Ok(Self::Map),
+            "list" => Ok(Self::List),
+            _ => Err(format!("Invalid storage format: '{s}'"))
+        }
+    }
+}
+
+/// The link data file in [`StorageFormat::List`].
+#[derive(Serialize, Deserialize)]
+struct LinkList<E> {
+    #[serde(default = "Vec::new")]
+    link: Vec<E>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyedEntry<K, E> {
+    key: K,
+    #[serde(flatten)]
+    entry: E,
+}
+
+/// Percent-encode everything but unreserved characters (RFC 3986), e.g. a key for use in a
+/// URL.
+pub fn percent_encode(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            result.push(b as char);
+        } else {
+            result.push_str(&format!("%{b:02X}"));
+        }
+    }
+    result
+}
+
+/// Decode a percent-encoded string, e.g. a key in a URL.
+/// 
+/// Returns `None` if an escape is malformed or the result is not valid UTF-8.
+pub fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = tail.get(..2).filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))?;
+            bytes.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Minimum length of a key.
+pub const MIN_KEY_LENGTH: usize = 4;
+
+/// Derive a key from the last path segment of a link, or its domain if the path is empty.
+/// 
+/// The result only contains lowercase `a-z`, `0-9` and the given word separator. Returns
+/// `None` if it would be shorter than [`MIN_KEY_LENGTH`].
+fn slug_from_link(link: &str, separator: Option<char>) -> Option<String> {
+    let link = link.split(['?', '#']).next().unwrap_or_default();
+    let rest = link.split_once("://").map_or(link, |(_, rest)| rest);
+    let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+
+    let source = match path.split('/').rfind(|s| !s.is_empty()) {
+        // Drop file extensions, e.g. `guide.html`
+        Some(segment) => segment.rsplit_once('.')
+            .map(|(stem, _)| stem)
+            .filter(|stem| !stem.is_empty())
+            .unwrap_or(segment),
+        None => {
+            let host = authority.rsplit('@').next().unwrap_or_default();
+            let host = host.split(':').next().unwrap_or_default();
+            host.strip_prefix("www.").unwrap_or(host)
+        }
+    };
+
+    let mut slug = String::with_capacity(source.len());
+    for c in source.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if let Some(sep) = separator.filter(|&sep| !slug.is_empty() && !slug.ends_with(sep)) {
+            slug.push(sep);
+        }
+    }
+    let slug = match separator {
+        Some(sep) => slug.trim_end_matches(sep),
+        None => &slug,
+    };
+
+    (slug.len() >= MIN_KEY_LENGTH).then(|| slug.to_string())
+}
+
+/// The link with the given scheme prepended if it doesn't start with `http://` or
+/// `https://`.
+pub fn with_default_scheme(link: &str, scheme: &str) -> String {
+    if link.starts_with("http://") || link.starts_with("https://") {
+        link.to_string()
+    } else {
+        format!("{scheme}://{link}")
+    }
+}
+
+/// Normalize a link so that trivially equivalent URLs compare equal.
+/// 
+/// Lowercases the scheme and host, strips default ports and applies the given trailing 
+/// slash policy to the path. User info, query and fragment are kept as-is. Links without 
+/// a scheme are returned unchanged.
+pub fn normalize_link(link: &str, trailing_slash: TrailingSlash) -> String {
+    let (link, fragment) = match link.find('#') {
+        Some(i) => link.split_at(i),
+        None => (link, ""),
+    };
+    let Some((scheme, rest)) = link.split_once("://") else {
+        return format!("{link}{fragment}");
+    };
+    let scheme = scheme.to_ascii_lowercase();
+
+    let (authority, rest) = rest.split_at(rest.find(['/', '?']).unwrap_or(rest.len()));
+    let (path, query) = rest.split_at(rest.find('?').unwrap_or(rest.len()));
+
+    // Split off user info; the host itself may be an IPv6 literal containing colons.
+    let (userinfo, host_port) = match authority.rfind('@') {
+        Some(i) => authority.split_at(i + 1),
+        None => ("", authority),
+    };
+    let (host, port) = match host_port.rfind(':') {
+        Some(i) if !host_port[i..].contains(']') => host_port.split_at(i),
+        _ => (host_port, ""),
+    };
+    let port = match (scheme.as_str(), port) {
+        ("http", ":80") | ("https", ":443") | (_, ":") => "",
+        _ => port,
+    };
+
+    let path = match trailing_slash {
+        TrailingSlash::Keep if path.is_empty() => "/".to_string(),
+        TrailingSlash::Keep => path.to_string(),
+        TrailingSlash::Add if path.ends_with('/') => path.to_string(),
+        TrailingSlash::Add => format!("{path}/"),
+        TrailingSlash::Remove => path.trim_end_matches('/').to_string(),
+    };
+
+    format!(
+        "{scheme}://{userinfo}{host}{port}{path}{query}{fragment}", 
+        host = host.to_ascii_lowercase()
+    )
+}
+
+/// Default for [`Links::set_key_max_attempts`].
+pub const DEFAULT_KEY_MAX_ATTEMPTS: usize = 1000;
+
+/// Error when no unique key could be generated.
+const KEY_EXHAUSTED: &str = "Could not allocate a unique key";
+
+/// A key generated for a link, see [`Links::generate_key`].
+#[derive(Debug, PartialEq)]
+enum GeneratedKey {
+    /// An unused key.
+    New(String),
+    /// The key the link is already stored under.
+    Existing(String),
+}
+
+/// Outcome of [`Links::add`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum AddResult<M = EntryMetadata> {
+    /// A new mapping was created.
+    Created(String, Entry<M>),
+    /// The link already had a mapping, which is returned as-is.
+    Existing(String, Entry<M>),
+}
+
+impl<M> AddResult<M> {
+    pub fn is_created(&self) -> bool {
+        matches!(self, AddResult::Created(..))
+    }
+
+    pub fn into_parts(self) -> (String, Entry<M>) {
+        match self {
+            AddResult::Created(key, entry) | AddResult::Existing(key, entry) => (key, entry)
+        }
+    }
+}
+
+/// Stores alias->link mappings and the reverse mapping.
+/// 
+/// Generic over the per-link metadata like [`Entry`]. Metadata of new links is created with
+/// [`Metadata::created_at`] at the time of the store's clock.
+#[derive(Clone, Debug)]
+pub struct Links<M = EntryMetadata> { 
+    /// Forward hashmap is used for finding the associated link for a given alias.
+    forward_map: HashMap<String, Entry<M>>, 
+    /// Inverse of the forward hashmap.
+    /// The forward mapping is surjective, so each link can have multiple associated aliases.
+    /// 
+    /// Not maintained if disabled with [`Links::set_reverse_lookup`], in which case lookups by
+    /// link scan the forward hashmap instead.
+    reverse_map: Option<HashMap<String, Vec<String>>>,
+    /// Maps lowercased keys to the stored keys that differ only in case, when keys are
+    /// case-insensitive. Lookups resolve to the first of them.
+    /// 
+    /// Keys are stored in their original case, so exact-case lookups never touch this index
+    /// and the redirect hot path doesn't allocate.
+    folded_index: Option<HashMap<String, BTreeSet<String>>>,
+    /// How keys are generated by [`Links::add`].
+    key_strategy: KeyStrategy,
+    /// How many candidate keys are tried when generating a key.
+    key_max_attempts: usize,
+    /// Lowercased keys that are never generated, e.g. blacklisted or reserved ones.
+    disallowed_keys: HashSet<String>,
+    /// Characters that generated keys may contain.
+    key_charset: KeyCharset,
+    /// Prepended to generated keys, see [`Links::set_key_prefix`].
+    key_prefix: String,
+    /// Layout used by [`Links::save`]. Set to the layout of the file by [`Links::load`].
+    storage_format: StorageFormat,
+    /// Whether [`Links::save`] writes volatile statistics to a separate file. Set by
+    /// [`Links::load`] if that file exists.
+    stats_sidecar: bool,
+    /// Whether [`Links::save`] gzips the files. Set by [`Links::load`] if the file is
+    /// compressed or named `*.gz`.
+    compressed: bool,
+    /// Time source for the metadata of new links.
+    clock: SharedClock,
+    /// Incremented whenever links may have changed, see [`Links::revision`].
+    revision: u64,
+}
+
+impl<M> Default for Links<M> {
+    fn default() -> Self {
+        Self {
+            forward_map: HashMap::new(),
+            reverse_map: Some(HashMap::new()),
+            folded_index: None,
+            key_strategy: KeyStrategy::default(),
+            key_max_attempts: DEFAULT_KEY_MAX_ATTEMPTS,
+            disallowed_keys: HashSet::new(),
+            key_charset: KeyCharset::default(),
+            key_prefix: String::new(),
+            storage_format: StorageFormat::default(),
+            stats_sidecar: false,
+            compressed: false,
+            clock: Arc::new(SystemClock),
+            revision: 0,
+        }
+    }
+}
+
+impl Links {
+    /// Create an empty store using the default [`EntryMetadata`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Consolidate two aliases of the same link: the statistics of `from` are added to
+    /// `into`, and `from` is removed. The merged link was created at the earlier and last used
+    /// at the later time of the two. Links are compared after [`normalize_link`] with the
+    /// given trailing slash policy.
+    /// 
+    /// Returns the removed entry of `from`.
+    /// 
+    /// ## Errors
+    /// 
+    /// This function will return an error if either key does not exist, both keys are the
+    /// same, they point at different links, or `from` is protected.
+    pub fn merge(&mut self, from: &str, into: &str, trailing_slash: TrailingSlash) -> Result<Entry, String> {
+        let from_key = self.resolve_key(from).ok_or(format!("Key '{from}' does not exist."))?.to_string();
+        let into_key = self.resolve_key(into).ok_or(format!("Key '{into}' does not exist."))?.to_string();
+        if from_key == into_key {
+            return Err("Cannot merge a key into itself.".to_string());
+        }
+        let same_link = normalize_link(&self.forward_map[&from_key].link, trailing_slash)
+            == normalize_link(&self.forward_map[&into_key].link, trailing_slash);
+        if !same_link {
+            return Err(format!("Keys '{from}' and '{into}' point at different links."));
+        }
+        if self.forward_map[&from_key].protected {
+            return Err(format!("Key '{from}' is protected."));
         }
+
+        let from = self.remove(&from_key).expect("Key removed before merging (unreachable state)");
+        let into = self.forward_map.get_mut(&into_key).expect("Key removed before merging (unreachable state)");
+        into.metadata.used += from.metadata.used;
+        into.metadata.created = into.metadata.created.min(from.metadata.created);
+        into.metadata.last_used = into.metadata.last_used.max(from.metadata.last_used);
+        if let Some(visitors) = &from.visitors {
+            into.visitors.get_or_insert_with(VisitorSketch::new).merge(visitors);
+        }
+        if from.primary {
+            self.make_primary(&into_key)?;
+        }
+        Ok(from)
     }
 }
 
+impl<M: Metadata> Links<M> {
+    /// Load link data from the given file, or start empty for [`IN_MEMORY_PATH`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {        
+        let path = path.as_ref();
+
+        if is_in_memory(path) {
+            Ok(Self::default())
+        } else if !path.exists() {
+            // Create the directory if it doesn't exist.
+            std::fs::create_dir_all(
+                path.parent()
+                    .ok_or(format!("Invalid link data path: '{}'", path.display()))?
+            ).map_err(|e| format!("Could not create directory for '{}': {e}", path.display()))?;
+            
+            // Create empty link storage & write to file
+            let result = Self { compressed: is_compressed_path(path), ..Self::default() };
+            result.save(path)?;
+            Ok(result)
+        } else {
+            // Read file contents
+            let (data, compressed) = read_file(path)
+                .map_err(|e| format!("Could not load links from '{}': {e}", path.display()))?;
+
+            let parse_error = |e: toml::de::Error| format!("Could not parse links in '{}': {e}", path.display());
+            let mut table: toml::Table = toml::from_str(&data).map_err(parse_error)?;
+
+            let stats_path = stats_path(path);
+            let stats_sidecar = stats_path.exists();
+            let stats: toml::Table = if stats_sidecar {
+                let (data, _) = read_file(&stats_path)
+                    .map_err(|e| format!("Could not load link statistics from '{}': {e}", stats_path.display()))?;
+                toml::from_str(&data)
+                    .map_err(|e| format!("Could not parse link statistics in '{}': {e}", stats_path.display()))?
+            } else {
+                toml::Table::new()
+            };
+            merge_stats(&mut table, &stats);
+
+            // Either format is accepted, so files can be migrated by changing the format.
+            let storage_format = match table.get("link") {
+                Some(toml::Value::Array(_)) => StorageFormat::List,
+                _ => StorageFormat::Map,
+            };
+            let mut forward_map: HashMap<String, Entry<M>> = match storage_format {
+                StorageFormat::Map => table.try_into().map_err(parse_error)?,
+                StorageFormat::List => table.try_into::<LinkList<KeyedEntry<String, Entry<M>>>>()
+                    .map_err(parse_error)?
+                    .link.into_iter()
+                    .map(|KeyedEntry { key, entry }| (key, entry))
+                    .collect(),
+            };
+            forward_map.values_mut().for_each(Entry::upgrade_alias);
+
+            let reverse_map = Some(build_reverse_map(&forward_map));
+            let compressed = compressed || is_compressed_path(path);
+            Ok(Self { forward_map, reverse_map, storage_format, stats_sidecar, compressed, ..Default::default() })
+        }
+    }
+
+    /// Maintain the reverse mapping from links to keys (or stop maintaining it).
+    /// 
+    /// The reverse mapping holds a copy of every key, so disabling it roughly halves the
+    /// memory used for keys, and adding and removing links no longer updates it. Lookups by
+    /// link, like [`Links::find_by_link`] and adding a link without a key, scan all links
+    /// instead.
+    pub fn set_reverse_lookup(&mut self, enabled: bool) {
+        self.reverse_map = enabled.then(|| build_reverse_map(&self.forward_map));
+    }
+
+    /// Make key lookups case-insensitive (or case-sensitive again).
+    /// 
+    /// If existing keys only differ in case, the one that sorts first wins lookups for the
+    /// others.
+    pub fn set_case_insensitive(&mut self, enabled: bool) {
+        if !enabled {
+            self.folded_index = None;
+            return;
+        }
+        let mut index = HashMap::<String, BTreeSet<String>>::new();
+        for key in self.forward_map.keys() {
+            let variants = index.entry(key.to_lowercase()).or_default();
+            if let Some(other) = variants.first() {
+                tracing::warn!("Keys '{key}' and '{other}' only differ in case");
+            }
+            variants.insert(key.clone());
+        }
+        self.folded_index = Some(index);
+    }
+
+    /// Set the clock that timestamps new links.
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    /// Set how keys are generated for links added without one.
+    pub fn set_key_strategy(&mut self, strategy: KeyStrategy) {
+        self.key_strategy = strategy;
+    }
+
+    /// Set how many candidate keys are tried when generating a key, at least one. Generating
+    /// a key fails once they are all taken, see [`Links::add`].
+    pub fn set_key_max_attempts(&mut self, attempts: usize) {
+        self.key_max_attempts = attempts.max(1);
+    }
+
+    pub fn storage_format(&self) -> StorageFormat {
+        self.storage_format
+    }
+
+    /// Set the layout used by [`Links::save`].
+    pub fn set_storage_format(&mut self, format: StorageFormat) {
+        self.storage_format = format;
+    }
+
+    pub fn stats_sidecar(&self) -> bool {
+        self.stats_sidecar
+    }
+
+    /// Write the volatile statistics of links (`used` and `last_used`) to a separate file
+    /// next to the link data file, see [`stats_path`].
+    /// 
+    /// The link data file then only changes when links are added, changed or removed, so it
+    /// can be kept under version control. Switching in either direction migrates on the next
+    /// save: the statistics are moved out of or back into the link data file, and a sidecar
+    /// that is no longer used is removed.
+    pub fn set_stats_sidecar(&mut self, enabled: bool) {
+        self.stats_sidecar = enabled;
+    }
+
+    pub fn compressed(&self) -> bool {
+        self.compressed
+    }
+
+    /// Gzip the files written by [`Links::save`]. Compressed files are detected by
+    /// [`Links::load`] regardless of this setting, so switching migrates on the next save.
+    pub fn set_compressed(&mut self, enabled: bool) {
+        self.compressed = enabled;
+    }
+
+    /// Set the characters that generated keys may contain.
+    pub fn set_key_charset(&mut self, charset: KeyCharset) {
+        self.key_charset = charset;
+    }
+
+    /// Start generated keys with the given prefix, e.g. `p-` to tell them apart from custom
+    /// keys. The generated part keeps its usual length, and disallowed keys are checked
+    /// against the whole key.
+    pub fn set_key_prefix(&mut self, prefix: impl Into<String>) {
+        self.key_prefix = prefix.into();
+    }
+
+    /// Set keys that generated keys must never be equal to, ignoring case.
+    /// 
+    /// This only affects key generation; such keys can still be added explicitly.
+    pub fn set_disallowed_keys<S: AsRef<str>>(&mut self, keys: impl IntoIterator<Item = S>) {
+        self.disallowed_keys = keys.into_iter()
+            .map(|k| k.as_ref().to_lowercase())
+            .collect();
+    }
+
+    /// Whether a generated key candidate is free to use.
+    fn is_available(&self, key: &str) -> bool {
+        self.resolve_key(key).is_none() && !self.disallowed_keys.contains(&key.to_lowercase())
+    }
+
+    /// Resolve the stored form of the given key.
+    fn resolve_key<'a>(&'a self, key: &'a str) -> Option<&'a str> {
+        if let Some((k, _)) = self.forward_map.get_key_value(key) {
+            return Some(k);
+        }
+        self.folded_index.as_ref()?
+            .get(&key.to_lowercase())?
+            .first()
+            .map(|k| k.as_str())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Entry<M>> {
+        self.forward_map.get(self.resolve_key(key)?)
+    }
+    
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Entry<M>> {
+        let key = self.resolve_key(key)?.to_string();
+        self.revision += 1;
+        self.forward_map.get_mut(&key)
+    }
+
+    /// Counter that changes whenever the stored links may have changed, so callers can tell
+    /// whether there is anything new to save. Mutable access through [`Links::get_mut`] counts
+    /// as a change, even if the entry is left as is.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Whether a link is stored under the given key, ignoring case if keys are
+    /// case-insensitive.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.resolve_key(key).is_some()
+    }
+
+    /// Number of stored links.
+    pub fn len(&self) -> usize {
+        self.forward_map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.forward_map.is_empty()
+    }
+
+    /// Follow internal links starting at the given key until an external link is reached.
+    /// 
+    /// Returns the stored key and entry at the end of the chain.
+    pub fn resolve<'a>(&'a self, key: &'a str) -> Result<(&'a str, &'a Entry<M>), ResolveError> {
+        let chain = self.chain(key)?;
+        Ok(chain[chain.len() - 1])
+    }
+
+    /// Like [`Links::resolve`], but returns the stored key and entry of every hop, starting
+    /// with the given key and ending with the external link. Settings of intermediate aliases,
+    /// like passwords, apply to the whole chain.
+    pub fn chain<'a>(&'a self, key: &'a str) -> Result<Vec<(&'a str, &'a Entry<M>)>, ResolveError> {
+        let mut chain: Vec<(&str, &Entry<M>)> = Vec::new();
+        let mut current = key;
+        loop {
+            let key = self.resolve_key(current)
+                .ok_or_else(|| ResolveError::NotFound(current.to_string()))?;
+            if chain.iter().any(|(k, _)| *k == key) || chain.len() > MAX_CHAIN_DEPTH {
+                return Err(ResolveError::Loop);
+            }
+
+            let entry = &self.forward_map[key];
+            chain.push((key, entry));
+            match entry.internal_target() {
+                Some(target) => current = target,
+                None => return Ok(chain)
+            }
+        }
+    }
+
+    /// Insert a new mapping with a generated key and the given link.
+    ///
+    /// If the link already has an associated mapping, that mapping is returned instead. This
+    /// is the primary key of the link if it has one.
+    /// 
+    /// ## Errors
+    /// 
+    /// This function will return an error if no unique key was found within the configured
+    /// number of attempts, see [`Links::set_key_max_attempts`].
+    pub fn add(&mut self, link: String) -> Result<AddResult<M>, String> {
+        if let Some(key) = self.primary_key(&link) {
+            let key = key.to_string();
+            let entry = self.forward_map[&key].clone();
+            return Ok(AddResult::Existing(key, entry));
+        }
+        match self.generate_key(&link)? {
+            GeneratedKey::New(key) => {
+                let entry = self.add_named(key.clone(), link)?;
+                Ok(AddResult::Created(key, entry))
+            },
+            GeneratedKey::Existing(key) => {
+                let entry = self.forward_map[&key].clone();
+                Ok(AddResult::Existing(key, entry))
+            }
+        }
+    }
+    
+    /// Generate a key for the given link using the configured [`KeyStrategy`].
+    fn generate_key(&self, link: &str) -> Result<GeneratedKey, String> {
+        match self.key_strategy {
+            KeyStrategy::Slug => match slug_from_link(link, self.key_charset.separator()) {
+                Some(slug) => self.generate_slug_key(link, &slug),
+                None => self.generate_hash_key(link),
+            },
+            KeyStrategy::Hash => self.generate_hash_key(link),
+        }
+    }
+
+    fn generate_hash_key(&self, link: &str) -> Result<GeneratedKey, String> {
+        let mut hasher = std::hash::DefaultHasher::new();
+        link.hash(&mut hasher);
+        let hash = self.encode_hash(hasher.finish());
+
+        // take first 4 characters, keep adding if there is a collision
+        for i in (MIN_KEY_LENGTH..=hash.len()).take(self.key_max_attempts) {
+            let key = &format!("{}{}", self.key_prefix, &hash[..i]);
+            if let Some(existing) = self.resolve_key(key) { 
+                if self.forward_map[existing].link == link {
+                    return Ok(GeneratedKey::Existing(existing.to_string()));
+                }
+                continue;
+            }
+            if !self.is_available(key) {
+                continue;
+            }
+            return Ok(GeneratedKey::New(key.into()));
+        }
+        Err(KEY_EXHAUSTED.to_string())
+    }
+
+    /// Encode a hash using characters allowed by the key charset.
+    /// 
+    /// This is base64 if both `-` and `_` are allowed, and base62 otherwise.
+    fn encode_hash(&self, hash: u64) -> String {
+        if self.key_charset.allows('-') && self.key_charset.allows('_') {
+            return BASE64_URL_SAFE_NO_PAD.encode(hash.to_le_bytes());
+        }
+
+        const ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+        let mut rest = hash;
+        let mut result = String::new();
+        // Least significant digit first, so prefixes differ as much as possible.
+        loop {
+            result.push(ALPHABET[(rest % 62) as usize] as char);
+            rest /= 62;
+            if rest == 0 {
+                break;
+            }
+        }
+        result
+    }
+
+    /// Use the slug as key, adding a numeric suffix (`-2`, `-3`, ...) if it is taken.
+    fn generate_slug_key(&self, link: &str, slug: &str) -> Result<GeneratedKey, String> {
+        let separator = self.key_charset.separator().map(String::from).unwrap_or_default();
+        for n in (1..).take(self.key_max_attempts) {
+            let prefix = &self.key_prefix;
+            let key = if n == 1 { format!("{prefix}{slug}") } else { format!("{prefix}{slug}{separator}{n}") };
+            match self.resolve_key(&key) {
+                Some(existing) if self.forward_map[existing].link == link => {
+                    return Ok(GeneratedKey::Existing(existing.to_string()))
+                },
+                Some(_) => continue,
+                None if !self.is_available(&key) => continue,
+                None => return Ok(GeneratedKey::New(key))
+            }
+        }
+        Err(KEY_EXHAUSTED.to_string())
+    }
+
+    /// Generate a fresh, unused key for an existing link.
+    /// 
+    /// Unlike [`Links::generate_key`], this never returns the key the link is already stored
+    /// under, since the hash is mixed with a random salt.
+    fn generate_rotated_key(&self, link: &str) -> Result<String, String> {
+        std::iter::repeat_with(|| {
+                let mut hasher = std::hash::DefaultHasher::new();
+                link.hash(&mut hasher);
+                OsRng.next_u64().hash(&mut hasher);
+                self.encode_hash(hasher.finish())
+            })
+            .flat_map(|hash| (MIN_KEY_LENGTH..=hash.len()).map(move |i| format!("{}{}", self.key_prefix, &hash[..i])))
+            .take(self.key_max_attempts)
+            .find(|key| self.is_available(key))
+            .ok_or(KEY_EXHAUSTED.to_string())
+    }
+
+    /// Move the entry at the given key to a newly generated key, keeping its link, metadata
+    /// and password. The old key stops resolving; internal links pointing at it are not
+    /// updated.
+    /// 
+    /// Returns the new key, or `None` if the key does not exist.
+    /// 
+    /// ## Errors
+    /// 
+    /// This function will return an error if no unique key was found, in which case the entry
+    /// is kept at the given key.
+    pub fn rotate(&mut self, key: &str) -> Result<Option<String>, String> {
+        let Some(entry) = self.get(key) else {
+            return Ok(None);
+        };
+        let new_key = self.generate_rotated_key(&entry.link)?;
+        let entry = self.remove(key)
+            .ok_or("Missing link during rotation (unreachable state)")?;
+        self.insert(new_key.clone(), entry)
+            .expect("Generated key already in use (unreachable state)");
+        Ok(Some(new_key))
+    }
+
+    /// Store a copy of the entry at the given key under another key, with fresh metadata so
+    /// both keys are counted separately. The copy keeps the link, title, password and
+    /// alternative target.
+    /// 
+    /// A key is generated if no new key is given. Returns the key of the copy.
+    /// 
+    /// ## Errors
+    /// 
+    /// This function will return an error if the key does not exist, the new key is already
+    /// in use, or no unique key could be generated.
+    pub fn copy(&mut self, key: &str, new_key: Option<String>) -> Result<String, String> {
+        let entry = self.get(key).ok_or("Key does not exist.")?;
+        let copy = Entry {
+            metadata: M::created_at(self.clock.now()),
+            visitors: None,
+            primary: false,
+            protected: false,
+            ..entry.clone()
+        };
+        let new_key = match new_key {
+            Some(key) => key,
+            None => self.generate_rotated_key(&copy.link)?,
+        };
+        self.insert(new_key.clone(), copy)?;
+        Ok(new_key)
+    }
+
+    /// Remove all stored links. Settings like the key strategy are kept.
+    pub fn clear(&mut self) {
+        self.revision += 1;
+        self.forward_map.clear();
+        if let Some(reverse_map) = &mut self.reverse_map {
+            reverse_map.clear();
+        }
+        if let Some(folded_index) = &mut self.folded_index {
+            folded_index.clear();
+        }
+    }
+
+    /// Replace all stored links, e.g. when restoring a backup. Settings like the key strategy
+    /// are kept.
+    /// 
+    /// ## Errors
+    /// 
+    /// This function will return an error if two of the given keys are the same, in which
+    /// case the stored links are left unchanged.
+    pub fn replace_all(&mut self, entries: impl IntoIterator<Item = (String, Entry<M>)>) -> Result<(), String> {
+        let mut replaced = Self {
+            forward_map: HashMap::new(),
+            reverse_map: self.reverse_map.as_ref().map(|_| HashMap::new()),
+            folded_index: self.folded_index.as_ref().map(|_| HashMap::new()),
+            key_strategy: self.key_strategy,
+            key_max_attempts: self.key_max_attempts,
+            disallowed_keys: self.disallowed_keys.clone(),
+            key_charset: self.key_charset.clone(),
+            key_prefix: self.key_prefix.clone(),
+            storage_format: self.storage_format,
+            stats_sidecar: self.stats_sidecar,
+            compressed: self.compressed,
+            clock: self.clock.clone(),
+            revision: self.revision + 1,
+        };
+        for (key, entry) in entries {
+            replaced.insert(key.clone(), entry)
+                .map_err(|_| format!("Duplicate key '{key}'"))?;
+        }
+        *self = replaced;
+        Ok(())
+    }
+
+    /// Remove all links that have expired at the given time and are set to be deleted then.
+    /// Protected links are kept.
+    /// 
+    /// Returns the removed links.
+    pub fn purge_expired(&mut self, now: DateTime<Utc>) -> Vec<(String, Entry<M>)> {
+        let expired = self.forward_map.iter()
+            .filter(|(_, v)| v.on_expire == OnExpire::Delete && v.is_expired(now) && !v.protected)
+            .map(|(k, _)| k.clone())
+            .collect::<Vec<_>>();
+        expired.into_iter()
+            .filter_map(|key| self.remove(&key).map(|entry| (key, entry)))
+            .collect()
+    }
+
+    /// Insert a new mapping with the given key and link.
+    ///
+    /// ## Errors
+    ///
+    /// This function will return an error if the given key is already in use.
+    pub fn add_named(&mut self, key: String, link: String) -> Result<Entry<M>, String> {
+        self.insert(key, Entry::with_metadata(link, M::created_at(self.clock.now())))
+    }
+
+    /// Insert a new mapping with the given key, link and metadata.
+    /// 
+    /// Unlike [`Links::add_named`], the metadata is stored as given instead of being stamped
+    /// fresh, so usage statistics survive when migrating links from elsewhere.
+    ///
+    /// ## Errors
+    ///
+    /// This function will return an error if the given key is already in use.
+    pub fn add_with_metadata(&mut self, key: String, link: String, metadata: M) -> Result<Entry<M>, String> {
+        self.insert(key, Entry::with_metadata(link, metadata))
+    }
+
+    fn insert(&mut self, key: String, mut entry: Entry<M>) -> Result<Entry<M>, String> {
+        if self.contains_key(&key) {
+            return Err("Key already in use.".into());
+        }
+        entry.upgrade_alias();
+        self.revision += 1;
+        if let Some(index) = &mut self.folded_index {
+            index.entry(key.to_lowercase()).or_default().insert(key.clone());
+        }
+
+        let Some(reverse_map) = &mut self.reverse_map else {
+            if entry.primary {
+                for other in self.forward_map.values_mut().filter(|e| e.link == entry.link) {
+                    other.primary = false;
+                }
+            }
+            self.forward_map.insert(key, entry.clone());
+            return Ok(entry);
+        };
+
+        // Update reverse hashmap, keeping the primary key first
+        match reverse_map.entry(entry.link.clone()) {
+            hash_map::Entry::Occupied(mut e) if entry.primary => {
+                for other in e.get() {
+                    if let Some(other) = self.forward_map.get_mut(other) {
+                        other.primary = false;
+                    }
+                }
+                e.get_mut().insert(0, key.clone());
+            },
+            hash_map::Entry::Occupied(mut e) => { 
+                e.get_mut().push(key.clone()); 
+            },
+            hash_map::Entry::Vacant(e) => { 
+                e.insert(vec![key.clone()]); 
+            },
+        }
+        // Update forward hashmap
+        self.forward_map.insert(key, entry.clone());
+        Ok(entry)
+    }
+
+    /// Remove the given mapping.
+    /// 
+    /// Returns `None` if the link alias does not exist.
+    pub fn remove(&mut self, key: &str) -> Option<Entry<M>> {
+        let key = self.resolve_key(key)?.to_string();
+        self.revision += 1;
+        let entry = self.forward_map.remove(&key);
+        if let Some(index) = &mut self.folded_index {
+            // Lookups move on to the next remaining case variant, if any
+            if let hash_map::Entry::Occupied(mut variants) = index.entry(key.to_lowercase()) {
+                variants.get_mut().remove(&key);
+                if variants.get().is_empty() {
+                    variants.remove();
+                }
+            }
+        }
+        
+        // Update reverse hashmap
+        let Some(reverse_map) = &mut self.reverse_map else {
+            return entry;
+        };
+        if let Some(e) = entry {
+            let reverse = reverse_map.get_mut(&e.link)
+                .expect("Missing reverse lookup entry (invalid state)");
+
+            if reverse.len() == 1 {
+                reverse_map.remove(&e.link);
+            } else {
+                let idx = reverse.iter().position(|x| *x == key)
+                    .expect("Missing reverse lookup entry (invalid state)");
+                reverse.remove(idx);
+            }
+            Some(e)
+        } else {
+            None
+        }
+    }
+
+    /// Check that the reverse mapping and case-insensitive index agree with the stored links.
+    /// 
+    /// Returns a description of every inconsistency found, so an empty list means the store
+    /// is consistent.
+    pub fn verify(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        // Without a reverse mapping, only the primary keys are left to check.
+        let built;
+        let reverse_map = match &self.reverse_map {
+            Some(reverse_map) => reverse_map,
+            None => {
+                built = build_reverse_map(&self.forward_map);
+                &built
+            }
+        };
+        for (key, entry) in &self.forward_map {
+            let listed = reverse_map.get(&entry.link)
+                .is_some_and(|keys| keys.iter().filter(|k| *k == key).count() == 1);
+            if !listed {
+                problems.push(format!("Key '{key}' is not listed exactly once for its link '{}'", entry.link));
+            }
+            if let Some(index) = &self.folded_index {
+                if !index.get(&key.to_lowercase()).is_some_and(|variants| variants.contains(key)) {
+                    problems.push(format!("Key '{key}' is missing from the case-insensitive index"));
+                }
+            }
+        }
+        for (link, keys) in reverse_map {
+            if keys.is_empty() {
+                problems.push(format!("Link '{link}' has no keys"));
+            }
+            for key in keys {
+                if self.forward_map.get(key).is_none_or(|e| e.link != *link) {
+                    problems.push(format!("Key '{key}' is listed for link '{link}' but doesn't point at it"));
+                }
+            }
+            let primaries = keys.iter()
+                .filter(|k| self.forward_map.get(*k).is_some_and(|e| e.primary))
+                .count();
+            if primaries > 1 {
+                problems.push(format!("Link '{link}' has {primaries} primary keys"));
+            }
+        }
+        problems.sort_unstable();
+        problems
+    }
+
+    /// Mark the given key as the canonical alias of its link, clearing the mark on any other
+    /// alias. The primary key is listed first by [`Links::find_by_link`] and returned when the
+    /// link is added again without a key.
+    /// 
+    /// ## Errors
+    /// 
+    /// This function will return an error if the key does not exist.
+    pub fn make_primary(&mut self, key: &str) -> Result<(), String> {
+        let key = self.resolve_key(key).ok_or("Key does not exist.")?.to_string();
+        self.revision += 1;
+        let link = self.forward_map[&key].link.clone();
+        let Some(reverse_map) = &mut self.reverse_map else {
+            for (alias, entry) in self.forward_map.iter_mut().filter(|(_, e)| e.link == link) {
+                entry.primary = *alias == key;
+            }
+            return Ok(());
+        };
+        let aliases = reverse_map.get_mut(&link)
+            .expect("Missing reverse lookup entry (invalid state)");
+        for alias in aliases.iter() {
+            if let Some(entry) = self.forward_map.get_mut(alias) {
+                entry.primary = *alias == key;
+            }
+        }
+        let idx = aliases.iter().position(|x| *x == key)
+            .expect("Missing reverse lookup entry (invalid state)");
+        aliases[..=idx].rotate_right(1);
+        Ok(())
+    }
+
+    /// The key marked as primary for the given link, if any.
+    fn primary_key(&self, link: &str) -> Option<&str> {
+        let Some(reverse_map) = &self.reverse_map else {
+            return self.forward_map.iter()
+                .find(|(_, e)| e.primary && e.link == link)
+                .map(|(k, _)| k.as_str());
+        };
+        let first = reverse_map.get(link)?.first()?;
+        self.forward_map[first].primary.then_some(first.as_str())
+    }
+
+    /// Find aliases that map to the given link, the primary one first if there is one.
+    /// 
+    /// Aliases are listed in the order they were added, or sorted if the reverse mapping is
+    /// disabled. Returns `None` if the link has no associated aliases.
+    pub fn find_by_link(&self, link: impl AsRef<str>) -> Option<Vec<String>> {
+        let link = link.as_ref();
+        let Some(reverse_map) = &self.reverse_map else {
+            let mut keys = self.forward_map.iter()
+                .filter(|(_, e)| e.link == link)
+                .map(|(k, e)| (!e.primary, k.clone()))
+                .collect::<Vec<_>>();
+            keys.sort_unstable();
+            return (!keys.is_empty()).then(|| keys.into_iter().map(|(_, k)| k).collect());
+        };
+        reverse_map.get(link).cloned()
+    }
+
+    /// All distinct links with their keys, in the same order as [`Links::find_by_link`].
+    /// The links are in no particular order.
+    pub fn aliases_by_link(&self) -> Vec<(&str, Vec<&str>)> {
+        if let Some(reverse_map) = &self.reverse_map {
+            return reverse_map.iter()
+                .map(|(link, keys)| (link.as_str(), keys.iter().map(String::as_str).collect()))
+                .collect();
+        }
+        let mut by_link: HashMap<&str, Vec<(bool, &str)>> = HashMap::new();
+        for (key, entry) in &self.forward_map {
+            by_link.entry(&entry.link).or_default().push((!entry.primary, key));
+        }
+        by_link.into_iter()
+            .map(|(link, mut keys)| {
+                keys.sort_unstable();
+                (link, keys.into_iter().map(|(_, k)| k).collect())
+            })
+            .collect()
+    }
+
+    /// Save link data to the given file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String>{
+        if is_in_memory(path.as_ref()) {
+            return Ok(());
+        }
+        write_file(path.as_ref(), &self.to_toml()?)
+    }
+
+    /// Serialize the links in the configured storage format, as written by [`Links::save`].
+    pub fn to_toml(&self) -> Result<LinkFiles, String> {
+        let error = |e: toml::ser::Error| format!("Could not serialize links: {e}");
+        let mut link = self.forward_map.iter()
+            .map(|(key, entry)| KeyedEntry { key: key.as_str(), entry })
+            .collect::<Vec<_>>();
+        link.sort_unstable_by_key(|e| e.key);
+
+        if !self.stats_sidecar {
+            let links = match self.storage_format {
+                StorageFormat::Map => toml::to_string(&self.forward_map.iter().collect::<HashMap<_, _>>()),
+                StorageFormat::List => toml::to_string(&LinkList { link }),
+            }.map_err(error)?;
+            return Ok(LinkFiles { links, stats: None, compressed: self.compressed });
+        }
+
+        // Round-trip through a table to split off the statistics. Tables are sorted, so the
+        // file is stable between saves.
+        let mut table = match self.storage_format {
+            StorageFormat::Map => toml::Table::try_from(&self.forward_map),
+            StorageFormat::List => toml::Table::try_from(LinkList { link }),
+        }.map_err(error)?;
+        let mut stats = toml::Table::new();
+        for (key, metadata) in metadata_tables(&mut table) {
+            let volatile = VOLATILE_FIELDS.iter()
+                .filter_map(|&field| Some((field.to_string(), metadata.remove(field)?)))
+                .collect::<toml::Table>();
+            if !volatile.is_empty() {
+                stats.insert(key, volatile.into());
+            }
+        }
+        Ok(LinkFiles {
+            links: toml::to_string(&table).map_err(error)?,
+            stats: Some(toml::to_string(&stats).map_err(error)?),
+            compressed: self.compressed,
+        })
+    }
+
+    pub fn iter(&self) -> hash_map::Iter<'_, String, Entry<M>> {
+        self.forward_map.iter()
+    }
+
+    /// Pick one of the links matching the filter uniformly at random.
+    /// 
+    /// Returns `None` if no link matches.
+    pub fn random(&self, filter: impl Fn(&str, &Entry<M>) -> bool) -> Option<(&str, &Entry<M>)> {
+        let count = self.forward_map.iter().filter(|(k, e)| filter(k, e)).count();
+        if count == 0 {
+            return None;
+        }
+        let idx = (OsRng.next_u64() % count as u64) as usize;
+        self.forward_map.iter()
+            .filter(|(k, e)| filter(k, e))
+            .nth(idx)
+            .map(|(k, e)| (k.as_str(), e))
+    }
+}
+
+/// Link data serialized by [`Links::to_toml`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinkFiles {
+    /// Contents of the link data file.
+    pub links: String,
+    /// Contents of the statistics file, if statistics are kept separately.
+    pub stats: Option<String>,
+    /// Whether the files are written gzipped.
+    pub compressed: bool,
+}
+
+/// Map each link to its keys, the primary key first.
+fn build_reverse_map<M>(forward_map: &HashMap<String, Entry<M>>) -> HashMap<String, Vec<String>> {
+    let mut reverse_map: HashMap<String, Vec<String>> = HashMap::new();
+    for (k, v) in forward_map {
+        if reverse_map.contains_key(&v.link) {
+            // link already has associated key; add to existing list, primary key first
+            let keys = reverse_map.get_mut(&v.link).unwrap();
+            if v.primary {
+                keys.insert(0, k.clone());
+            } else {
+                keys.push(k.clone());
+            }
+        } else {
+            // create a new entry for this link
+            reverse_map.insert(v.link.clone(), vec![k.clone()]);
+        }
+    }
+    reverse_map
+}
+
+/// Metadata fields that change whenever a link is followed, see [`Links::set_stats_sidecar`].
+const VOLATILE_FIELDS: &[&str] = &["used", "last_used"];
+
+/// Path of the file holding the statistics of the links in the given file when they are
+/// kept separately, e.g. `links.stats.toml` for `links.toml` and `links.stats.toml.gz` for
+/// `links.toml.gz`.
+pub fn stats_path(path: &Path) -> PathBuf {
+    if is_compressed_path(path) {
+        path.with_extension("").with_extension("stats.toml.gz")
+    } else {
+        path.with_extension("stats.toml")
+    }
+}
+
+/// Whether the name of the link data file asks for it to be gzipped, i.e. ends in `.gz`.
+pub fn is_compressed_path(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "gz")
+}
+
+/// Magic bytes at the start of gzipped files.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Read a file that may be gzipped, and whether it was.
+fn read_file(path: &Path) -> std::io::Result<(String, bool)> {
+    let bytes = std::fs::read(path)?;
+    if bytes.starts_with(&GZIP_MAGIC) {
+        let mut data = String::new();
+        GzDecoder::new(bytes.as_slice()).read_to_string(&mut data)?;
+        return Ok((data, true));
+    }
+    let data = String::from_utf8(bytes)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    Ok((data, false))
+}
+
+/// Replace the file with the given data, gzipped if requested.
+/// 
+/// The data is written to a temporary file next to it first and then renamed, so readers
+/// never see a partially written file.
+fn replace_file(path: &Path, data: &str, compressed: bool) -> std::io::Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let mut file = std::fs::File::create(&tmp_path)?;
+    if compressed {
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        encoder.write_all(data.as_bytes())?;
+        encoder.finish()?;
+    } else {
+        file.write_all(data.as_bytes())?;
+    }
+    std::fs::rename(&tmp_path, path)
+}
+
+/// Write links serialized by [`Links::to_toml`] to the given file, and their statistics to
+/// the file at [`stats_path`]. The statistics file is removed if it isn't used.
+pub fn write_file(path: &Path, data: &LinkFiles) -> Result<(), String> {
+    if is_in_memory(path) {
+        return Ok(());
+    }
+    let write_error = |path: &Path, e: std::io::Error| format!("Could not write to file '{}': {}", path.display(), e);
+    replace_file(path, &data.links, data.compressed)
+        .map_err(|e| write_error(path, e))?;
+
+    let stats_path = stats_path(path);
+    match &data.stats {
+        Some(stats) => replace_file(&stats_path, stats, data.compressed)
+            .map_err(|e| write_error(&stats_path, e)),
+        None if stats_path.exists() => std::fs::remove_file(&stats_path)
+            .map_err(|e| format!("Could not remove file '{}': {}", stats_path.display(), e)),
+        None => Ok(())
+    }
+}
+
+/// The metadata table of every entry in a parsed link data file, by key.
+fn metadata_tables(table: &mut toml::Table) -> Vec<(String, &mut toml::Table)> {
+    // Same detection of the storage format as in `Links::load`
+    let entries: Vec<(String, &mut toml::Table)> = if table.get("link").is_some_and(toml::Value::is_array) {
+        table.get_mut("link").and_then(toml::Value::as_array_mut).into_iter().flatten()
+            .filter_map(|v| v.as_table_mut())
+            .filter_map(|t| Some((t.get("key")?.as_str()?.to_string(), t)))
+            .collect()
+    } else {
+        table.iter_mut()
+            .filter_map(|(k, v)| Some((k.clone(), v.as_table_mut()?)))
+            .collect()
+    };
+    entries.into_iter()
+        .filter_map(|(k, t)| Some((k, t.get_mut("metadata")?.as_table_mut()?)))
+        .collect()
+}
+
+/// Move the statistics from a parsed statistics file back into the metadata of the entries.
+/// 
+/// Entries without statistics, e.g. added to the link data file by hand, count as unused
+/// since their creation.
+fn merge_stats(table: &mut toml::Table, stats: &toml::Table) {
+    for (key, metadata) in metadata_tables(table) {
+        if let Some(toml::Value::Table(volatile)) = stats.get(&key) {
+            metadata.extend(volatile.clone());
+        }
+        if let Some(created) = metadata.get("created").cloned() {
+            metadata.entry("used").or_insert(0.into());
+            metadata.entry("last_used").or_insert(created);
+        }
+    }
+}
+
+impl<M> IntoIterator for Links<M> {
+    type Item = (String, Entry<M>);
+
+    type IntoIter = std::collections::hash_map::IntoIter<String, Entry<M>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.forward_map.into_iter()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+
+    use vector_assertions::assert_vec_eq;
+
+    use super::*;
+
+    #[test]
+    fn generate_key() {
+        let mut links = Links::new();
+        let link = "https://example.com";
+        let Ok(GeneratedKey::New(key)) = links.generate_key(link) else {
+            panic!("expected a new key");
+        };
+        assert_eq!(key.len(), 4);
+        links.add_named(key.clone(), link.to_string()).unwrap();
+
+        assert_eq!(links.generate_key(link), Ok(GeneratedKey::Existing(key)));
+    }
+
+    #[test]
+    fn generate_key_skips_disallowed() {
+        let link = "https://example.com";
+        let Ok(GeneratedKey::New(first)) = Links::new().generate_key(link) else {
+            panic!("expected a new key");
+        };
+
+        let mut links = Links::new();
+        links.set_disallowed_keys([first.to_uppercase()]);
+        let Ok(GeneratedKey::New(key)) = links.generate_key(link) else {
+            panic!("expected a new key");
+        };
+        assert_eq!(key.len(), first.len() + 1);
+        assert!(key.starts_with(&first));
+
+        links.set_key_strategy(KeyStrategy::Slug);
+        links.set_disallowed_keys(["guide"]);
+        assert_eq!(links.generate_key("https://example.com/guide"), Ok(GeneratedKey::New("guide-2".to_string())));
+    }
+
+    #[test]
+    fn slug_sanitization() {
+        assert_eq!(slug_from_link("https://example.com/docs/guide", Some('-')).as_deref(), Some("guide"));
+        assert_eq!(slug_from_link("https://example.com/docs/guide/", Some('-')).as_deref(), Some("guide"));
+        assert_eq!(slug_from_link("https://example.com/Getting Started.html?x=1#top", Some('-')).as_deref(), Some("getting-started"));
+        assert_eq!(slug_from_link("https://example.com/__init__", Some('-')).as_deref(), Some("init"));
+        assert_eq!(slug_from_link("https://user@www.example.com:8080", Some('-')).as_deref(), Some("example-com"));
+        assert_eq!(slug_from_link("example.com/release-notes", Some('-')).as_deref(), Some("release-notes"));
+        // Too short
+        assert_eq!(slug_from_link("https://example.com/a", Some('-')), None);
+        assert_eq!(slug_from_link("https://example.com/---", Some('-')), None);
+    }
+
+    #[test]
+    fn custom_key_charset() {
+        let charset: KeyCharset = "._".parse().unwrap();
+        assert!(charset.allows('.') && charset.allows('_') && charset.allows('é'));
+        assert!(!charset.allows('-'));
+        assert_eq!(charset.describe(), "0-9, A-Z, a-z, . or _");
+        assert_eq!(charset.pattern(), r"^[\p{Alphabetic}\p{N}\._]+$");
+        assert_eq!(KeyCharset::default().describe(), "0-9, A-Z, a-z, _ or -");
+        assert!("/".parse::<KeyCharset>().is_err());
+        assert!("abc".parse::<KeyCharset>().is_err());
+
+        let mut links = Links::new();
+        links.set_key_charset("alphanumeric".parse().unwrap());
+        for i in 0..100 {
+            let (key, _) = links.add(format!("https://example.com/{i}")).unwrap().into_parts();
+            assert!(key.chars().all(|c| c.is_ascii_alphanumeric()), "{key}");
+        }
+
+        links.set_key_strategy(KeyStrategy::Slug);
+        links.set_key_charset(charset);
+        let (key, _) = links.add("https://example.com/release-notes".to_string()).unwrap().into_parts();
+        assert_eq!(key, "release_notes");
+        let (key, _) = links.add("https://example.org/release-notes".to_string()).unwrap().into_parts();
+        assert_eq!(key, "release_notes_2");
+    }
+
+    #[test]
+    fn key_prefix() {
+        let mut links = Links::new();
+        links.set_key_prefix("p-");
+        let (first, _) = links.add("https://example.com/a".to_string()).unwrap().into_parts();
+        assert!(first.starts_with("p-") && first.len() == 2 + MIN_KEY_LENGTH, "{first}");
+        let (again, _) = links.add("https://example.com/a".to_string()).unwrap().into_parts();
+        assert_eq!(again, first);
+
+        // Disallowed keys are matched against the whole key
+        let mut links = Links::new();
+        links.set_key_prefix("p-");
+        links.set_disallowed_keys([first.as_str()]);
+        let (key, _) = links.add("https://example.com/a".to_string()).unwrap().into_parts();
+        assert!(key.starts_with(&first) && key.len() > first.len(), "{key}");
+
+        links.set_key_strategy(KeyStrategy::Slug);
+        let (key, _) = links.add("https://example.com/guide".to_string()).unwrap().into_parts();
+        assert_eq!(key, "p-guide");
+        let (key, _) = links.add("https://example.org/guide".to_string()).unwrap().into_parts();
+        assert_eq!(key, "p-guide-2");
+    }
+
+    #[test]
+    fn slug_collision_suffix() {
+        let mut links = Links::new();
+        links.set_key_strategy(KeyStrategy::Slug);
+
+        let (key, _) = links.add("https://example.com/docs/guide".to_string()).unwrap().into_parts();
+        assert_eq!(key, "guide");
+        let (key, _) = links.add("https://example.org/guide".to_string()).unwrap().into_parts();
+        assert_eq!(key, "guide-2");
+        let (key, _) = links.add("https://example.net/guide".to_string()).unwrap().into_parts();
+        assert_eq!(key, "guide-3");
+
+        // Existing links keep their key
+        let result = links.add("https://example.org/guide".to_string()).unwrap();
+        assert!(!result.is_created());
+        assert_eq!(result.into_parts().0, "guide-2");
+    }
+
+    #[test]
+    fn slug_fallback_to_hash() {
+        let mut links = Links::new();
+        links.set_key_strategy(KeyStrategy::Slug);
+
+        let link = "https://example.com/a";
+        assert_eq!(links.generate_key(link), Links::new().generate_hash_key(link));
+    }
+
+    #[test]
+    fn key_max_attempts() {
+        let mut links = Links::new();
+        links.set_key_strategy(KeyStrategy::Slug);
+        links.set_key_max_attempts(3);
+        for domain in ["example.com", "example.org", "example.net"] {
+            links.add(format!("https://{domain}/guide")).unwrap();
+        }
+
+        // All of `guide`, `guide-2` and `guide-3` are taken
+        let err = links.add("https://example.edu/guide".to_string()).unwrap_err();
+        assert_eq!(err, "Could not allocate a unique key");
+        assert_eq!(links.len(), 3);
+        // Existing links are still found
+        assert!(!links.add("https://example.org/guide".to_string()).unwrap().is_created());
 
+        links.set_key_strategy(KeyStrategy::Hash);
+        links.set_key_max_attempts(1);
+        let link = "https://example.com/docs";
+        let Ok(GeneratedKey::New(key)) = links.generate_key(link) else {
+            panic!("expected a new key");
+        };
+        links.set_disallowed_keys([key]);
+        assert!(links.add(link.to_string()).is_err());
+    }
 
-/// Stores alias->link mappings and the reverse mapping.
-#[derive(Clone, Debug, Default)]
-pub struct Links { 
-    /// Forward hashmap is used for finding the associated link for a given alias.
-    forward_map: HashMap<String, Entry>, 
-    /// Inverse of the forward hashmap.
-    /// The forward mapping is surjective, so each link can have multiple associated aliases.
-    /// 
-    /// Note: might be worth benching to see if linear search is actually slower.
-    reverse_map: HashMap<String, Vec<String>>,
-}
+    #[test]
+    fn add_with_metadata() {
+        let mut links = Links::new();
+        let created = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let metadata = EntryMetadata { used: 42, last_used: created, created, source: None };
 
-impl Links {
-    /// Load link data from the given file
-    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {        
-        let path = path.as_ref();
+        let entry = links.add_with_metadata("key1".to_string(), "https://example.com".to_string(), metadata.clone()).unwrap();
+        assert_eq!(entry.metadata, metadata);
+        assert_eq!(links.get("key1").unwrap().metadata, metadata);
+        assert_eq!(links.find_by_link("https://example.com").unwrap(), ["key1"]);
 
-        if !path.exists() {
-            // Create the directory if it doesn't exist.
-            std::fs::create_dir_all(
-                path.parent()
-                    .ok_or(format!("Invalid link data path: '{}'", path.display()))?
-            ).map_err(|e| format!("Could not create directory: {e}"))?;
-            
-            // Create empty link storage & write to file
-            let result: Self = Self { 
-                forward_map: HashMap::new(), 
-                reverse_map: HashMap::new() 
-            };
-            result.save(path)?;
-            Ok(result)
-        } else {
-            // Read file contents
-            let data = std::fs::read_to_string(path)
-                .map_err(|e| format!("Could not load links: {e}"))?;
-
-            let forward_map: HashMap<String, Entry> = toml::from_str(&data).unwrap();
-
-            // Build reverse lookup
-            let mut reverse_map: HashMap<String, Vec<String>> = HashMap::new();
-            for (k, v) in &forward_map {
-                if reverse_map.contains_key(&v.link) {
-                    // link already has associated key; add to existing list
-                    reverse_map.get_mut(&v.link).unwrap().push(k.clone());
-                } else {
-                    // create a new entry for this link
-                    reverse_map.insert(v.link.clone(), vec![k.clone()]);
-                }
-            }
-            Ok(Self { forward_map, reverse_map })
-        }
+        assert!(links.add_with_metadata("key1".to_string(), "https://example.org".to_string(), metadata).is_err());
     }
 
-    pub fn get(&self, key: &str) -> Option<&Entry> {
-        self.forward_map.get(key)
-    }
-    
-    pub fn get_mut(&mut self, key: &str) -> Option<&mut Entry> {
-        self.forward_map.get_mut(key)
+    #[test]
+    fn created_with_clock() {
+        let created = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let clock = Arc::new(crate::clock::MockClock::new(created));
+        let mut links: Links = Links::new();
+        links.set_clock(clock.clone());
+
+        links.add_named("key1".to_string(), "https://example.com".to_string()).unwrap();
+        clock.advance(chrono::Duration::days(1));
+        links.copy("key1", Some("key2".to_string())).unwrap();
+
+        assert_eq!(links.get("key1").unwrap().metadata.created, created);
+        assert_eq!(links.get("key1").unwrap().metadata.last_used, created);
+        assert_eq!(links.get("key2").unwrap().metadata.created, created + chrono::Duration::days(1));
     }
 
-    /// Insert a new mapping with a generated key and the given link.
-    ///
-    /// ## Errors
-    ///
-    /// This function will return an error if the key is already in use, a.k.a. the link
-    /// already has an associated mapping 
-    pub fn add(&mut self, link: String) -> (String, Entry) {
-        match self.generate_key(&link) {
-            Ok(key) => (key.clone(), self.add_named(key, link).unwrap()),
-            Err(pair) => pair
+    #[test]
+    fn custom_metadata() {
+        #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+        struct Owner {
+            name: String,
         }
+        impl Metadata for Owner {}
+
+        let mut links = Links::<Owner>::default();
+        links.add_named("key1".to_string(), "https://example.com".to_string()).unwrap();
+        links.get_mut("key1").unwrap().metadata.name = "alice".to_string();
+
+        let tmp_file = temp_dir().join("landmower_test_custom_metadata.toml");
+        links.save(&tmp_file).unwrap();
+        let loaded = Links::<Owner>::load(&tmp_file).unwrap();
+        std::fs::remove_file(&tmp_file).unwrap();
+
+        assert_eq!(loaded.get("key1").unwrap().metadata.name, "alice");
     }
-    
-    fn generate_key(&self, link: &str) -> Result<String, (String, Entry)> {
-        // hash + base64 encode
-        let mut hasher = std::hash::DefaultHasher::new();
-        link.hash(&mut hasher);
-        let hash = BASE64_URL_SAFE_NO_PAD.encode(hasher.finish().to_le_bytes());
 
-        // take first 4 characters, keep adding if there is a collision
-        for i in 4..=hash.len() {
-            let key = &hash[..i];
-            if let Some(other) = self.forward_map.get(key) { 
-                if other.link == link {
-                    return Err((key.to_string(), other.clone()));
-                }
-                continue;
-            }
-            return Ok(key.into());
-        }
-        let other = self.get(&hash).unwrap().clone();
-        Err((hash, other)) // hash collision -> link already present in storage
+    #[test]
+    fn storage_format_migration() {
+        let tmp_file = temp_dir().join("landmower_test_storage_format.toml");
+        let _ = std::fs::remove_file(&tmp_file);
+
+        let mut links = Links::new();
+        links.add_named("key2".to_string(), "https://example2.com".to_string()).unwrap();
+        let entry = links.add_named("key1".to_string(), "https://example1.com".to_string()).unwrap();
+        links.set_storage_format(StorageFormat::List);
+        links.save(&tmp_file).unwrap();
+
+        let data = std::fs::read_to_string(&tmp_file).unwrap();
+        assert!(data.starts_with("[[link]]\nkey = \"key1\""), "{data}");
+
+        // Round-trip in list format
+        let mut loaded: Links = Links::load(&tmp_file).unwrap();
+        assert_eq!(loaded.storage_format(), StorageFormat::List);
+        assert_eq!(loaded.get("key1"), Some(&entry));
+        assert_eq!(loaded.find_by_link("https://example2.com"), Some(vec!["key2".to_string()]));
+
+        // List -> map
+        loaded.set_storage_format(StorageFormat::Map);
+        loaded.save(&tmp_file).unwrap();
+        let loaded: Links = Links::load(&tmp_file).unwrap();
+        assert_eq!(loaded.storage_format(), StorageFormat::Map);
+        assert_eq!(loaded.get("key1"), Some(&entry));
+        assert_eq!(loaded.len(), 2);
+
+        std::fs::remove_file(&tmp_file).unwrap();
     }
 
-    /// Insert a new mapping with the given key and link.
-    ///
-    /// ## Errors
-    ///
-    /// This function will return an error if the given key is already in use.
-    pub fn add_named(&mut self, key: String, link: String) -> Result<Entry, String> {
-        let entry = Entry::from(link);
-        // Update reverse hashmap
-        match self.reverse_map.entry(entry.link.clone()) {
-            hash_map::Entry::Occupied(mut e) => { 
-                e.get_mut().push(key.clone()); 
-            },
-            hash_map::Entry::Vacant(e) => { 
-                e.insert(vec![key.clone()]); 
-            },
-        }
-        // Update forward hashmap
-        if let hash_map::Entry::Vacant(e) = self.forward_map.entry(key) {
-            e.insert(entry.clone());
-            Ok(entry)
-        } else {
-            Err("Key already in use.".into())
+    #[test]
+    fn stats_sidecar() {
+        let tmp_file = temp_dir().join("landmower_test_stats_sidecar.toml");
+        let stats_file = stats_path(&tmp_file);
+        let _ = std::fs::remove_file(&tmp_file);
+        let _ = std::fs::remove_file(&stats_file);
+        assert_eq!(stats_file, temp_dir().join("landmower_test_stats_sidecar.stats.toml"));
+
+        for format in [StorageFormat::Map, StorageFormat::List] {
+            let mut links = Links::new();
+            links.set_storage_format(format);
+            links.set_stats_sidecar(true);
+            links.add_named("key1".to_string(), "https://example1.com".to_string()).unwrap();
+            links.add_named("key2".to_string(), "https://example2.com".to_string()).unwrap();
+            links.get_mut("key1").unwrap().metadata.used = 5;
+            links.save(&tmp_file).unwrap();
+
+            let data = std::fs::read_to_string(&tmp_file).unwrap();
+            assert!(!data.contains("used"), "{data}");
+            assert!(stats_file.exists());
+
+            // Following links doesn't change the link data file
+            links.get_mut("key1").unwrap().metadata.used = 6;
+            links.get_mut("key1").unwrap().metadata.last_used = Utc::now();
+            links.save(&tmp_file).unwrap();
+            assert_eq!(std::fs::read_to_string(&tmp_file).unwrap(), data);
+
+            let mut loaded: Links = Links::load(&tmp_file).unwrap();
+            assert!(loaded.stats_sidecar());
+            assert_eq!(loaded.storage_format(), format);
+            assert_eq!(loaded.get("key1"), links.get("key1"));
+            assert_eq!(loaded.get("key2"), links.get("key2"));
+
+            // Migrate back into a single file
+            loaded.set_stats_sidecar(false);
+            loaded.save(&tmp_file).unwrap();
+            assert!(!stats_file.exists());
+            let loaded: Links = Links::load(&tmp_file).unwrap();
+            assert!(!loaded.stats_sidecar());
+            assert_eq!(loaded.get("key1").unwrap().metadata.used, 6);
+
+            std::fs::remove_file(&tmp_file).unwrap();
         }
     }
 
-    /// Remove the given mapping.
-    /// 
-    /// Returns `None` if the link alias does not exist.
-    pub fn remove(&mut self, key: &str) -> Option<Entry> {
-        let entry = self.forward_map.remove(key);
-        
-        // Update reverse hashmap
-        if let Some(e) = entry {
-            println!("map {:?}", self.reverse_map);
-            let reverse = self.reverse_map.get_mut(&e.link)
-                .expect("Missing reverse lookup entry (invalid state)");
+    #[test]
+    fn compressed_round_trip() {
+        let tmp_file = temp_dir().join("landmower_test_compressed.toml.gz");
+        let stats_file = stats_path(&tmp_file);
+        let _ = std::fs::remove_file(&tmp_file);
+        let _ = std::fs::remove_file(&stats_file);
+        assert_eq!(stats_file, temp_dir().join("landmower_test_compressed.stats.toml.gz"));
 
-            if reverse.len() == 1 {
-                self.reverse_map.remove(&e.link);
-            } else {
-                let idx = reverse.iter().position(|x| *x == key)
-                    .expect("Missing reverse lookup entry (invalid state)");
-                reverse.remove(idx);
-            }
-            Some(e)
-        } else {
-            None
+        // New files named `*.gz` are compressed
+        let mut links: Links = Links::load(&tmp_file).unwrap();
+        assert!(links.compressed());
+        for i in 0..100 {
+            links.add_named(format!("key{i}"), format!("https://example.com/{i}")).unwrap();
         }
-    }
+        links.get_mut("key1").unwrap().metadata.used = 5;
+        links.set_stats_sidecar(true);
+        links.save(&tmp_file).unwrap();
+        assert!(std::fs::read(&tmp_file).unwrap().starts_with(&GZIP_MAGIC));
+        assert!(std::fs::read(&stats_file).unwrap().starts_with(&GZIP_MAGIC));
 
-    /// Find aliases that map to the given link.
-    /// 
-    /// Returns `None` if the link has no associated aliases.
-    pub fn find_by_link(&self, link: impl AsRef<str>) -> Option<&[String]> {
-        self.reverse_map.get(link.as_ref()).map(|v| v.as_slice())
-    }
+        let loaded: Links = Links::load(&tmp_file).unwrap();
+        assert!(loaded.compressed());
+        assert_eq!(loaded.len(), 100);
+        for (key, entry) in links.iter() {
+            assert_eq!(loaded.get(key), Some(entry));
+        }
+        std::fs::remove_file(&tmp_file).unwrap();
+        std::fs::remove_file(&stats_file).unwrap();
 
-    /// Save link data to the given file.
-    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String>{
-        let path = path.as_ref();
-        let data = toml::to_string(&self.forward_map.iter().collect::<HashMap<_, _>>())
-            .unwrap();
-        std::fs::write(path, data)
-            .map_err(|e| format!("Could not write to file '{}': {}", path.display(), e))?;
-        Ok(())
-    }
+        // Compression is detected regardless of the name, and can be turned off again
+        let tmp_file = temp_dir().join("landmower_test_compressed.toml");
+        links.set_stats_sidecar(false);
+        links.save(&tmp_file).unwrap();
+        let mut loaded: Links = Links::load(&tmp_file).unwrap();
+        assert!(loaded.compressed());
+        assert_eq!(loaded.get("key1"), links.get("key1"));
 
-    pub fn iter(&self) -> hash_map::Iter<'_, String, Entry> {
-        self.forward_map.iter()
+        loaded.set_compressed(false);
+        loaded.save(&tmp_file).unwrap();
+        assert!(std::fs::read_to_string(&tmp_file).unwrap().contains("https://example.com/1"));
+        let loaded: Links = Links::load(&tmp_file).unwrap();
+        assert!(!loaded.compressed());
+        assert_eq!(loaded.len(), 100);
+
+        std::fs::remove_file(&tmp_file).unwrap();
     }
-}
 
-impl IntoIterator for Links {
-    type Item = (String, Entry);
+    #[test]
+    fn stats_sidecar_missing_entry() {
+        let tmp_file = temp_dir().join("landmower_test_stats_missing.toml");
+        let created = "2024-01-01T00:00:00Z";
+        std::fs::write(&tmp_file, format!("[key1]\nlink = \"https://example.com\"\n\n[key1.metadata]\ncreated = \"{created}\"\n")).unwrap();
+        std::fs::write(stats_path(&tmp_file), "").unwrap();
 
-    type IntoIter = std::collections::hash_map::IntoIter<String, Entry>;
+        let loaded: Links = Links::load(&tmp_file).unwrap();
+        let metadata = &loaded.get("key1").unwrap().metadata;
+        assert_eq!(metadata.used, 0);
+        assert_eq!(metadata.last_used, created.parse::<DateTime<Utc>>().unwrap());
 
-    fn into_iter(self) -> Self::IntoIter {
-        self.forward_map.into_iter()
+        std::fs::remove_file(&tmp_file).unwrap();
+        std::fs::remove_file(stats_path(&tmp_file)).unwrap();
     }
-}
-
 
-#[cfg(test)]
-mod tests {
-    use std::env::temp_dir;
+    #[test]
+    fn load_unwritable_dir() {
+        // A regular file where the data directory should be
+        let blocker = temp_dir().join("landmower_test_load_blocker");
+        std::fs::write(&blocker, "").unwrap();
+        let path = blocker.join("links.toml");
 
-    use vector_assertions::assert_vec_eq;
+        let err = Links::<EntryMetadata>::load(&path).unwrap_err();
+        assert!(err.starts_with("Could not create directory"), "{err}");
+        assert!(err.contains(&*path.to_string_lossy()), "{err}");
 
-    use super::*;
+        std::fs::remove_file(&blocker).unwrap();
+    }
 
     #[test]
-    fn generate_key() {
-        let mut links = Links { forward_map: HashMap::new(), reverse_map: HashMap::new() };
-        let link = "https://example.com";
-        let key = links.generate_key(link).unwrap();
-        assert_eq!(key.len(), 4);
-        let entry = links.add_named(key.clone(), link.to_string()).unwrap();
-        let result = links.generate_key(link);
+    fn in_memory() {
+        let mut links = Links::<EntryMetadata>::load(IN_MEMORY_PATH).unwrap();
+        assert!(links.is_empty());
 
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), (key, entry));
+        links.add_named("key1".to_string(), "https://example.com".to_string()).unwrap();
+        links.save(IN_MEMORY_PATH).unwrap();
+        write_file(Path::new(IN_MEMORY_PATH), &links.to_toml().unwrap()).unwrap();
+        assert!(!Path::new(IN_MEMORY_PATH).exists());
     }
 
     #[test]
@@ -244,14 +2027,14 @@ mod tests {
             ("-_0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz", "https://example5.com"),
         ].into_iter()
         .map(|(k, v)| (k.to_string(), Entry::from(v.to_string())))
-        .collect::<HashMap<_, _>>();
+        .collect::<HashMap<String, Entry>>();
 
-        let links = Links { forward_map: test_links, reverse_map: HashMap::new() };        
+        let links = Links { forward_map: test_links, ..Default::default() };        
         let tmp_file = temp_dir().join("landmower_test.toml");
         
         links.save(&tmp_file).unwrap();
 
-        let loaded = Links::load(&tmp_file).unwrap();            
+        let loaded: Links = Links::load(&tmp_file).unwrap();            
         
         println!("{:?}", loaded);
         let old_keys: Vec<_> = links.forward_map        
@@ -277,34 +2060,49 @@ mod tests {
 
     #[test]
     fn add() {
-        let mut links = Links { forward_map: HashMap::new(), reverse_map: HashMap::new() };
+        let mut links = Links::new();
         let link = "https://example.com";
         
-        let (key, entry) = links.add(link.to_string());
+        let result = links.add(link.to_string()).unwrap();
+        assert!(result.is_created());
+        let (key, entry) = result.into_parts();
+        
+        assert_eq!(links.forward_map.len(), 1);
+        assert_eq!(links.reverse_map.as_ref().unwrap().len(), 1);
+        assert_eq!(links.reverse_map.as_ref().unwrap().get(&entry.link).unwrap().len(), 1);        
+        assert_eq!(links.reverse_map.as_ref().unwrap().get(&entry.link).unwrap()[0], key);
+    }
+
+    #[test]
+    fn add_existing() {
+        let mut links = Links::new();
+        let link = "https://example.com";
         
+        let (key, _) = links.add(link.to_string()).unwrap().into_parts();
+        let result = links.add(link.to_string()).unwrap();
+
+        assert!(!result.is_created());
+        assert_eq!(result.into_parts().0, key);
         assert_eq!(links.forward_map.len(), 1);
-        assert_eq!(links.reverse_map.len(), 1);
-        assert_eq!(links.reverse_map.get(&entry.link).unwrap().len(), 1);        
-        assert_eq!(links.reverse_map.get(&entry.link).unwrap()[0], key);
     }
 
     #[test]
     fn add_named_base_case() {
-        let mut links = Links { forward_map: HashMap::new(), reverse_map: HashMap::new() };
+        let mut links = Links::new();
         let link = "https://example.com";
         let key = "key";
 
         let entry = links.add_named(key.to_string(), link.to_string()).unwrap();
 
         assert_eq!(links.forward_map.len(), 1);
-        assert_eq!(links.reverse_map.len(), 1);
-        assert_eq!(links.reverse_map.get(&entry.link).unwrap().len(), 1);
-        assert_eq!(links.reverse_map.get(&entry.link).unwrap()[0], key);
+        assert_eq!(links.reverse_map.as_ref().unwrap().len(), 1);
+        assert_eq!(links.reverse_map.as_ref().unwrap().get(&entry.link).unwrap().len(), 1);
+        assert_eq!(links.reverse_map.as_ref().unwrap().get(&entry.link).unwrap()[0], key);
     }
 
     #[test]
     fn add_named_key_collision() {
-        let mut links = Links { forward_map: HashMap::new(), reverse_map: HashMap::new() };
+        let mut links = Links::new();
         let link = "https://example.com";
         let key = "key";        
         links.add_named(key.to_string(), link.to_string()).unwrap();
@@ -316,22 +2114,22 @@ mod tests {
 
     #[test]
     fn add_named_link_collision() {
-        let mut links = Links { forward_map: HashMap::new(), reverse_map: HashMap::new() };
+        let mut links = Links::new();
         let link = "https://example.com";
         let key1 = "key1";
         let key2 = "key2";
         let entry = links.add_named(key1.to_string(), link.to_string()).unwrap();        
         
         assert!(links.add_named(key2.to_string(), link.to_string()).is_ok());
-        assert_eq!(links.reverse_map.get(&entry.link).unwrap().len(), 2);
+        assert_eq!(links.reverse_map.as_ref().unwrap().get(&entry.link).unwrap().len(), 2);
 
-        assert!(links.reverse_map.get(&entry.link).unwrap().contains(&key1.to_string()));
-        assert!(links.reverse_map.get(&entry.link).unwrap().contains(&key2.to_string()));
+        assert!(links.reverse_map.as_ref().unwrap().get(&entry.link).unwrap().contains(&key1.to_string()));
+        assert!(links.reverse_map.as_ref().unwrap().get(&entry.link).unwrap().contains(&key2.to_string()));
     }
 
     #[test]
     fn remove() {
-        let mut links = Links { forward_map: HashMap::new(), reverse_map: HashMap::new() };
+        let mut links = Links::new();
         let link = "https://example.com";
         let key = "key";
         
@@ -340,12 +2138,222 @@ mod tests {
 
         assert_eq!(removed.link, entry.link);
         assert_eq!(links.forward_map.len(), 0);
-        assert_eq!(links.reverse_map.len(), 0);
+        assert_eq!(links.reverse_map.as_ref().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn rotate() {
+        let mut links = Links::new();
+        links.add_named("key1".to_string(), "https://example.com".to_string()).unwrap();
+        links.get_mut("key1").unwrap().metadata.used = 5;
+
+        let new_key = links.rotate("key1").unwrap().unwrap();
+        assert_ne!(new_key, "key1");
+        assert!(links.get("key1").is_none());
+        assert_eq!(links.get(&new_key).unwrap().metadata.used, 5);
+        assert_eq!(links.find_by_link("https://example.com").unwrap(), [new_key]);
+
+        assert!(links.rotate("key1").unwrap().is_none());
+    }
+
+    #[test]
+    fn replace_all() {
+        let mut links = Links::new();
+        links.set_case_insensitive(true);
+        links.add_named("old".to_string(), "https://example.com/old".to_string()).unwrap();
+
+        let entries = [
+            ("Docs".to_string(), Entry::from("https://example.com/docs".to_string())),
+            ("home".to_string(), Entry::from("https://example.com".to_string())),
+        ];
+        links.replace_all(entries.clone()).unwrap();
+        assert!(links.get("old").is_none());
+        assert!(links.find_by_link("https://example.com/old").is_none());
+        assert_eq!(links.get("docs").unwrap().link, "https://example.com/docs");
+        assert_eq!(links.find_by_link("https://example.com").unwrap(), ["home"]);
+
+        // Keys only differing in case collide
+        let duplicate = [entries[0].clone(), ("DOCS".to_string(), entries[1].1.clone())];
+        assert!(links.replace_all(duplicate).is_err());
+        assert!(links.get("home").is_some());
+    }
+
+    #[test]
+    fn entry_accessors() {
+        let mut entry: Entry = Entry::new("https://example.com".to_string());
+        entry.tags.insert("docs".to_string());
+        assert_eq!(entry.link(), "https://example.com");
+        assert_eq!(entry.metadata().used(), 0);
+        assert_eq!(entry.tags().len(), 1);
+        assert!(!entry.is_password_protected());
+        entry.set_password("hunter2").unwrap();
+        assert!(entry.is_password_protected());
+    }
+
+    #[test]
+    fn clear() {
+        let mut links = Links::new();
+        links.set_case_insensitive(true);
+        links.add_named("Docs".to_string(), "https://example.com/docs".to_string()).unwrap();
+        links.add_named("home".to_string(), "https://example.com".to_string()).unwrap();
+
+        links.clear();
+        assert!(links.is_empty());
+        assert!(links.get("docs").is_none());
+        assert!(links.find_by_link("https://example.com").is_none());
+
+        // The key of a cleared link is free again, regardless of case
+        links.add_named("DOCS".to_string(), "https://example.org".to_string()).unwrap();
+        assert_eq!(links.get("docs").unwrap().link, "https://example.org");
+    }
+
+    #[test]
+    fn merge() {
+        let mut links = Links::new();
+        links.add_named("from".to_string(), "https://Example.com/docs/".to_string()).unwrap();
+        links.add_named("into".to_string(), "https://example.com/docs".to_string()).unwrap();
+        links.add_named("other".to_string(), "https://example.com/other".to_string()).unwrap();
+        let earlier = Utc::now() - chrono::Duration::days(1);
+        let later = Utc::now() + chrono::Duration::days(1);
+        let from = links.get_mut("from").unwrap();
+        from.metadata.used = 3;
+        from.metadata.created = earlier;
+        let into = links.get_mut("into").unwrap();
+        into.metadata.used = 2;
+        into.metadata.last_used = later;
+
+        assert!(links.merge("from", "other", TrailingSlash::Remove).is_err());
+        assert!(links.merge("from", "missing", TrailingSlash::Remove).is_err());
+        assert!(links.merge("from", "from", TrailingSlash::Remove).is_err());
+        assert!(links.merge("from", "into", TrailingSlash::Keep).is_err());
+        links.get_mut("from").unwrap().protected = true;
+        assert!(links.merge("from", "into", TrailingSlash::Remove).is_err());
+        links.get_mut("from").unwrap().protected = false;
+
+        links.merge("from", "into", TrailingSlash::Remove).unwrap();
+        assert!(links.get("from").is_none());
+        assert!(links.find_by_link("https://Example.com/docs/").is_none());
+        let into = links.get("into").unwrap();
+        assert_eq!(into.metadata.used, 5);
+        assert_eq!(into.metadata.created, earlier);
+        assert_eq!(into.metadata.last_used, later);
+    }
+
+    #[test]
+    fn make_primary() {
+        let link = "https://example.com";
+        let mut links = Links::new();
+        for key in ["key1", "key2", "key3"] {
+            links.add_named(key.to_string(), link.to_string()).unwrap();
+        }
+        assert!(links.make_primary("missing").is_err());
+
+        links.make_primary("key2").unwrap();
+        assert_eq!(links.find_by_link(link).unwrap(), ["key2", "key1", "key3"]);
+        links.make_primary("key3").unwrap();
+        assert_eq!(links.find_by_link(link).unwrap(), ["key3", "key2", "key1"]);
+        assert!(links.get("key3").unwrap().primary);
+        assert!(!links.get("key2").unwrap().primary);
+
+        // Adding the link again returns the primary key, and copies aren't primary
+        assert_eq!(links.add(link.to_string()).unwrap().into_parts().0, "key3");
+        let copy = links.copy("key3", None).unwrap();
+        assert!(!links.get(&copy).unwrap().primary);
+        assert_eq!(links.find_by_link(link).unwrap()[0], "key3");
+
+        let tmp_file = temp_dir().join("landmower_test_primary.toml");
+        links.save(&tmp_file).unwrap();
+        let loaded: Links = Links::load(&tmp_file).unwrap();
+        assert_eq!(loaded.find_by_link(link).unwrap()[0], "key3");
+        std::fs::remove_file(&tmp_file).unwrap();
+    }
+
+    #[test]
+    fn verify() {
+        let mut links = Links::new();
+        links.add_named("key1".to_string(), "https://example.com".to_string()).unwrap();
+        links.add_named("key2".to_string(), "https://example.com".to_string()).unwrap();
+        links.make_primary("key2").unwrap();
+        assert!(links.verify().is_empty());
+
+        links.reverse_map.as_mut().unwrap().get_mut("https://example.com").unwrap().retain(|k| k != "key1");
+        links.reverse_map.as_mut().unwrap().insert("https://example.com/other".to_string(), vec!["key2".to_string()]);
+        assert_eq!(links.verify().len(), 2, "{:?}", links.verify());
+    }
+
+    #[test]
+    fn len_and_contains_key() {
+        let mut links = Links::new();
+        assert!(links.is_empty());
+        assert_eq!(links.len(), 0);
+
+        links.add_named("Docs".to_string(), "https://example.com/docs".to_string()).unwrap();
+        links.add_named("home".to_string(), "https://example.com".to_string()).unwrap();
+        assert!(!links.is_empty());
+        assert_eq!(links.len(), 2);
+        assert!(links.contains_key("Docs"));
+        assert!(!links.contains_key("docs"));
+
+        links.set_case_insensitive(true);
+        assert!(links.contains_key("docs"));
+
+        links.remove("home");
+        assert_eq!(links.len(), 1);
+        assert!(!links.contains_key("home"));
+    }
+
+    #[test]
+    fn purge_expired() {
+        let now = Utc::now();
+        let mut links = Links::new();
+        for (key, on_expire, expires_at) in [
+            ("deleted", OnExpire::Delete, Some(now - chrono::Duration::minutes(1))),
+            ("pending", OnExpire::Delete, Some(now + chrono::Duration::minutes(1))),
+            ("gone", OnExpire::Gone, Some(now - chrono::Duration::minutes(1))),
+            ("forever", OnExpire::Delete, None),
+            ("protected", OnExpire::Delete, Some(now - chrono::Duration::minutes(1))),
+        ] {
+            links.add_named(key.to_string(), format!("https://example.com/{key}")).unwrap();
+            let entry = links.get_mut(key).unwrap();
+            entry.on_expire = on_expire;
+            entry.expires_at = expires_at;
+            entry.protected = key == "protected";
+        }
+
+        let purged = links.purge_expired(now);
+        assert_eq!(purged.len(), 1);
+        assert_eq!(purged[0].0, "deleted");
+        assert!(links.get("deleted").is_none());
+        assert!(links.find_by_link("https://example.com/deleted").is_none());
+        assert!(links.get("pending").is_some());
+        assert!(links.get("gone").is_some());
+        assert!(links.get("forever").is_some());
+        assert!(links.get("gone").unwrap().is_expired(now));
+        assert!(links.get("protected").is_some());
+    }
+
+    #[test]
+    fn copy() {
+        let mut links = Links::new();
+        links.add_named("key1".to_string(), "https://example.com".to_string()).unwrap();
+        links.get_mut("key1").unwrap().metadata.used = 5;
+
+        let new_key = links.copy("key1", None).unwrap();
+        assert_ne!(new_key, "key1");
+        assert_eq!(links.get("key1").unwrap().metadata.used, 5);
+        assert_eq!(links.get(&new_key).unwrap().metadata.used, 0);
+        assert_eq!(links.get(&new_key).unwrap().link, "https://example.com");
+
+        assert_eq!(links.copy("key1", Some("key2".to_string())).unwrap(), "key2");
+        assert_eq!(links.find_by_link("https://example.com").unwrap(), ["key1".to_string(), new_key, "key2".to_string()]);
+
+        assert!(links.copy("key1", Some("key2".to_string())).is_err());
+        assert!(links.copy("nope", None).is_err());
     }
 
     #[test]
     fn remove_nonexistent() {
-        let mut links = Links { forward_map: HashMap::new(), reverse_map: HashMap::new() };
+        let mut links = Links::new();
         let link = "https://example.com";
         let key = "key";
 
@@ -357,7 +2365,7 @@ mod tests {
 
     #[test]
     fn find_by_link() {
-        let mut links = Links { forward_map: HashMap::new(), reverse_map: HashMap::new() };
+        let mut links = Links::new();
         let link = "https://example.com";
         let key1 = "key1";
         let key2 = "key2";
@@ -374,9 +2382,224 @@ mod tests {
         assert!(result.contains(&key2.to_string()));
     }
 
+    #[test]
+    fn find_by_link_without_reverse_lookup() {
+        let link = "https://example.com";
+        for enabled in [true, false] {
+            let mut links = Links::new();
+            links.set_reverse_lookup(enabled);
+            links.add_named("key2".to_string(), link.to_string()).unwrap();
+            links.add_named("key1".to_string(), link.to_string()).unwrap();
+            links.add_named("other".to_string(), "https://example.com/other".to_string()).unwrap();
+
+            let mut result = links.find_by_link(link).unwrap();
+            result.sort();
+            assert_eq!(result, ["key1", "key2"]);
+            assert!(links.find_by_link("https://example.com/missing").is_none());
+
+            links.make_primary("key2").unwrap();
+            assert_eq!(links.find_by_link(link).unwrap()[0], "key2");
+            assert_eq!(links.add(link.to_string()).unwrap().into_parts().0, "key2");
+
+            links.remove("key2");
+            assert_eq!(links.find_by_link(link).unwrap(), ["key1"]);
+            links.remove("key1");
+            assert!(links.find_by_link(link).is_none());
+            assert!(links.verify().is_empty(), "{:?}", links.verify());
+        }
+
+        // Enabling it again rebuilds the index
+        let mut links = Links::new();
+        links.set_reverse_lookup(false);
+        links.add_named("key1".to_string(), link.to_string()).unwrap();
+        links.set_reverse_lookup(true);
+        assert_eq!(links.find_by_link(link).unwrap(), ["key1"]);
+    }
+
+    #[test]
+    fn percent_decode_round_trip() {
+        assert_eq!(percent_decode(&percent_encode("café menu")).as_deref(), Some("café menu"));
+        assert_eq!(percent_decode("docs").as_deref(), Some("docs"));
+        assert_eq!(percent_decode("bad%2"), None);
+        assert_eq!(percent_decode("%+1"), None);
+        assert_eq!(percent_decode("%FF"), None);
+    }
+
+    #[test]
+    fn normalize_link_equivalent_variants() {
+        let variants = [
+            "https://example.com",
+            "https://example.com/",
+            "HTTPS://Example.COM",
+            "https://example.com:443/",
+        ];
+        for variant in variants {
+            assert_eq!(normalize_link(variant, TrailingSlash::Remove), "https://example.com");
+            assert_eq!(normalize_link(variant, TrailingSlash::Keep), "https://example.com/");
+            assert_eq!(normalize_link(variant, TrailingSlash::Add), "https://example.com/");
+        }
+    }
+
+    #[test]
+    fn normalize_link_trailing_slash() {
+        let link = "http://example.com:80/docs/";
+        assert_eq!(normalize_link(link, TrailingSlash::Remove), "http://example.com/docs");
+        assert_eq!(normalize_link(link, TrailingSlash::Keep), "http://example.com/docs/");
+        assert_eq!(normalize_link("http://example.com/docs", TrailingSlash::Add), "http://example.com/docs/");
+    }
+
+    #[test]
+    fn normalize_link_preserves_other_parts() {
+        assert_eq!(
+            normalize_link("http://User:Pw@Example.com:8080/Path/?Q=1#Frag", TrailingSlash::Remove), 
+            "http://User:Pw@example.com:8080/Path?Q=1#Frag"
+        );
+        assert_eq!(
+            normalize_link("https://[::1]:443/", TrailingSlash::Remove), 
+            "https://[::1]"
+        );
+        assert_eq!(normalize_link("example.com/Path/", TrailingSlash::Remove), "example.com/Path/");
+    }
+
+    #[test]
+    fn password() {
+        let mut entry: Entry = Entry::from("https://example.com".to_string());
+        assert!(entry.verify_password("anything"));
+
+        entry.set_password("hunter2").unwrap();
+
+        assert!(!entry.password.as_ref().unwrap().contains("hunter2"));
+        assert!(entry.verify_password("hunter2"));
+        assert!(!entry.verify_password("hunter3"));
+    }
+
+    #[test]
+    fn case_insensitive_get() {
+        let mut links = Links::new();
+        links.add_named("MyKey".to_string(), "https://example.com".to_string()).unwrap();
+
+        assert!(links.get("mykey").is_none());
+
+        links.set_case_insensitive(true);
+        assert!(links.get("MyKey").is_some());
+        assert!(links.get("mykey").is_some());
+        assert!(links.get("MYKEY").is_some());
+
+        links.set_case_insensitive(false);
+        assert!(links.get("mykey").is_none());
+    }
+
+    #[test]
+    fn case_insensitive_add_named_collision() {
+        let mut links = Links::new();
+        links.set_case_insensitive(true);
+        links.add_named("MyKey".to_string(), "https://example1.com".to_string()).unwrap();
+
+        let result = links.add_named("mykey".to_string(), "https://example2.com".to_string());
+
+        assert!(result.is_err());
+        assert_eq!(links.forward_map.len(), 1);
+        assert!(links.find_by_link("https://example2.com").is_none());
+    }
+
+    #[test]
+    fn case_insensitive_remove() {
+        let mut links = Links::new();
+        links.set_case_insensitive(true);
+        links.add_named("MyKey".to_string(), "https://example.com".to_string()).unwrap();
+
+        assert!(links.remove("MYKEY").is_some());
+        assert!(links.get("MyKey").is_none());
+        assert!(links.add_named("mykey".to_string(), "https://example.com".to_string()).is_ok());
+    }
+
+    #[test]
+    fn case_insensitive_variants() {
+        let mut links = Links::new();
+        links.add_named("Docs".to_string(), "https://example.com/a".to_string()).unwrap();
+        links.add_named("docs".to_string(), "https://example.com/b".to_string()).unwrap();
+        links.add_named("DOCS".to_string(), "https://example.com/c".to_string()).unwrap();
+        links.set_case_insensitive(true);
+        assert_eq!(links.get("dOcS").unwrap().link, "https://example.com/c");
+
+        // Removing a variant the index doesn't point at leaves lookups alone
+        assert!(links.remove("docs").is_some());
+        assert_eq!(links.get("dOcS").unwrap().link, "https://example.com/c");
+
+        // Removing the one it points at hands lookups to the remaining variant
+        assert!(links.remove("DOCS").is_some());
+        assert_eq!(links.get("dOcS").unwrap().link, "https://example.com/a");
+        assert!(links.verify().is_empty());
+
+        assert!(links.remove("Docs").is_some());
+        assert!(links.get("dOcS").is_none());
+    }
+
+    #[test]
+    fn resolve_chain() {
+        let mut links = Links::new();
+        links.add_named("docs".to_string(), "https://example.com/docs".to_string()).unwrap();
+        links.add_named("d".to_string(), "go:docs".to_string()).unwrap();
+        links.add_named("dd".to_string(), "go:d".to_string()).unwrap();
+
+        let (key, entry) = links.resolve("dd").unwrap();
+        assert_eq!(key, "docs");
+        assert_eq!(entry.link, "https://example.com/docs");
+        assert_eq!(links.resolve("docs").unwrap().0, "docs");
+
+        let keys: Vec<_> = links.chain("dd").unwrap().into_iter().map(|(key, _)| key).collect();
+        assert_eq!(keys, ["dd", "d", "docs"]);
+    }
+
+    #[test]
+    fn alias_loaded_from_link() {
+        let path = std::env::temp_dir().join("landmower_test_alias_loaded_from_link.toml");
+        let created = Utc::now().to_rfc3339();
+        let metadata = format!("metadata = {{ used = 0, last_used = {created}, created = {created} }}");
+        // Written before the indirection was stored explicitly
+        std::fs::write(&path, format!("[docs]\nlink = \"https://example.com/docs\"\n{metadata}\n\n[d]\nlink = \"go:docs\"\n{metadata}\n")).unwrap();
+
+        let links = Links::<EntryMetadata>::load(&path).unwrap();
+        assert_eq!(links.get("d").unwrap().alias_of.as_deref(), Some("docs"));
+        assert_eq!(links.get("docs").unwrap().alias_of, None);
+        assert_eq!(links.resolve("d").unwrap().0, "docs");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn resolve_missing_target() {
+        let mut links = Links::new();
+        links.add_named("d".to_string(), "go:docs".to_string()).unwrap();
+
+        assert_eq!(links.resolve("d"), Err(ResolveError::NotFound("docs".to_string())));
+        assert_eq!(links.resolve("x"), Err(ResolveError::NotFound("x".to_string())));
+    }
+
+    #[test]
+    fn resolve_cycle() {
+        let mut links = Links::new();
+        links.add_named("a".to_string(), "go:b".to_string()).unwrap();
+        links.add_named("b".to_string(), "go:a".to_string()).unwrap();
+
+        assert_eq!(links.resolve("a"), Err(ResolveError::Loop));
+    }
+
+    #[test]
+    fn resolve_too_deep() {
+        let mut links = Links::new();
+        links.add_named("k0".to_string(), "https://example.com".to_string()).unwrap();
+        for i in 1..=MAX_CHAIN_DEPTH + 1 {
+            links.add_named(format!("k{i}"), format!("go:k{}", i - 1)).unwrap();
+        }
+
+        assert!(links.resolve(&format!("k{MAX_CHAIN_DEPTH}")).is_ok());
+        assert_eq!(links.resolve(&format!("k{}", MAX_CHAIN_DEPTH + 1)), Err(ResolveError::Loop));
+    }
+
     #[test]
     fn find_by_link_nonexistent() {
-        let links = Links { forward_map: HashMap::new(), reverse_map: HashMap::new() };
+        let links = Links::new();
         let result = links.find_by_link("nonexistent");
 
         assert!(result.is_none());
@@ -1,83 +1,637 @@
-use std::{
-    sync::Arc, 
-    time::Duration
-};
+use std::{net::SocketAddr, time::{Duration, Instant}};
 
 use axum::{
-    body::Body, 
-    extract::{Path, State}, 
-    http::StatusCode, 
-    response::Redirect, 
+    extract::{ConnectInfo, Path, Query, State}, 
+    http::{header, HeaderMap, HeaderValue, StatusCode}, 
+    response::{Html, IntoResponse, Redirect, Response}, 
     routing, 
+    Form,
     Router
 };
 
-use axum_embed::ServeEmbed;
-use minijinja::Environment;
-use rust_embed::Embed;
-use tokio::sync::RwLock;
-use concurrent_queue::ConcurrentQueue;
-use tower_http::trace::TraceLayer;
-use http_body_util::BodyExt;
+#[cfg(feature = "webui")]
+use {
+    axum::body::Body,
+    http_body_util::BodyExt,
+    minijinja::Environment,
+};
+#[cfg(all(feature = "webui", not(webui_placeholder)))]
+use {
+    axum_embed::ServeEmbed,
+    rust_embed::Embed,
+};
+use tower_http::{set_header::SetResponseHeaderLayer, trace::TraceLayer};
+use serde::{Deserialize, Serialize};
 
 use landmower::*;
-use links::Links;
+use api::jsend::Jsend;
+use links::{percent_encode, Links};
 
+#[cfg(all(feature = "webui", not(webui_placeholder)))]
 #[derive(Embed, Clone)]
 #[folder = "static"]
 struct PageAssets;
 
+#[tracing::instrument(skip_all, fields(key = %key, outcome))]
 async fn redirect(
     Path(key): Path<String>, 
-    State(state): State<AppState>
-) -> Result<Redirect, api::HttpError> {
+    State(state): State<AppState>,
+    Query(query): Query<RedirectQuery>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+) -> Result<Response, api::HttpError> {
+    let start = Instant::now();
     let links = state.links.read().await;
-    let mut link = links.get(&key)
-        .ok_or((StatusCode::NOT_FOUND, "Link does not exist.".to_string()))?
-        .link.clone();   
-    
-    if !(link.starts_with("http://") || link.starts_with("https://")) {
-        link = format!("http://{}", link);
+    let lock_wait = start.elapsed();
+    let resolved = resolve(&links, &key);
+    if matches!(resolved, Err((StatusCode::NOT_FOUND, _))) && state.deleted_at(&key).await.is_some() {
+        tracing::Span::current().record("outcome", "deleted");
+        let message = state.config.deleted_message.clone().unwrap_or("Link has been deleted.".to_string());
+        return Ok((StatusCode::GONE, message).into_response());
+    }
+    let (hops, entry) = match resolved {
+        Err((StatusCode::NOT_FOUND, _)) if links.is_empty() && state.config.empty_store != EmptyStore::NotFound => {
+            return Ok(empty_store(&state));
+        },
+        Err((StatusCode::NOT_FOUND, _)) if state.config.fallback_redirect.is_some() => {
+            return Ok(fallback_redirect(&state, &key));
+        },
+        res => res?,
+    };
+
+    if let Some(res) = rate_limited(&state, &key) {
+        return Ok(res);
+    }
+
+    if let Some(res) = expired(&hops, state.clock.now()) {
+        return Ok(res);
+    }
+
+    let locked = is_locked(&hops);
+    if query.is_peek() {
+        return Ok(peek(&state, &key, entry, locked, &headers));
+    }
+
+    if locked {
+        tracing::Span::current().record("outcome", "password_required");
+        return Ok(password_page(&key, None).into_response());
+    }
+
+    tracing::Span::current().record("outcome", "redirect");
+    let visitor = visitor(&headers, connect_info.map(|ConnectInfo(addr)| addr));
+    let res = follow(&state, &key, entry, &headers, visitor);
+
+    // Lock contention with the metadata worker shows up here first.
+    let elapsed = start.elapsed();
+    if elapsed > state.config.slow_redirect_threshold {
+        tracing::warn!(?elapsed, ?lock_wait, "Slow redirect");
+    } else {
+        tracing::debug!(?elapsed, ?lock_wait, "Redirect timing");
+    }
+    Ok(res)
+}
+
+#[derive(Deserialize)]
+struct RedirectQuery {
+    peek: Option<String>,
+}
+
+impl RedirectQuery {
+    /// Whether `peek` is given with any value other than `0` or `false`.
+    fn is_peek(&self) -> bool {
+        self.peek.as_deref().is_some_and(|v| v != "0" && !v.eq_ignore_ascii_case("false"))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct PeekResponse {
+    key: String,
+    target: String,
+}
+
+/// Return the destination of an entry as JSON instead of redirecting to it.
+/// 
+/// Peeking never counts as a click, so `used` is not incremented. The destination of
+/// password-protected links is not revealed.
+fn peek(state: &AppState, key: &str, entry: &links::Entry, locked: bool, headers: &HeaderMap) -> Response {
+    if locked {
+        tracing::Span::current().record("outcome", "password_required");
+        let res = Jsend::<PeekResponse, _>::Fail("Link is password protected".to_string());
+        return res.with_status(StatusCode::FORBIDDEN).into_response();
+    }
+
+    tracing::Span::current().record("outcome", "peek");
+    let res = Jsend::<_, String>::Success(PeekResponse {
+        key: key.to_string(),
+        target: destination(state, entry, headers),
+    });
+    if entry.alt_target.is_some() {
+        ([(header::VARY, "Accept")], res).into_response()
+    } else {
+        res.into_response()
+    }
+}
+
+/// Pick the destination of an entry based on the `Accept` header of the request.
+/// 
+/// Precedence:
+/// 1. Entries without an alternative target always go to their link.
+/// 2. Clients that accept `text/html`, i.e. browsers, go to the link.
+/// 3. All other clients go to the alternative target. This includes clients that send no
+///    `Accept` header or only `*/*`, like `curl`.
+fn negotiate_target<'a>(entry: &'a links::Entry, headers: &HeaderMap) -> &'a str {
+    match &entry.alt_target {
+        Some(alt_target) if !accepts_html(headers) => alt_target,
+        _ => &entry.link
+    }
+}
+
+/// Whether the `Accept` header explicitly lists `text/html` with a non-zero quality.
+fn accepts_html(headers: &HeaderMap) -> bool {
+    headers.get_all(header::ACCEPT).iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|range| {
+            let mut params = range.split(';').map(str::trim);
+            let media_type = params.next().unwrap_or_default();
+            let refused = params.any(|p| p.strip_prefix("q=")
+                .and_then(|q| q.parse::<f32>().ok())
+                .is_some_and(|q| q == 0.0));
+            media_type.eq_ignore_ascii_case("text/html") && !refused
+        })
+}
+
+/// Entries along the chain of a key, and the one holding its external link.
+type Resolved<'a> = (Vec<&'a links::Entry>, &'a links::Entry);
+
+/// Resolve a key to the entry holding its external link, following internal links.
+fn resolve<'a>(links: &'a Links, key: &'a str) -> Result<Resolved<'a>, api::HttpError> {
+    match links.chain(key) {
+        Ok(chain) => {
+            let hops: Vec<_> = chain.into_iter().map(|(_, entry)| entry).collect();
+            let entry = hops[hops.len() - 1];
+            Ok((hops, entry))
+        },
+        Err(links::ResolveError::NotFound(_)) => {
+            tracing::Span::current().record("outcome", "not_found");
+            Err((StatusCode::NOT_FOUND, "Link does not exist.".to_string()))
+        },
+        Err(links::ResolveError::Loop) => {
+            tracing::Span::current().record("outcome", "loop");
+            Err((StatusCode::LOOP_DETECTED, "Link redirects in a loop.".to_string()))
+        }
+    }
+}
+
+/// Whether following a chain requires a password, i.e. any entry along it has one.
+fn is_locked(hops: &[&links::Entry]) -> bool {
+    hops.iter().any(|entry| entry.password.is_some())
+}
+
+/// Respond with `429 Too Many Requests` if the key has been followed too often recently.
+fn rate_limited(state: &AppState, key: &str) -> Option<Response> {
+    let limiter = state.key_rate_limiter.as_ref()?;
+    let key = if state.config.case_insensitive_keys { key.to_lowercase() } else { key.to_string() };
+    if limiter.check(&key, Instant::now()) {
+        return None;
+    }
+    tracing::Span::current().record("outcome", "rate_limited");
+    let retry_after = limiter.window().as_secs().max(1).to_string();
+    Some((StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, retry_after)], "Too many requests for this link.").into_response())
+}
+
+/// Respond according to the expiry action of the first entry along a chain that has expired
+/// by `now`, if any.
+fn expired(hops: &[&links::Entry], now: chrono::DateTime<chrono::Utc>) -> Option<Response> {
+    let expired = hops.iter().find(|e| e.is_expired(now))?;
+
+    match &expired.on_expire {
+        links::OnExpire::Redirect(url) => {
+            tracing::Span::current().record("outcome", "expired_redirect");
+            Some((StatusCode::FOUND, [(header::LOCATION, url.clone())]).into_response())
+        },
+        links::OnExpire::Gone | links::OnExpire::Delete => {
+            tracing::Span::current().record("outcome", "expired");
+            Some((StatusCode::GONE, "Link has expired.").into_response())
+        }
     }
+}
+
+/// Redirect an unknown key to the configured fallback URL, with `{key}` replaced by the
+/// percent-encoded key.
+fn fallback_redirect(state: &AppState, key: &str) -> Response {
+    tracing::Span::current().record("outcome", "fallback");
+    let url = state.config.fallback_redirect.as_deref().unwrap_or("/")
+        .replace("{key}", &percent_encode(key));
+    (StatusCode::FOUND, [(header::LOCATION, url)]).into_response()
+}
+
+/// Respond to a redirect while no links exist yet, as configured.
+fn empty_store(state: &AppState) -> Response {
+    tracing::Span::current().record("outcome", "empty_store");
+    match &state.config.empty_store {
+        EmptyStore::Redirect(url) => (StatusCode::FOUND, [(header::LOCATION, url.clone())]).into_response(),
+        // The key doesn't exist either way, so the setup page keeps the 404 status.
+        EmptyStore::Setup => (StatusCode::NOT_FOUND, setup_page()).into_response(),
+        EmptyStore::NotFound => (StatusCode::NOT_FOUND, "Link does not exist.").into_response(),
+    }
+}
+
+/// Follow a random link, counting the click on the chosen key.
+/// 
+/// Only links that can be followed without further ado are picked, i.e. ones whose chain
+/// resolves without any expired or password-protected entry along it.
+#[tracing::instrument(skip_all, fields(key, outcome))]
+async fn random_redirect(
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+) -> Result<Response, api::HttpError> {
+    let links = state.links.read().await;
+    let now = state.clock.now();
+    let followable = |key: &str, _: &links::Entry| links.chain(key).is_ok_and(|chain| {
+        chain.iter().all(|(_, e)| !e.is_expired(now) && e.password.is_none())
+    });
+    let Some((key, _)) = links.random(followable) else {
+        tracing::Span::current().record("outcome", "not_found");
+        return Err((StatusCode::NOT_FOUND, "No links to pick from.".to_string()));
+    };
+    tracing::Span::current().record("key", key);
+    let (_, entry) = resolve(&links, key)?;
+
+    tracing::Span::current().record("outcome", "redirect");
+    let visitor = visitor(&headers, connect_info.map(|ConnectInfo(addr)| addr));
+    Ok(follow(&state, key, entry, &headers, visitor))
+}
+
+/// List all keys the caller may access, sorted, for clients browsing the redirect namespace.
+async fn go_index(State(state): State<AppState>, caller: auth::Caller) -> Jsend<Vec<String>, ()> {
+    let mut keys: Vec<_> = state.links.read().await.iter()
+        .filter(|(_, entry)| caller.may_access(&entry.acl))
+        .map(|(key, _)| key.clone())
+        .collect();
+    keys.sort_unstable();
+    Jsend::Success(keys)
+}
 
-    let req = LinkAccessEvent {
-        key: key.clone(),
-        timestamp: std::time::SystemTime::now()
+/// Redirect the root to the configured URL instead of serving the web UI.
+async fn root_redirect(State(state): State<AppState>) -> Response {
+    let url = state.config.root_redirect.as_deref().unwrap_or("/");
+    (StatusCode::FOUND, [(header::LOCATION, url)]).into_response()
+}
+
+#[derive(Deserialize)]
+struct UnlockForm {
+    password: String,
+}
+
+/// Follow a password-protected link after checking the submitted password against every
+/// entry along its chain that has one.
+#[tracing::instrument(skip_all, fields(key = %key, outcome))]
+async fn unlock(
+    Path(key): Path<String>, 
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Form(form): Form<UnlockForm>,
+) -> Result<Response, api::HttpError> {
+    let (locks, entry) = {
+        let links = state.links.read().await;
+        let (hops, entry) = resolve(&links, &key)?;
+        if let Some(res) = expired(&hops, state.clock.now()) {
+            return Ok(res);
+        }
+        let locks: Vec<_> = hops.into_iter()
+            .filter(|hop| hop.password.is_some())
+            .cloned()
+            .collect();
+        (locks, entry.clone())
     };
 
-    if let Err(e) = state.access_event_queue.push(req) {
-        eprintln!("Failed to push update request for link '{}': {:?}",  key.as_str(), e);
+    // Hashing is deliberately slow; keep it off the async workers.
+    let valid = tokio::task::spawn_blocking(move || {
+        locks.iter().all(|lock| lock.verify_password(&form.password))
+    })
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if !valid {
+        tracing::Span::current().record("outcome", "wrong_password");
+        let page = password_page(&key, Some("Incorrect password."));
+        return Ok((StatusCode::UNAUTHORIZED, page).into_response());
+    }
+
+    tracing::Span::current().record("outcome", "redirect");
+    let visitor = visitor(&headers, connect_info.map(|ConnectInfo(addr)| addr));
+    Ok(follow(&state, &key, &entry, &headers, visitor))
+}
+
+/// Identify whoever follows a link, for estimating unique visitors.
+/// 
+/// The client address is taken from the first `X-Forwarded-For` entry if present, so clients
+/// behind a reverse proxy are told apart. Spoofing it only skews the estimate. Clients without
+/// a known address, e.g. on a Unix socket without a proxy header, aren't counted.
+fn visitor(headers: &HeaderMap, addr: Option<SocketAddr>) -> Option<u64> {
+    let forwarded = headers.get("x-forwarded-for")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(',').next())
+        .map(|ip| ip.trim().to_string())
+        .filter(|ip| !ip.is_empty());
+    let ip = forwarded.or_else(|| addr.map(|addr| addr.ip().to_string()))?;
+    let user_agent = headers.get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    Some(visitors::visitor_hash(&ip, user_agent))
+}
+
+/// Count a click on the given key and redirect to the destination of its entry.
+fn follow(
+    state: &AppState,
+    key: &str,
+    entry: &links::Entry,
+    headers: &HeaderMap,
+    visitor: Option<u64>,
+) -> Response {
+    let link = destination(state, entry, headers);
+
+    if !state.config.disable_analytics {
+        let req = LinkAccessEvent {
+            key: key.to_string(),
+            timestamp: state.clock.now().into(),
+            visitor,
+        };
+
+        if let Err(e) = state.access_event_queue.push(req) {
+            eprintln!("Failed to push update request for link '{}': {:?}", key, e);
+        }
+    }
+    state.redirects_served.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+
+    let mut res = Redirect::to(&link).into_response();
+    if entry.alt_target.is_some() {
+        // The destination depends on the `Accept` header, so caches must not mix them up.
+        res.headers_mut().insert(header::VARY, HeaderValue::from_static("Accept"));
+    }
+    if state.config.link_headers {
+        insert_link_headers(res.headers_mut(), key, entry);
+    }
+    res
+}
+
+/// Describe the followed link in `X-Landmower-*` headers. Uses don't include the current
+/// click, since it hasn't been applied yet.
+fn insert_link_headers(headers: &mut HeaderMap, key: &str, entry: &links::Entry) {
+    let values = [
+        ("x-landmower-key", percent_encode(key)),
+        ("x-landmower-created", entry.metadata.created.to_rfc3339()),
+        ("x-landmower-uses", entry.metadata.used.to_string()),
+    ];
+    for (name, value) in values {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(name, value);
+        }
     }
+}
+
+/// The absolute URL an entry redirects to for the given request headers.
+fn destination(state: &AppState, entry: &links::Entry, headers: &HeaderMap) -> String {
+    links::with_default_scheme(negotiate_target(entry, headers), &state.config.default_scheme)
+}
+
+fn password_page(key: &str, error: Option<&str>) -> Html<String> {
+    let error = error
+        .map(|e| format!("<p class=\"error\">{}</p>", html_escape(e)))
+        .unwrap_or_default();
 
-    Ok(Redirect::to(&link))
+    Html(format!(r#"<!doctype html>
+<html lang="en">
+  <head>
+    <meta charset="UTF-8" />
+    <meta name="viewport" content="width=device-width, initial-scale=1" />
+    <title>Password required</title>
+    <style>
+      body {{ font-family: sans-serif; display: flex; justify-content: center; margin-top: 20vh; }}
+      form {{ display: flex; flex-direction: column; gap: 0.5rem; }}
+      .error {{ color: #c00; }}
+    </style>
+  </head>
+  <body>
+    <form method="post" action="/go/{key}">
+      <label for="password">This link is password protected.</label>
+      <input id="password" name="password" type="password" autofocus required />
+      {error}
+      <button type="submit">Continue</button>
+    </form>
+  </body>
+</html>"#, key = html_escape(key)))
 }
 
+fn setup_page() -> Html<&'static str> {
+    Html(r#"<!doctype html>
+<html lang="en">
+  <head>
+    <meta charset="UTF-8" />
+    <meta name="viewport" content="width=device-width, initial-scale=1" />
+    <title>No links yet</title>
+    <style>
+      body { font-family: sans-serif; display: flex; justify-content: center; margin-top: 20vh; }
+      main { max-width: 30rem; }
+    </style>
+  </head>
+  <body>
+    <main>
+      <h1>No links yet</h1>
+      <p>This landmower instance doesn't have any links. Add the first one in the
+        <a href="/">web UI</a> or with <code>POST /api/links</code>, and it will be
+        available here.</p>
+    </main>
+  </body>
+</html>"#)
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Poll interval of the metadata worker after it applied events.
+const WORKER_MIN_INTERVAL: Duration = Duration::from_millis(50);
+/// Longest poll interval of the metadata worker while idle.
+const WORKER_MAX_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Interval at which the metadata worker removes expired links set to be deleted.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60);
+
 async fn metadata_update_worker(state: AppState) {
+    let mut interval = WORKER_MIN_INTERVAL;
+    let mut last_purge = Instant::now();
+    let mut last_autosave = Instant::now();
     loop {
-        if !state.access_event_queue.is_empty() {
-            let mut links = state.links.write().await;
-            while let Ok(el) = state.access_event_queue.pop() {
-                let link = links.get_mut(&el.key).unwrap();
-                link.metadata.used += 1;
-                link.metadata.last_used = link.metadata.last_used.max(
-                    chrono::DateTime::from(el.timestamp)
-                );
+        if last_purge.elapsed() >= PURGE_INTERVAL {
+            purge_expired_links(&state).await;
+            state.purge_tombstones().await;
+            if let Some(limiter) = &state.key_rate_limiter {
+                limiter.prune(Instant::now());
+            }
+            last_purge = Instant::now();
+        }
+        if state.config.autosave_interval.is_some_and(|interval| last_autosave.elapsed() >= interval) {
+            if let Err(e) = state.save_links_if_changed().await {
+                tracing::error!("Failed to auto-save links: {e}");
+            }
+            last_autosave = Instant::now();
+        }
+
+        // Drain the queue in batches, releasing the lock in between so redirects can proceed.
+        let mut busy = false;
+        while apply_access_events(&state).await > 0 {
+            busy = true;
+            tokio::task::yield_now().await;
+        }
+        interval = next_poll_interval(interval, busy);
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Poll again soon after applying events, and back off exponentially while idle to avoid
+/// needless wakeups on quiet instances.
+fn next_poll_interval(current: Duration, busy: bool) -> Duration {
+    if busy {
+        WORKER_MIN_INTERVAL
+    } else {
+        (current * 2).min(WORKER_MAX_INTERVAL)
+    }
+}
+
+/// Remove expired links whose expiry action is to delete them.
+/// 
+/// Returns the number of links removed.
+async fn purge_expired_links(state: &AppState) -> usize {
+    let mut links = state.links.write().await;
+    let purged = links.purge_expired(state.clock.now());
+    if purged.is_empty() {
+        return 0;
+    }
+    if let Err(e) = state.save_links(links).await {
+        tracing::error!("Failed to save links after purging expired ones: {e}");
+    }
+    for (key, entry) in &purged {
+        tracing::info!("Deleted expired link '{key}'");
+        state.bury(key).await;
+        webhook::notify(state, webhook::Event::new(state, webhook::EventKind::Deleted, key, &entry.link));
+    }
+    purged.len()
+}
+
+/// Apply up to `worker_batch_size` queued access events to the link metadata.
+/// 
+/// Returns the number of events applied.
+async fn apply_access_events(state: &AppState) -> usize {
+    if state.access_event_queue.is_empty() {
+        return 0;
+    }
+
+    let mut applied = 0;
+    let mut logged = Vec::new();
+    let tailed = state.access_tail.receiver_count() > 0;
+    {
+        let mut links = state.links.write().await;
+        let base_revision = links.revision();
+        let mut changed = Vec::new();
+        while applied < state.config.worker_batch_size {
+            let Ok(el) = state.access_event_queue.pop() else {
+                break;
+            };
+            applied += 1;
+            if state.config.event_log_path.is_some() || tailed {
+                logged.push(event_log::LoggedAccess::from(&el));
+            }
+            // The link may have been removed since it was accessed.
+            let Some(link) = links.get_mut(&el.key) else {
+                continue;
+            };
+            link.metadata.used += 1;
+            link.metadata.last_used = link.metadata.last_used.max(
+                chrono::DateTime::from(el.timestamp)
+            );
+            if let Some(visitor) = el.visitor {
+                link.visitors.get_or_insert_with(visitors::VisitorSketch::new).add(visitor);
+            }
+            changed.push((el.key, link.metadata.used));
+        }
+        // Redirects may proceed while the snapshot of counters catches up.
+        let links = links.downgrade();
+        state.update_link_counts(&links, base_revision, &changed);
+    }
+    // Written after releasing the lock and off the async workers, so slow disks don't hold
+    // up redirects.
+    if let Some(path) = state.config.event_log_path.clone() {
+        let written = tokio::task::spawn_blocking(move || {
+            let res = event_log::append(&path, &logged);
+            (res, logged)
+        }).await;
+        logged = match written {
+            Ok((res, logged)) => {
+                if let Err(e) = res {
+                    tracing::error!("Failed to write access log: {e}");
+                }
+                logged
+            },
+            Err(e) => {
+                tracing::error!("Failed to write access log: {e}");
+                return applied;
             }
+        };
+        let mut daily_clicks = state.daily_clicks.write().await;
+        for access in &logged {
+            daily_clicks.record(access);
+        }
+    }
+    if tailed {
+        for access in logged {
+            // Fails only if the last client disconnected in the meantime.
+            let _ = state.access_tail.send(access);
         }
-        tokio::time::sleep(Duration::from_millis(200)).await;
     }
+    applied
 }
 
+/// Render the server configuration into HTML and JavaScript assets.
+/// 
+/// Templated responses depend on the configuration rather than just the embedded file, so
+/// their validators are dropped and they are marked `no-cache`. All other assets keep the
+/// `ETag` and `Last-Modified` headers of the embedded file, and conditional requests using
+/// `If-Modified-Since` are answered with `304 Not Modified`.
+#[cfg(feature = "webui")]
 async fn inject_environment(
     State(state): State<AppState>,
     req: axum::extract::Request,
     next: axum::middleware::Next
 ) -> axum::response::Response {
+    // `If-None-Match` takes precedence and is already handled by the static service.
+    let if_modified_since = req.headers().get(header::IF_MODIFIED_SINCE)
+        .filter(|_| !req.headers().contains_key(header::IF_NONE_MATCH))
+        .and_then(|v| parse_http_date(v.to_str().ok()?));
+
     let res = next.run(req).await;
-    let (parts, body) = res.into_parts();
-    let content = String::from_utf8(
-        body.collect().await.unwrap().to_bytes().to_vec()
-    ).unwrap();    
+    if !is_template(&res) {
+        return match if_modified_since {
+            Some(since) if !is_modified_since(&res, since) => not_modified(res),
+            _ => res,
+        };
+    }
+
+    let (mut parts, body) = res.into_parts();
+    let content = match body.collect().await.map(|b| String::from_utf8(b.to_bytes().to_vec())) {
+        Ok(Ok(content)) => content,
+        _ => {
+            tracing::error!("Failed to read template");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
     
     let env = Environment::new();
     let replaced = env.render_str(&content, state.config.jinja_context())
@@ -85,41 +639,1288 @@ async fn inject_environment(
         tracing::error!("Failed to render template: {:?}", e);
         content
     });    
-    
+
+    parts.headers.remove(header::ETAG);
+    parts.headers.remove(header::LAST_MODIFIED);
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(header::CACHE_CONTROL, header::HeaderValue::from_static("no-cache"));
     axum::http::Response::from_parts(parts, Body::from(replaced))
 }
 
+/// Whether a response from the static service goes through templating.
+#[cfg(feature = "webui")]
+fn is_template(res: &Response) -> bool {
+    let content_type = res.headers().get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+
+    res.status() == StatusCode::OK
+        && !res.headers().contains_key(header::CONTENT_ENCODING)
+        && (content_type.starts_with("text/html") || content_type.contains("javascript"))
+}
+
+#[cfg(feature = "webui")]
+fn is_modified_since(res: &Response, since: chrono::DateTime<chrono::Utc>) -> bool {
+    let last_modified = res.headers().get(header::LAST_MODIFIED)
+        .and_then(|v| parse_http_date(v.to_str().ok()?));
+
+    match last_modified {
+        Some(last_modified) => res.status() != StatusCode::OK || last_modified > since,
+        None => true,
+    }
+}
+
+#[cfg(feature = "webui")]
+fn not_modified(res: Response) -> Response {
+    let mut not_modified = StatusCode::NOT_MODIFIED.into_response();
+    for name in [header::ETAG, header::LAST_MODIFIED, header::CACHE_CONTROL] {
+        if let Some(value) = res.headers().get(&name) {
+            not_modified.headers_mut().insert(name, value.clone());
+        }
+    }
+    not_modified
+}
+
+#[cfg(feature = "webui")]
+fn parse_http_date(value: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::parse_from_rfc2822(value).ok()
+        .map(|date| date.to_utc())
+}
+
+enum Listener {
+    Tcp(tokio::net::TcpListener),
+    #[cfg(feature = "tls")]
+    Tls(std::net::TcpListener, axum_server::tls_rustls::RustlsConfig),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener, std::path::PathBuf),
+}
+
+#[cfg(feature = "tls")]
+async fn bind_tls(config: &Config) -> Listener {
+    let (Some(cert), Some(key)) = (&config.tls_cert, &config.tls_key) else {
+        unreachable!("TLS listener without certificate and key");
+    };
+    let tls_config = match tls::load_config(cert, key).await {
+        Ok(tls_config) => tls_config,
+        Err(e) => {
+            tracing::error!("Failed to load TLS certificate '{}' and key '{}': {e}", cert.display(), key.display());
+            std::process::exit(1);
+        }
+    };
+    Listener::Tls(std::net::TcpListener::bind(&config.bind_address).unwrap(), tls_config)
+}
+
+#[cfg(not(feature = "tls"))]
+async fn bind_tls(_config: &Config) -> Listener {
+    tracing::error!("A TLS certificate and key are configured, but landmower was built without the `tls` feature");
+    std::process::exit(1);
+}
+
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt()
         .with_max_level(tracing::Level::TRACE)
         .init();
     
-    let config = Arc::new(Config::from_env());
-    let state = AppState { 
-        config: config.clone(),
-        links: RwLock::new(Links::load(&config.link_data_path).unwrap()).into(), 
-        access_event_queue: ConcurrentQueue::unbounded().into()
+    let config = Config::from_env();
+    if std::env::args().nth(1).as_deref() == Some("doctor") {
+        let report = doctor::run(&config);
+        println!("{report}");
+        std::process::exit(if report.is_healthy() { 0 } else { 1 });
+    }
+
+    let links = match Links::load(&config.link_data_path) {
+        Ok(links) => links,
+        Err(e) => {
+            tracing::error!("Failed to load link data: {e}");
+            std::process::exit(1);
+        }
+    };
+    if links::is_in_memory(&config.link_data_path) {
+        tracing::warn!("Links are kept in memory only and will be lost on shutdown");
+    }
+    if config.uses_placeholder_base_url() {
+        tracing::warn!(
+            "Short URLs use the placeholder base URL '{}'; set LANDMOWER_BASE_URL to where clients reach this server",
+            config.server_base_url
+        );
+    }
+    let migrate = !links::is_in_memory(&config.link_data_path)
+        && (links.storage_format() != config.storage_format || links.stats_sidecar() != config.stats_sidecar);
+    let state = AppState::new(config, links);
+    let config = state.config.clone();
+
+    if migrate {
+        match state.links.read().await.save(&config.link_data_path) {
+            Ok(()) => tracing::info!(
+                "Migrated link data to the {:?} storage format (separate statistics: {})",
+                config.storage_format, config.stats_sidecar
+            ),
+            Err(e) => tracing::error!("Failed to migrate link data: {e}"),
+        }
+    }
+
+    let app = app(state.clone());
+    
+    let listener = match config.bind_address.strip_prefix("unix:") {
+        _ if config.tls_cert.is_some() && config.tls_key.is_some() => bind_tls(&config).await,
+        #[cfg(unix)]
+        Some(path) => match unix_socket::bind(path.as_ref()) {
+            Ok(listener) => Listener::Unix(listener, path.into()),
+            Err(e) => {
+                tracing::error!("Failed to bind Unix socket '{path}': {e}");
+                std::process::exit(1);
+            }
+        },
+        #[cfg(not(unix))]
+        Some(_) => panic!("Unix sockets are not supported on this platform"),
+        None => Listener::Tcp(tokio::net::TcpListener::bind(&config.bind_address).await.unwrap()),
+    };
+
+    if let Some(path) = &config.pending_events_path {
+        match event_log::restore_queue(path, &state.access_event_queue) {
+            Ok(0) => {},
+            Ok(n) => tracing::info!("Restored {n} pending access events"),
+            Err(e) => tracing::error!("Failed to restore pending access events: {e}"),
+        }
+    }
+
+    let worker_handle = tokio::task::spawn(metadata_update_worker(state.clone()));
+
+    let shutdown = shutdown_signal();
+    match listener {
+        Listener::Tcp(listener) => axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+            .with_graceful_shutdown(shutdown)
+            .await.unwrap(),
+        #[cfg(feature = "tls")]
+        Listener::Tls(listener, tls_config) => tls::serve(listener, tls_config, app, shutdown)
+            .await.unwrap(),
+        #[cfg(unix)]
+        Listener::Unix(listener, path) => {
+            unix_socket::serve(listener, app, shutdown).await;
+            if let Err(e) = std::fs::remove_file(&path) {
+                tracing::error!("Failed to remove socket '{}': {e}", path.display());
+            }
+        }
+    }
+    worker_handle.abort();
+
+    if let Some(path) = &config.pending_events_path {
+        match event_log::persist_queue(path, &state.access_event_queue) {
+            Ok(0) => {},
+            Ok(n) => tracing::info!("Saved {n} pending access events"),
+            Err(e) => tracing::error!("Failed to save pending access events: {e}"),
+        }
+    }
+    let links = state.links.read().await;
+    if let Err(e) = links.save(&config.link_data_path) {
+        tracing::error!("Failed to save links on shutdown: {e}");
+    }
+}
+
+fn app(state: AppState) -> Router {
+    let config = state.config.clone();
+
+    let mut redirects = Router::new()
+        .route("/go/:key", routing::get(redirect).post(unlock))
+        .route("/go/~random", routing::get(random_redirect));
+    if state.access_log.is_some() {
+        redirects = redirects.route_layer(axum::middleware::from_fn_with_state(state.clone(), log_access));
+    }
+
+    let mut app = Router::new()
+        .nest("/api", api::router(&config))
+        .merge(redirects);
+
+    if config.root_redirect.is_some() {
+        app = app.route("/", routing::get(root_redirect));
+    }
+
+    // Matched before the UI fallback, so `/go/` only reaches the UI by default.
+    match config.go_index {
+        GoIndex::Ui => {},
+        GoIndex::NotFound => app = app.route("/go/", routing::get(not_found)),
+        GoIndex::Json => app = app.route("/go/", routing::get(go_index)),
+    }
+
+    app = if config.serve_ui {
+        serve_ui(app, &state)
+    } else {
+        app.fallback(not_found)
+    };
+
+    if config.security_headers {
+        // Headers set by handlers take precedence.
+        let header = |name, value| SetResponseHeaderLayer::if_not_present(name, HeaderValue::from_static(value));
+        app = app
+            .layer(header(header::X_CONTENT_TYPE_OPTIONS, "nosniff"))
+            .layer(header(header::REFERRER_POLICY, "strict-origin-when-cross-origin"))
+            .layer(header(header::CONTENT_SECURITY_POLICY, CONTENT_SECURITY_POLICY));
+    }
+
+    app.with_state(state)
+        .layer(TraceLayer::new_for_http())
+}
+
+/// Write a redirect request to the access log, see [`access_log`].
+async fn log_access(
+    State(state): State<AppState>,
+    req: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> Response {
+    let Some(log) = state.access_log.clone() else {
+        return next.run(req).await;
+    };
+    let (referrer, user_agent) = {
+        let header = |name| req.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+        (header(header::REFERER), header(header::USER_AGENT))
     };
-        
+    let remote = req.extensions().get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| addr.ip().to_string());
+    let method = req.method().to_string();
+    let target = req.uri().path_and_query().map_or(req.uri().path(), |p| p.as_str()).to_string();
+    let version = format!("{:?}", req.version());
+    let key = req.uri().path().strip_prefix("/go/").and_then(links::percent_decode);
+    let time = state.clock.now();
+
+    let res = next.run(req).await;
+    let access = access_log::Access {
+        remote: remote.as_deref(),
+        time,
+        method: &method,
+        target: &target,
+        version: &version,
+        status: res.status().as_u16(),
+        referrer: referrer.as_deref(),
+        user_agent: user_agent.as_deref(),
+        key: key.as_deref(),
+    };
+    if let Err(e) = log.write(&access) {
+        tracing::error!("Failed to write access log: {e}");
+    }
+    res
+}
+
+/// Content security policy sent with the security headers. The web UI bundle only loads
+/// same-origin scripts, but its fonts and icons come from Google Fonts and jsdelivr, and the
+/// password page uses an inline stylesheet.
+const CONTENT_SECURITY_POLICY: &str = "default-src 'self'; \
+    style-src 'self' 'unsafe-inline' https://fonts.googleapis.com https://cdn.jsdelivr.net; \
+    font-src 'self' https://fonts.gstatic.com https://cdn.jsdelivr.net; \
+    img-src 'self' data:; frame-ancestors 'none'; base-uri 'self'; form-action 'self'";
+
+/// Serve the embedded web UI on all paths not handled by other routes.
+#[cfg(feature = "webui")]
+fn serve_ui(app: Router<AppState>, state: &AppState) -> Router<AppState> {
+    // Only the UI goes through templating, never API responses or redirects.
+    let ui = ui_assets(Router::new())
+        .layer(axum::middleware::from_fn_with_state(state.clone(), inject_environment))
+        .with_state(state.clone());
+    app.fallback_service(ui)
+}
+
+#[cfg(all(feature = "webui", not(webui_placeholder)))]
+fn ui_assets(router: Router<AppState>) -> Router<AppState> {
     let serve_embed = ServeEmbed::<PageAssets>::with_parameters(
         Some("index.html".to_string()),
         axum_embed::FallbackBehavior::Ok,
         Some("index.html".to_string()),
     );
+    router.fallback_service(serve_embed)
+}
 
-    let app = Router::new()
-        .nest("/api", api::router())
-        .route("/go/:key", routing::get(redirect))                
-        .nest_service("/", serve_embed)
-        .layer(axum::middleware::from_fn_with_state(state.clone(), inject_environment))
-        .with_state(state.clone())
-        .layer(TraceLayer::new_for_http());
-    
-    let listener = tokio::net::TcpListener::bind(&config.bind_address).await.unwrap();
+/// Serve the placeholder page written by the build script when no built UI was available.
+#[cfg(all(feature = "webui", webui_placeholder))]
+fn ui_assets(router: Router<AppState>) -> Router<AppState> {
+    tracing::warn!("Built without a prebuilt web UI; serving a placeholder page");
+    router.fallback(|| async { Html(include_str!(concat!(env!("OUT_DIR"), "/placeholder.html"))) })
+}
 
-    let worker_handle = tokio::task::spawn(metadata_update_worker(state.clone()));
+#[cfg(not(feature = "webui"))]
+fn serve_ui(app: Router<AppState>, _state: &AppState) -> Router<AppState> {
+    tracing::warn!("Built without the `webui` feature; the web UI is not available");
+    app.fallback(not_found)
+}
+
+async fn not_found() -> (StatusCode, &'static str) {
+    (StatusCode::NOT_FOUND, "Not found.")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::{net::TcpListener, sync::mpsc};
+    use reqwest::redirect::Policy;
+    use landmower::clock::{Clock as _, MockClock};
+
+    use super::*;
+
+    async fn setup_test_app(config: Config) -> (String, mpsc::Sender<()>) {
+        setup_test_app_with_state(AppState::new(config, Links::new())).await
+    }
+
+    async fn setup_test_app_with_state(state: AppState) -> (String, mpsc::Sender<()>) {
+        serve_test_router(app(state)).await
+    }
+
+    async fn serve_test_router(app: Router) -> (String, mpsc::Sender<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
 
-    axum::serve(listener, app).await.unwrap();
-    worker_handle.await.unwrap();
-}
\ No newline at end of file
+        let (sender, mut receiver) = mpsc::channel(1);
+
+        tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service())
+                .with_graceful_shutdown(async move {
+                    receiver.recv().await;
+                })
+                .await.unwrap();
+        });
+
+        (addr, sender)
+    }
+
+    fn test_client() -> reqwest::Client {
+        reqwest::Client::builder()
+            .redirect(Policy::none())
+            .build().unwrap()
+    }
+
+    mod root {
+        use super::*;
+
+        #[cfg(feature = "webui")]
+        #[tokio::test]
+        async fn serves_ui_by_default() {
+            let (addr, shutdown) = setup_test_app(Config::default()).await;
+
+            let res = test_client().get(format!("{addr}/")).send().await.unwrap();
+            assert_eq!(res.status(), 200);
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn redirect_configured() {
+            let (addr, shutdown) = setup_test_app(Config {
+                root_redirect: Some("https://example.com/".to_string()),
+                ..Config::default()
+            }).await;
+
+            let client = test_client();
+
+            let res = client.get(format!("{addr}/")).send().await.unwrap();
+            assert_eq!(res.status(), 302);
+            assert_eq!(res.headers()[header::LOCATION], "https://example.com/");
+
+            #[cfg(feature = "webui")]
+            {
+                let res = client.get(format!("{addr}/index.html")).send().await.unwrap();
+                assert_eq!(res.status(), 200);
+            }
+
+            shutdown.send(()).await.unwrap();
+        }
+    }
+
+    mod api_only {
+        use super::*;
+
+        #[tokio::test]
+        async fn unknown_paths_not_found() {
+            let (addr, shutdown) = setup_test_app(Config {
+                serve_ui: false,
+                ..Config::default()
+            }).await;
+
+            let client = test_client();
+
+            for path in ["/", "/index.html", "/some/unknown/path"] {
+                let res = client.get(format!("{addr}{path}")).send().await.unwrap();
+                assert_eq!(res.status(), 404);
+            }
+
+            let res = client.get(format!("{addr}/api/links")).send().await.unwrap();
+            assert_eq!(res.status(), 200);
+
+            shutdown.send(()).await.unwrap();
+        }
+    }
+
+    mod go_index {
+        use super::*;
+
+        async fn setup(go_index: GoIndex) -> (String, mpsc::Sender<()>) {
+            let mut links = Links::new();
+            links.add_named("key2".to_string(), "https://example.com/2".to_string()).unwrap();
+            links.add_named("key1".to_string(), "https://example.com/1".to_string()).unwrap();
+            setup_test_app_with_state(AppState::new(Config { go_index, ..Config::default() }, links)).await
+        }
+
+        #[cfg(feature = "webui")]
+        #[tokio::test]
+        async fn ui_by_default() {
+            let (addr, shutdown) = setup(GoIndex::default()).await;
+
+            let res = test_client().get(format!("{addr}/go/")).send().await.unwrap();
+            assert_eq!(res.status(), 200);
+            assert!(res.headers()[header::CONTENT_TYPE].to_str().unwrap().starts_with("text/html"));
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn not_found() {
+            let (addr, shutdown) = setup(GoIndex::NotFound).await;
+
+            let res = test_client().get(format!("{addr}/go/")).send().await.unwrap();
+            assert_eq!(res.status(), 404);
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn json() {
+            let (addr, shutdown) = setup(GoIndex::Json).await;
+            let client = test_client();
+
+            let keys = client.get(format!("{addr}/go/")).send().await.unwrap()
+                .json::<Jsend<Vec<String>, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(keys, ["key1", "key2"]);
+
+            // Keys still redirect
+            let res = client.get(format!("{addr}/go/key1")).send().await.unwrap();
+            assert_eq!(res.status(), 303);
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn json_acl() {
+            let mut links = Links::new();
+            links.add_named("key1".to_string(), "https://example.com/1".to_string()).unwrap();
+            links.add_named("private".to_string(), "https://example.com/2".to_string()).unwrap();
+            links.get_mut("private").unwrap().acl = vec!["alice".to_string()];
+            let (addr, shutdown) = setup_test_app_with_state(AppState::new(Config {
+                go_index: GoIndex::Json,
+                api_token: Some("admin-secret".to_string()),
+                user_tokens: [("alice".to_string(), "alice-secret".to_string())].into(),
+                ..Config::default()
+            }, links)).await;
+            let keys = |token: Option<&str>| {
+                let mut req = test_client().get(format!("{addr}/go/"));
+                if let Some(token) = token {
+                    req = req.bearer_auth(token);
+                }
+                req.send()
+            };
+
+            let data = keys(None).await.unwrap()
+                .json::<Jsend<Vec<String>, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(data, ["key1"]);
+            let data = keys(Some("alice-secret")).await.unwrap()
+                .json::<Jsend<Vec<String>, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(data, ["key1", "private"]);
+
+            shutdown.send(()).await.unwrap();
+        }
+    }
+
+    mod metadata_worker {
+        use std::time::{Instant, SystemTime};
+
+        use super::*;
+
+        #[test]
+        fn adaptive_poll_interval() {
+            let mut interval = WORKER_MIN_INTERVAL;
+            for _ in 0..100 {
+                let next = next_poll_interval(interval, false);
+                assert!(next >= interval);
+                interval = next;
+            }
+            assert_eq!(interval, WORKER_MAX_INTERVAL);
+            assert_eq!(next_poll_interval(interval, true), WORKER_MIN_INTERVAL);
+        }
+
+        #[tokio::test]
+        async fn access_tail() {
+            let mut links = Links::new();
+            links.add_named("key1".to_string(), "https://example.com".to_string()).unwrap();
+            let state = AppState::new(Config::default(), links);
+            let mut tail = state.access_tail.subscribe();
+
+            state.access_event_queue.push(LinkAccessEvent {
+                key: "key1".to_string(),
+                timestamp: SystemTime::now(),
+                visitor: None,
+            }).unwrap();
+            assert_eq!(apply_access_events(&state).await, 1);
+            assert_eq!(tail.try_recv().unwrap().key, "key1");
+            assert!(tail.try_recv().is_err());
+        }
+
+        #[tokio::test]
+        async fn link_counts() {
+            let mut links = Links::new();
+            links.add_named("key1".to_string(), "https://example.com".to_string()).unwrap();
+            let state = AppState::new(Config::default(), links);
+            for _ in 0..3 {
+                state.access_event_queue.push(LinkAccessEvent {
+                    key: "key1".to_string(),
+                    timestamp: SystemTime::now(),
+                    visitor: None,
+                }).unwrap();
+            }
+            apply_access_events(&state).await;
+
+            // Served from the snapshot while the store is locked
+            let mut links = state.links.write().await;
+            assert_eq!(state.link_counts()["key1"].used, 3);
+            links.add_named("key2".to_string(), "https://example.com/2".to_string()).unwrap();
+            drop(links);
+            assert_eq!(state.link_counts()["key2"].used, 0);
+        }
+
+        #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+        async fn redirects_not_starved() {
+            const EVENTS: u64 = 200_000;
+
+            let mut links = Links::new();
+            links.add_named("key1".to_string(), "https://example.com".to_string()).unwrap();
+            let state = AppState::new(Config {
+                worker_batch_size: 100,
+                ..Config::default()
+            }, links);
+            for _ in 0..EVENTS {
+                state.access_event_queue.push(LinkAccessEvent {
+                    key: "key1".to_string(),
+                    timestamp: SystemTime::now(),
+                    visitor: None,
+                }).unwrap();
+            }
+
+            let (addr, shutdown) = setup_test_app_with_state(state.clone()).await;
+            tokio::spawn(metadata_update_worker(state.clone()));
+
+            let client = test_client();
+            let mut redirects = 0;
+            let mut served_while_busy = 0;
+            while !state.access_event_queue.is_empty() {
+                let res = client.get(format!("{addr}/go/key1")).send().await.unwrap();
+                assert_eq!(res.status(), 303);
+                redirects += 1;
+                // A starved redirect would only be answered once the worker drained the queue.
+                if !state.access_event_queue.is_empty() {
+                    served_while_busy += 1;
+                }
+            }
+            assert!(served_while_busy > 0, "redirects were only served after the queue drained");
+
+            let deadline = Instant::now() + Duration::from_secs(5);
+            loop {
+                let used = state.links.read().await.get("key1").unwrap().metadata.used;
+                if used == EVENTS + redirects {
+                    break;
+                }
+                assert!(Instant::now() < deadline, "expected {} accesses, got {used}", EVENTS + redirects);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn unique_visitors() {
+            let mut links = Links::new();
+            links.add_named("key1".to_string(), "https://example.com".to_string()).unwrap();
+            let state = AppState::new(Config::default(), links);
+            let (addr, shutdown) = setup_test_app_with_state(state.clone()).await;
+
+            let client = test_client();
+            for ip in ["10.0.0.1", "10.0.0.2", "10.0.0.1", "10.0.0.3, 10.0.0.1"] {
+                let res = client.get(format!("{addr}/go/key1"))
+                    .header("x-forwarded-for", ip)
+                    .send().await.unwrap();
+                assert_eq!(res.status(), 303);
+            }
+            apply_access_events(&state).await;
+
+            let links = state.links.read().await;
+            let entry = links.get("key1").unwrap();
+            assert_eq!(entry.metadata.used, 4);
+            assert_eq!(entry.visitors.as_ref().unwrap().estimate(), 3);
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn purge_expired() {
+            let mut links = Links::new();
+            links.add_named("deleted".to_string(), "https://example.com".to_string()).unwrap();
+            let entry = links.get_mut("deleted").unwrap();
+            entry.expires_at = Some(chrono::Utc::now() - chrono::Duration::minutes(1));
+            entry.on_expire = links::OnExpire::Delete;
+            let links_path = std::env::temp_dir().join("links-purge-expired.toml");
+            let state = AppState::new(Config {
+                link_data_path: links_path.clone(),
+                ..Config::default()
+            }, links);
+
+            assert_eq!(purge_expired_links(&state).await, 1);
+            assert!(state.links.read().await.get("deleted").is_none());
+            assert!(Links::<links::EntryMetadata>::load(&links_path).unwrap().get("deleted").is_none());
+            assert_eq!(purge_expired_links(&state).await, 0);
+
+            std::fs::remove_file(links_path).unwrap();
+        }
+
+        #[tokio::test]
+        async fn autosave() {
+            let links_path = std::env::temp_dir().join("links-autosave.toml");
+            let state = AppState::new(Config {
+                link_data_path: links_path.clone(),
+                ..Config::default()
+            }, Links::load(&links_path).unwrap());
+            assert!(!state.save_links_if_changed().await.unwrap());
+
+            // Changed without saving, e.g. by applying access events
+            state.links.write().await
+                .add_named("key1".to_string(), "https://example.com".to_string()).unwrap();
+            assert!(state.save_links_if_changed().await.unwrap());
+            assert!(Links::<links::EntryMetadata>::load(&links_path).unwrap().get("key1").is_some());
+            assert!(!state.save_links_if_changed().await.unwrap());
+
+            state.links.write().await.get_mut("key1").unwrap().metadata.used += 1;
+            assert!(state.save_links_if_changed().await.unwrap());
+            assert_eq!(Links::<links::EntryMetadata>::load(&links_path).unwrap().get("key1").unwrap().metadata.used, 1);
+
+            std::fs::remove_file(links_path).unwrap();
+        }
+    }
+
+    #[cfg(feature = "webui")]
+    mod static_assets {
+        use super::*;
+
+        const LAST_MODIFIED: &str = "Wed, 21 Oct 2015 07:28:00 GMT";
+
+        /// Stand-in for the embedded assets, with the headers the static service sets.
+        async fn setup_mock_ui() -> (String, mpsc::Sender<()>) {
+            let state = AppState::new(Config {
+                server_base_url: "short.example/".to_string(),
+                ..Config::default()
+            }, Links::new());
+            let headers = |content_type| [
+                (header::CONTENT_TYPE, content_type),
+                (header::ETAG, "abc123"),
+                (header::LAST_MODIFIED, LAST_MODIFIED),
+            ];
+
+            let router = Router::new()
+                .route("/index.html", routing::get(move || async move {
+                    (headers("text/html"), "<p>{{ server_base_url }}</p>")
+                }))
+                .route("/style.css", routing::get(move || async move {
+                    (headers("text/css"), "p { color: red; }")
+                }))
+                .layer(axum::middleware::from_fn_with_state(state.clone(), inject_environment))
+                .with_state(state);
+            serve_test_router(router).await
+        }
+
+        #[tokio::test]
+        async fn templates_not_cached() {
+            let (addr, shutdown) = setup_mock_ui().await;
+
+            let res = test_client().get(format!("{addr}/index.html")).send().await.unwrap();
+            assert_eq!(res.status(), 200);
+            assert_eq!(res.headers()[header::CACHE_CONTROL], "no-cache");
+            assert!(!res.headers().contains_key(header::ETAG));
+            assert!(!res.headers().contains_key(header::LAST_MODIFIED));
+            assert_eq!(res.text().await.unwrap(), "<p>short.example/</p>");
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn assets_keep_validators() {
+            let (addr, shutdown) = setup_mock_ui().await;
+
+            let res = test_client().get(format!("{addr}/style.css")).send().await.unwrap();
+            assert_eq!(res.status(), 200);
+            assert_eq!(res.headers()[header::ETAG], "abc123");
+            assert_eq!(res.headers()[header::LAST_MODIFIED], LAST_MODIFIED);
+            assert_eq!(res.text().await.unwrap(), "p { color: red; }");
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn if_modified_since() {
+            let (addr, shutdown) = setup_mock_ui().await;
+            let client = test_client();
+
+            let res = client.get(format!("{addr}/style.css"))
+                .header(header::IF_MODIFIED_SINCE, LAST_MODIFIED)
+                .send().await.unwrap();
+            assert_eq!(res.status(), 304);
+            assert_eq!(res.headers()[header::ETAG], "abc123");
+
+            let res = client.get(format!("{addr}/style.css"))
+                .header(header::IF_MODIFIED_SINCE, "Tue, 20 Oct 2015 07:28:00 GMT")
+                .send().await.unwrap();
+            assert_eq!(res.status(), 200);
+
+            // Templates are always rendered again.
+            let res = client.get(format!("{addr}/index.html"))
+                .header(header::IF_MODIFIED_SINCE, LAST_MODIFIED)
+                .send().await.unwrap();
+            assert_eq!(res.status(), 200);
+
+            shutdown.send(()).await.unwrap();
+        }
+    }
+
+    mod redirect {
+        use super::*;
+
+        async fn setup_alt_target() -> (String, mpsc::Sender<()>) {
+            let mut links = Links::new();
+            links.add_named("docs".to_string(), "https://example.com/docs".to_string()).unwrap();
+            links.get_mut("docs").unwrap().alt_target = Some("https://example.com/openapi.json".to_string());
+            setup_test_app_with_state(AppState::new(Config::default(), links)).await
+        }
+
+        #[tokio::test]
+        async fn alt_target_negotiation() {
+            let (addr, shutdown) = setup_alt_target().await;
+            let client = test_client();
+
+            let res = client.get(format!("{addr}/go/docs"))
+                .header(header::ACCEPT, "text/html,application/xhtml+xml,*/*;q=0.8")
+                .send().await.unwrap();
+            assert_eq!(res.status(), 303);
+            assert_eq!(res.headers()[header::LOCATION], "https://example.com/docs");
+            assert_eq!(res.headers()[header::VARY], "Accept");
+
+            for accept in [Some("*/*"), Some("application/json"), Some("text/html;q=0, */*"), None] {
+                let mut req = client.get(format!("{addr}/go/docs"));
+                if let Some(accept) = accept {
+                    req = req.header(header::ACCEPT, accept);
+                }
+                let res = req.send().await.unwrap();
+                assert_eq!(res.headers()[header::LOCATION], "https://example.com/openapi.json", "Accept: {accept:?}");
+            }
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn access_log() {
+            let log_path = std::env::temp_dir().join("landmower-access-log.log");
+            let _ = std::fs::remove_file(&log_path);
+            let mut links = Links::new();
+            links.add_named("docs".to_string(), "https://example.com/docs".to_string()).unwrap();
+            let clock = Arc::new(MockClock::new("2024-03-05T14:07:09Z".parse().unwrap()));
+            let state = AppState::with_clock(Config {
+                access_log_format: access_log::AccessLogFormat::Combined,
+                access_log_path: Some(log_path.clone()),
+                ..Config::default()
+            }, links, clock);
+            let (addr, shutdown) = setup_test_app_with_state(state).await;
+            let client = test_client();
+
+            client.get(format!("{addr}/go/docs?ref=mail"))
+                .header(header::REFERER, "https://mail.example.com/")
+                .header(header::USER_AGENT, "curl/8.5.0")
+                .send().await.unwrap();
+            client.get(format!("{addr}/go/missing")).send().await.unwrap();
+
+            // Written in the background
+            let deadline = Instant::now() + Duration::from_secs(5);
+            let log = loop {
+                let log = std::fs::read_to_string(&log_path).unwrap();
+                if log.lines().count() >= 2 || Instant::now() > deadline {
+                    break log;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            };
+            let lines = log.lines().collect::<Vec<_>>();
+            assert_eq!(lines, [
+                r#"- - - [05/Mar/2024:14:07:09 +0000] "GET /go/docs?ref=mail HTTP/1.1" 303 - "https://mail.example.com/" "curl/8.5.0" "docs""#,
+                r#"- - - [05/Mar/2024:14:07:09 +0000] "GET /go/missing HTTP/1.1" 404 - - - "missing""#,
+            ]);
+
+            shutdown.send(()).await.unwrap();
+            std::fs::remove_file(&log_path).unwrap();
+        }
+
+        #[tokio::test]
+        async fn analytics_disabled() {
+            let mut links = Links::new();
+            links.add_named("key1".to_string(), "https://example.com".to_string()).unwrap();
+            let state = AppState::new(Config {
+                disable_analytics: true,
+                ..Config::default()
+            }, links);
+            let (addr, shutdown) = setup_test_app_with_state(state.clone()).await;
+
+            for _ in 0..3 {
+                let res = test_client().get(format!("{addr}/go/key1")).send().await.unwrap();
+                assert_eq!(res.status(), 303);
+            }
+            assert!(state.access_event_queue.is_empty());
+            apply_access_events(&state).await;
+            assert_eq!(state.links.read().await.get("key1").unwrap().metadata.used, 0);
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn random() {
+            let mut links = Links::new();
+            links.add_named("key1".to_string(), "https://example.com/1".to_string()).unwrap();
+            links.add_named("key2".to_string(), "https://example.com/2".to_string()).unwrap();
+            links.add_named("locked".to_string(), "https://example.com/locked".to_string()).unwrap();
+            links.get_mut("locked").unwrap().set_password("hunter2").unwrap();
+            // Unlocked aliases of locked or expired entries aren't picked either
+            links.add_named("outer".to_string(), "go:locked".to_string()).unwrap();
+            links.add_named("expired".to_string(), "https://example.com/expired".to_string()).unwrap();
+            links.get_mut("expired").unwrap().expires_at = Some(chrono::Utc::now() - chrono::Duration::minutes(1));
+            links.add_named("stale".to_string(), "go:expired".to_string()).unwrap();
+            let state = AppState::new(Config::default(), links);
+            let (addr, shutdown) = setup_test_app_with_state(state.clone()).await;
+            let client = test_client();
+
+            for _ in 0..30 {
+                let res = client.get(format!("{addr}/go/~random")).send().await.unwrap();
+                assert_eq!(res.status(), 303);
+                let location = res.headers()[header::LOCATION].to_str().unwrap();
+                assert!(["https://example.com/1", "https://example.com/2"].contains(&location), "{location}");
+            }
+            apply_access_events(&state).await;
+            let links = state.links.read().await;
+            assert_eq!(links.get("key1").unwrap().metadata.used + links.get("key2").unwrap().metadata.used, 30);
+            for key in ["locked", "outer", "stale"] {
+                assert_eq!(links.get(key).unwrap().metadata.used, 0, "{key}");
+            }
+            drop(links);
+
+            state.links.write().await.clear();
+            let res = client.get(format!("{addr}/go/~random")).send().await.unwrap();
+            assert_eq!(res.status(), 404);
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn percent_encoded_key() {
+            let mut links = Links::new();
+            links.add_named("café".to_string(), "https://example.com".to_string()).unwrap();
+            let (addr, shutdown) = setup_test_app_with_state(AppState::new(Config::default(), links)).await;
+
+            let res = test_client().get(format!("{addr}/go/caf%C3%A9")).send().await.unwrap();
+            assert_eq!(res.status(), 303);
+            assert_eq!(res.headers()[header::LOCATION], "https://example.com");
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn without_alt_target() {
+            let mut links = Links::new();
+            links.add_named("test".to_string(), "https://example.com".to_string()).unwrap();
+            let (addr, shutdown) = setup_test_app_with_state(AppState::new(Config::default(), links)).await;
+
+            let res = test_client().get(format!("{addr}/go/test"))
+                .header(header::ACCEPT, "application/json")
+                .send().await.unwrap();
+            assert_eq!(res.headers()[header::LOCATION], "https://example.com");
+            assert!(!res.headers().contains_key(header::VARY));
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn expired() {
+            let past = chrono::Utc::now() - chrono::Duration::minutes(1);
+            let future = chrono::Utc::now() + chrono::Duration::minutes(1);
+            let mut links = Links::new();
+            for (key, expires_at, on_expire) in [
+                ("gone", past, links::OnExpire::Gone),
+                ("moved", past, links::OnExpire::Redirect("https://example.com/archive".to_string())),
+                ("deleted", past, links::OnExpire::Delete),
+                ("pending", future, links::OnExpire::Gone),
+            ] {
+                links.add_named(key.to_string(), format!("https://example.com/{key}")).unwrap();
+                let entry = links.get_mut(key).unwrap();
+                entry.expires_at = Some(expires_at);
+                entry.on_expire = on_expire;
+            }
+            links.add_named("alias".to_string(), "go:gone".to_string()).unwrap();
+            // An expired alias in the middle of a chain stops it too
+            links.add_named("middle".to_string(), "go:pending".to_string()).unwrap();
+            links.get_mut("middle").unwrap().expires_at = Some(past);
+            links.add_named("outer".to_string(), "go:middle".to_string()).unwrap();
+            let (addr, shutdown) = setup_test_app_with_state(AppState::new(Config::default(), links)).await;
+            let client = test_client();
+
+            for key in ["gone", "deleted", "alias", "middle", "outer"] {
+                let res = client.get(format!("{addr}/go/{key}")).send().await.unwrap();
+                assert_eq!(res.status(), 410, "{key}");
+            }
+
+            let res = client.get(format!("{addr}/go/moved")).send().await.unwrap();
+            assert_eq!(res.status(), 302);
+            assert_eq!(res.headers()[header::LOCATION], "https://example.com/archive");
+
+            let res = client.get(format!("{addr}/go/pending")).send().await.unwrap();
+            assert_eq!(res.headers()[header::LOCATION], "https://example.com/pending");
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn uptime_stats() {
+            let mut links = Links::new();
+            links.add_named("test".to_string(), "https://example.com".to_string()).unwrap();
+            let state = AppState::new(Config::default(), links);
+            let (addr, shutdown) = setup_test_app_with_state(state.clone()).await;
+            let client = test_client();
+
+            for key in ["test", "test", "missing"] {
+                client.get(format!("{addr}/go/{key}")).send().await.unwrap();
+            }
+            client.get(format!("{addr}/go/test?peek=1")).send().await.unwrap();
+
+            let stats = client.get(format!("{addr}/api/stats/uptime"))
+                .send().await.unwrap()
+                .json::<Jsend<serde_json::Value, ()>>().await.unwrap()
+                .success().unwrap();
+            assert_eq!(stats["redirects"], 2);
+            assert_eq!(stats["queue_depth"], 2);
+            assert_eq!(stats["started_at"], serde_json::to_value(state.started_at).unwrap());
+
+            // Applying the queued accesses doesn't change the count
+            apply_access_events(&state).await;
+            assert_eq!(state.redirects_served.load(std::sync::atomic::Ordering::Relaxed), 2);
+            assert!(state.access_event_queue.is_empty());
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn not_found() {
+            let (addr, shutdown) = setup_test_app(Config::default()).await;
+
+            let res = test_client().get(format!("{addr}/go/missing")).send().await.unwrap();
+            assert_eq!(res.status(), 404);
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn empty_store() {
+            let config = Config { empty_store: EmptyStore::Setup, ..Config::default() };
+            let (addr, shutdown) = setup_test_app(config.clone()).await;
+            let res = test_client().get(format!("{addr}/go/missing")).send().await.unwrap();
+            assert_eq!(res.status(), 404);
+            assert!(res.text().await.unwrap().contains("No links yet"));
+            shutdown.send(()).await.unwrap();
+
+            let (addr, shutdown) = setup_test_app(Config {
+                empty_store: EmptyStore::Redirect("/welcome".to_string()),
+                fallback_redirect: Some("https://search.example.com/?q={key}".to_string()),
+                ..Config::default()
+            }).await;
+            let res = test_client().get(format!("{addr}/go/missing")).send().await.unwrap();
+            assert_eq!(res.status(), 302);
+            assert_eq!(res.headers()[header::LOCATION], "/welcome");
+            shutdown.send(()).await.unwrap();
+
+            // Only applies while there are no links at all
+            let mut links = Links::new();
+            links.add_named("test".to_string(), "https://example.com".to_string()).unwrap();
+            let (addr, shutdown) = setup_test_app_with_state(AppState::new(config, links)).await;
+            let res = test_client().get(format!("{addr}/go/missing")).send().await.unwrap();
+            assert_eq!(res.status(), 404);
+            assert!(!res.text().await.unwrap().contains("No links yet"));
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn key_rate_limit() {
+            let mut links = Links::new();
+            links.add_named("hot".to_string(), "https://example.com/hot".to_string()).unwrap();
+            links.add_named("cold".to_string(), "https://example.com/cold".to_string()).unwrap();
+            let config = Config { key_rate_limit: Some(5), ..Config::default() };
+            let (addr, shutdown) = setup_test_app_with_state(AppState::new(config, links)).await;
+            let client = test_client();
+
+            for _ in 0..5 {
+                let res = client.get(format!("{addr}/go/hot")).send().await.unwrap();
+                assert_eq!(res.status(), 303);
+            }
+            for _ in 0..20 {
+                let res = client.get(format!("{addr}/go/hot")).send().await.unwrap();
+                assert_eq!(res.status(), 429);
+                assert_eq!(res.headers()[header::RETRY_AFTER], "60");
+            }
+            let res = client.get(format!("{addr}/go/cold")).send().await.unwrap();
+            assert_eq!(res.status(), 303);
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn schemeless_link() {
+            for (default_scheme, expected) in [(None, "https://example.com/docs"), (Some("http"), "http://example.com/docs")] {
+                let mut links = Links::new();
+                links.add_named("docs".to_string(), "example.com/docs".to_string()).unwrap();
+                let mut config = Config::default();
+                if let Some(scheme) = default_scheme {
+                    config.default_scheme = scheme.to_string();
+                }
+                let (addr, shutdown) = setup_test_app_with_state(AppState::new(config, links)).await;
+
+                let res = test_client().get(format!("{addr}/go/docs")).send().await.unwrap();
+                assert_eq!(res.status(), 303);
+                assert_eq!(res.headers()[header::LOCATION], expected);
+
+                shutdown.send(()).await.unwrap();
+            }
+        }
+
+        #[tokio::test]
+        async fn expires_with_clock() {
+            let clock = Arc::new(MockClock::new(chrono::Utc::now()));
+            let mut links = Links::new();
+            links.add_named("launch".to_string(), "https://example.com".to_string()).unwrap();
+            links.get_mut("launch").unwrap().expires_at = Some(clock.now() + chrono::Duration::hours(1));
+            let state = AppState::with_clock(Config::default(), links, clock.clone());
+            let (addr, shutdown) = setup_test_app_with_state(state).await;
+            let client = test_client();
+
+            let res = client.get(format!("{addr}/go/launch")).send().await.unwrap();
+            assert_eq!(res.status(), 303);
+            clock.advance(chrono::Duration::hours(2));
+            let res = client.get(format!("{addr}/go/launch")).send().await.unwrap();
+            assert_eq!(res.status(), 410);
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn deleted_grace() {
+            let mut links = Links::new();
+            links.add_named("old-docs".to_string(), "https://example.com/old".to_string()).unwrap();
+            links.add_named("docs".to_string(), "https://example.com".to_string()).unwrap();
+            let clock = Arc::new(MockClock::new(chrono::Utc::now()));
+            let state = AppState::with_clock(Config {
+                deleted_grace: Some(Duration::from_secs(3600)),
+                deleted_message: Some("Moved to /go/docs".to_string()),
+                ..Config::default()
+            }, links, clock.clone());
+            let (addr, shutdown) = setup_test_app_with_state(state.clone()).await;
+            let client = test_client();
+
+            let res = client.delete(format!("{addr}/api/links/old-docs")).send().await.unwrap();
+            assert_eq!(res.status(), 200);
+            let res = client.get(format!("{addr}/go/old-docs")).send().await.unwrap();
+            assert_eq!(res.status(), 410);
+            assert_eq!(res.text().await.unwrap(), "Moved to /go/docs");
+            let res = client.get(format!("{addr}/go/never-existed")).send().await.unwrap();
+            assert_eq!(res.status(), 404);
+
+            // After the window the key is like any other unknown key, and is purged
+            clock.advance(chrono::Duration::hours(2));
+            let res = client.get(format!("{addr}/go/old-docs")).send().await.unwrap();
+            assert_eq!(res.status(), 404);
+            assert_eq!(state.purge_tombstones().await, 1);
+            assert!(state.tombstones.read().await.is_empty());
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn deleted_grace_rotate_and_merge() {
+            let mut links = Links::new();
+            links.add_named("old-docs".to_string(), "https://example.com/old".to_string()).unwrap();
+            links.add_named("docs".to_string(), "https://example.com".to_string()).unwrap();
+            links.add_named("manual".to_string(), "https://example.com".to_string()).unwrap();
+            let state = AppState::new(Config {
+                link_data_path: links::IN_MEMORY_PATH.into(),
+                deleted_grace: Some(Duration::from_secs(3600)),
+                ..Config::default()
+            }, links);
+            let (addr, shutdown) = setup_test_app_with_state(state).await;
+            let client = test_client();
+
+            let res = client.post(format!("{addr}/api/links/old-docs/rotate")).send().await.unwrap();
+            assert_eq!(res.status(), 200);
+            let res = client.post(format!("{addr}/api/links/merge"))
+                .json(&serde_json::json!({ "from": "manual", "into": "docs" }))
+                .send().await.unwrap();
+            assert_eq!(res.status(), 200);
+
+            for key in ["old-docs", "manual"] {
+                let res = client.get(format!("{addr}/go/{key}")).send().await.unwrap();
+                assert_eq!(res.status(), 410, "{key}");
+            }
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn fallback_redirect() {
+            let (addr, shutdown) = setup_test_app(Config {
+                fallback_redirect: Some("https://search.example.com/?q={key}".to_string()),
+                ..Config::default()
+            }).await;
+
+            let res = test_client().get(format!("{addr}/go/missing")).send().await.unwrap();
+            assert_eq!(res.status(), 302);
+            assert_eq!(res.headers()[header::LOCATION], "https://search.example.com/?q=missing");
+
+            let res = test_client().get(format!("{addr}/go/caf%C3%A9%20menu")).send().await.unwrap();
+            assert_eq!(res.headers()[header::LOCATION], "https://search.example.com/?q=caf%C3%A9%20menu");
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn link_headers() {
+            for enabled in [false, true] {
+                let mut links = Links::new();
+                let entry = links.add_named("test".to_string(), "https://example.com".to_string()).unwrap();
+                let config = Config { link_headers: enabled, ..Config::default() };
+                let (addr, shutdown) = setup_test_app_with_state(AppState::new(config, links)).await;
+
+                let res = test_client().get(format!("{addr}/go/test")).send().await.unwrap();
+                assert_eq!(res.status(), 303);
+                let headers = res.headers();
+                if enabled {
+                    assert_eq!(headers["x-landmower-key"], "test");
+                    assert_eq!(headers["x-landmower-created"], entry.metadata.created.to_rfc3339().as_str());
+                    assert_eq!(headers["x-landmower-uses"], "0");
+                } else {
+                    for name in ["x-landmower-key", "x-landmower-created", "x-landmower-uses"] {
+                        assert!(!headers.contains_key(name), "{name}");
+                    }
+                }
+
+                shutdown.send(()).await.unwrap();
+            }
+        }
+
+        #[tokio::test]
+        async fn security_headers() {
+            for enabled in [false, true] {
+                let mut links = Links::new();
+                links.add_named("secret".to_string(), "https://example.com".to_string()).unwrap();
+                links.get_mut("secret").unwrap().set_password("hunter2").unwrap();
+                let config = Config { security_headers: enabled, ..Config::default() };
+                let (addr, shutdown) = setup_test_app_with_state(AppState::new(config, links)).await;
+
+                // The password page is HTML
+                let res = test_client().get(format!("{addr}/go/secret")).send().await.unwrap();
+                assert_eq!(res.status(), 200);
+                assert!(res.headers()[header::CONTENT_TYPE].to_str().unwrap().starts_with("text/html"));
+                let headers = res.headers();
+                if enabled {
+                    assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+                    assert_eq!(headers[header::REFERRER_POLICY], "strict-origin-when-cross-origin");
+                    assert_eq!(headers[header::CONTENT_SECURITY_POLICY], CONTENT_SECURITY_POLICY);
+                } else {
+                    assert!(!headers.contains_key(header::CONTENT_SECURITY_POLICY));
+                    assert!(!headers.contains_key(header::X_CONTENT_TYPE_OPTIONS));
+                }
+
+                #[cfg(feature = "webui")]
+                {
+                    let res = test_client().get(format!("{addr}/index.html")).send().await.unwrap();
+                    assert_eq!(res.status(), 200);
+                    let headers = res.headers();
+                    if enabled {
+                        assert_eq!(headers[header::CONTENT_SECURITY_POLICY], CONTENT_SECURITY_POLICY);
+                        assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+                    } else {
+                        assert!(!headers.contains_key(header::CONTENT_SECURITY_POLICY));
+                    }
+                }
+
+                shutdown.send(()).await.unwrap();
+            }
+        }
+
+        #[tokio::test]
+        async fn password_on_alias() {
+            let mut links = Links::new();
+            links.add_named("docs".to_string(), "https://example.com/docs".to_string()).unwrap();
+            links.add_named("locked".to_string(), "go:docs".to_string()).unwrap();
+            links.add_named("outer".to_string(), "go:locked".to_string()).unwrap();
+            links.get_mut("locked").unwrap().set_password("hunter2").unwrap();
+            let (addr, shutdown) = setup_test_app_with_state(AppState::new(Config::default(), links)).await;
+            let client = test_client();
+
+            for key in ["locked", "outer"] {
+                let res = client.get(format!("{addr}/go/{key}")).send().await.unwrap();
+                assert_eq!(res.status(), 200, "{key}");
+                assert!(res.text().await.unwrap().contains("password"), "{key}");
+
+                let res = client.get(format!("{addr}/go/{key}?peek=1")).send().await.unwrap();
+                assert_eq!(res.status(), 403, "{key}");
+
+                let res = client.post(format!("{addr}/go/{key}"))
+                    .form(&[("password", "wrong")])
+                    .send().await.unwrap();
+                assert_eq!(res.status(), 401, "{key}");
+
+                let res = client.post(format!("{addr}/go/{key}"))
+                    .form(&[("password", "hunter2")])
+                    .send().await.unwrap();
+                assert_eq!(res.status(), 303, "{key}");
+                assert_eq!(res.headers()[header::LOCATION], "https://example.com/docs");
+            }
+
+            let res = client.get(format!("{addr}/go/docs")).send().await.unwrap();
+            assert_eq!(res.status(), 303);
+
+            shutdown.send(()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn peek() {
+            let mut links = Links::new();
+            links.add_named("test".to_string(), "example.com".to_string()).unwrap();
+            links.add_named("secret".to_string(), "https://example.com/secret".to_string()).unwrap();
+            links.get_mut("secret").unwrap().set_password("hunter2").unwrap();
+            let state = AppState::new(Config::default(), links);
+            let (addr, shutdown) = setup_test_app_with_state(state.clone()).await;
+            let client = test_client();
+
+            for query in ["peek=1", "peek", "peek=true"] {
+                let res = client.get(format!("{addr}/go/test?{query}")).send().await.unwrap();
+                assert_eq!(res.status(), 200, "{query}");
+                let data = res.json::<Jsend<PeekResponse, String>>().await.unwrap().success().unwrap();
+                assert_eq!(data.key, "test");
+                assert_eq!(data.target, "https://example.com");
+            }
+            // Peeking is not a click
+            assert!(state.access_event_queue.is_empty());
+
+            let res = client.get(format!("{addr}/go/test?peek=0")).send().await.unwrap();
+            assert_eq!(res.status(), 303);
+
+            let res = client.get(format!("{addr}/go/secret?peek=1")).send().await.unwrap();
+            assert_eq!(res.status(), 403);
+            assert!(res.json::<Jsend<PeekResponse, String>>().await.unwrap().is_fail());
+
+            shutdown.send(()).await.unwrap();
+        }
+    }
+}
@@ -0,0 +1,261 @@
+//! Outbound requests to link destinations.
+//!
+//! Every request made here goes through a client built by [`client`], which enforces the
+//! configured timeout and caps the number of followed redirects, so a slow or misbehaving
+//! destination can't tie up tasks. Probes made through a [`Prober`] additionally share a
+//! limit on how many run at once.
+
+use std::sync::Arc;
+
+use reqwest::{header, redirect, Client};
+use tokio::sync::Semaphore;
+
+use crate::Config;
+
+/// Maximum number of redirects followed when introspecting a destination.
+pub const MAX_REDIRECTS: usize = 5;
+
+/// Maximum number of bytes read from a page while looking for its title.
+const MAX_TITLE_SCAN_BYTES: usize = 64 * 1024;
+
+/// Whether new links must point at a destination that responds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VerifyReachable {
+    /// Accept links without checking their destination.
+    #[default]
+    Off,
+    /// Reject links whose destination doesn't respond successfully.
+    Reject,
+    /// Accept all links, but record whether their destination responded.
+    Warn,
+}
+
+impl std::str::FromStr for VerifyReachable {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" | "false" => Ok(Self::Off),
+            "reject" | "true" => Ok(Self::Reject),
+            "warn" => Ok(Self::Warn),
+            _ => Err(format!("Invalid reachability check: '{s}'"))
+        }
+    }
+}
+
+/// Build the client used for all requests to link destinations.
+pub fn client(config: &Config) -> Client {
+    Client::builder()
+        .connect_timeout(config.fetch_timeout)
+        .timeout(config.fetch_timeout)
+        .redirect(redirect::Policy::limited(MAX_REDIRECTS))
+        .build()
+        .expect("Failed to build HTTP client")
+}
+
+/// Probes link destinations, at most a fixed number at a time across all callers.
+#[derive(Clone, Debug)]
+pub struct Prober {
+    client: Client,
+    permits: Arc<Semaphore>,
+    concurrency: usize,
+}
+
+impl Prober {
+    /// Probe with the given client, at most `concurrency` (at least one) at a time.
+    pub fn new(client: Client, concurrency: usize) -> Self {
+        let concurrency = concurrency.max(1);
+        Self { client, permits: Arc::new(Semaphore::new(concurrency)), concurrency }
+    }
+
+    /// How many probes run at once at most.
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    /// [`fetch_title`], waiting for a free slot first.
+    pub async fn fetch_title(&self, url: &str) -> Option<String> {
+        let _permit = self.permits.acquire().await.ok()?;
+        fetch_title(&self.client, url).await
+    }
+
+    /// [`is_reachable`], waiting for a free slot first.
+    pub async fn is_reachable(&self, url: &str) -> bool {
+        let Ok(_permit) = self.permits.acquire().await else {
+            return false;
+        };
+        is_reachable(&self.client, url).await
+    }
+}
+
+/// Fetch the `<title>` of the page at the given URL.
+///
+/// Returns `None` if the request fails or times out, the response is not HTML or the page
+/// has no title.
+pub async fn fetch_title(client: &Client, url: &str) -> Option<String> {
+    let mut res = client.get(url).send().await
+        .inspect_err(|e| tracing::debug!("Could not fetch title of '{url}': {e}"))
+        .ok()?;
+
+    let is_html = res.headers().get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/html"));
+
+    if !res.status().is_success() || !is_html {
+        return None;
+    }
+
+    let mut body = Vec::new();
+    while body.len() < MAX_TITLE_SCAN_BYTES {
+        match res.chunk().await {
+            Ok(Some(chunk)) => body.extend_from_slice(&chunk),
+            Ok(None) => break,
+            Err(e) => {
+                tracing::debug!("Could not fetch title of '{url}': {e}");
+                return None;
+            }
+        }
+    }
+
+    parse_title(&String::from_utf8_lossy(&body))
+}
+
+/// Whether the destination at the given URL responds with a success or redirect status.
+///
+/// Asks with `HEAD` first, so no body is transferred. Hosts that don't implement `HEAD` tend
+/// to respond with an error status like 405, so any error status is double-checked with
+/// `GET`, whose body is never read. Failed requests, including timeouts, aren't retried.
+pub async fn is_reachable(client: &Client, url: &str) -> bool {
+    let ok = |status: reqwest::StatusCode| status.is_success() || status.is_redirection();
+    match client.head(url).send().await {
+        Ok(res) if ok(res.status()) => return true,
+        Ok(_) => {},
+        Err(e) => {
+            tracing::debug!("Could not reach '{url}': {e}");
+            return false;
+        }
+    }
+    client.get(url).send().await
+        .inspect_err(|e| tracing::debug!("Could not reach '{url}': {e}"))
+        .is_ok_and(|res| ok(res.status()))
+}
+
+fn parse_title(html: &str) -> Option<String> {
+    // ASCII lowercasing keeps byte offsets intact, so indices into `lower` are valid for `html`.
+    let lower = html.to_ascii_lowercase();
+    let start = lower.find("<title")?;
+    let start = start + lower[start..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+
+    let title = html[start..end].split_whitespace().collect::<Vec<_>>().join(" ");
+    if title.is_empty() { None } else { Some(title) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::atomic::{AtomicUsize, Ordering}, time::{Duration, Instant}};
+
+    use axum::{http::StatusCode, response::Html, routing, Router};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    async fn setup_mock_server() -> String {
+        let router = Router::new()
+            .route("/page", routing::get(|| async {
+                Html("<html><head><TITLE>\n  Example   page </TITLE></head></html>")
+            }))
+            .route("/plain", routing::get(|| async { "<title>Not HTML</title>" }))
+            .route("/error", routing::get(|| async { StatusCode::INTERNAL_SERVER_ERROR }))
+            .route("/no_head", routing::get(|| async { "GET only" })
+                .head(|| async { StatusCode::METHOD_NOT_ALLOWED }))
+            .route("/slow", routing::get(|| async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Html("<title>Too late</title>")
+            }));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+        addr
+    }
+
+    fn test_client() -> Client {
+        client(&Config {
+            fetch_timeout: Duration::from_millis(200),
+            ..Config::default()
+        })
+    }
+
+    #[test]
+    fn parse_title_missing() {
+        assert_eq!(parse_title("<html><head></head></html>"), None);
+        assert_eq!(parse_title("<title>   </title>"), None);
+    }
+
+    #[tokio::test]
+    async fn fetch_title_base_case() {
+        let addr = setup_mock_server().await;
+        let title = fetch_title(&test_client(), &format!("{addr}/page")).await;
+        assert_eq!(title.as_deref(), Some("Example page"));
+    }
+
+    #[tokio::test]
+    async fn fetch_title_not_html() {
+        let addr = setup_mock_server().await;
+        let title = fetch_title(&test_client(), &format!("{addr}/plain")).await;
+        assert_eq!(title, None);
+    }
+
+    #[tokio::test]
+    async fn fetch_title_timeout() {
+        let addr = setup_mock_server().await;
+
+        let start = Instant::now();
+        let title = fetch_title(&test_client(), &format!("{addr}/slow")).await;
+
+        assert_eq!(title, None);
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn is_reachable_status() {
+        let addr = setup_mock_server().await;
+        let client = test_client();
+        assert!(is_reachable(&client, &format!("{addr}/page")).await);
+        assert!(is_reachable(&client, &format!("{addr}/no_head")).await);
+        assert!(!is_reachable(&client, &format!("{addr}/error")).await);
+        assert!(!is_reachable(&client, &format!("{addr}/missing")).await);
+        assert!(!is_reachable(&client, &format!("{addr}/slow")).await);
+    }
+
+    #[tokio::test]
+    async fn prober_concurrency() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let router = Router::new().route("/", routing::get({
+            let (in_flight, max_in_flight) = (in_flight.clone(), max_in_flight.clone());
+            || async move {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(current, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                "ok"
+            }
+        }));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+
+        let prober = Prober::new(test_client(), 3);
+        let probes = (0..12).map(|_| prober.is_reachable(&addr));
+        let results = futures_util::future::join_all(probes).await;
+
+        assert!(results.into_iter().all(|reachable| reachable));
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 3);
+    }
+}
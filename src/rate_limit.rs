@@ -0,0 +1,95 @@
+//! Limiting how often a single link is followed, so one hot key can't be used to generate
+//! load on the metadata worker or the destination.
+//!
+//! Hits are counted per key with a sliding window counter: the count of the previous fixed
+//! window is weighted by how much of it still overlaps the sliding window. This needs two
+//! counters per key instead of a timestamp per hit.
+
+use std::{collections::HashMap, sync::Mutex, time::{Duration, Instant}};
+
+/// Counts hits per key and rejects them above a limit per window.
+#[derive(Debug)]
+pub struct KeyRateLimiter {
+    limit: u32,
+    window: Duration,
+    windows: Mutex<HashMap<String, Window>>,
+}
+
+#[derive(Debug)]
+struct Window {
+    start: Instant,
+    current: u32,
+    previous: u32,
+}
+
+impl KeyRateLimiter {
+    /// Allow `limit` hits per key within any `window`.
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self { limit, window, windows: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Count a hit on the key at the given time.
+    /// 
+    /// Returns `false`, without counting the hit, if the key is over its limit.
+    pub fn check(&self, key: &str, now: Instant) -> bool {
+        let mut windows = self.windows.lock().unwrap();
+        if !windows.contains_key(key) {
+            windows.insert(key.to_string(), Window { start: now, current: 0, previous: 0 });
+        }
+        let w = windows.get_mut(key).expect("Window inserted above (unreachable state)");
+
+        let elapsed = now.saturating_duration_since(w.start);
+        if elapsed >= self.window * 2 {
+            *w = Window { start: now, current: 0, previous: 0 };
+        } else if elapsed >= self.window {
+            w.previous = w.current;
+            w.current = 0;
+            w.start += self.window;
+        }
+
+        let overlap = 1.0 - now.saturating_duration_since(w.start).as_secs_f64() / self.window.as_secs_f64();
+        let estimate = f64::from(w.previous) * overlap + f64::from(w.current);
+        if estimate >= f64::from(self.limit) {
+            return false;
+        }
+        w.current += 1;
+        true
+    }
+
+    /// Forget keys that weren't hit for long enough that their counts no longer matter.
+    /// 
+    /// Returns the number of keys forgotten.
+    pub fn prune(&self, now: Instant) -> usize {
+        let mut windows = self.windows.lock().unwrap();
+        let before = windows.len();
+        windows.retain(|_, w| now.saturating_duration_since(w.start) < self.window * 2);
+        before - windows.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sliding_window() {
+        let limiter = KeyRateLimiter::new(10, Duration::from_secs(10));
+        let start = Instant::now();
+
+        assert!((0..10).all(|_| limiter.check("hot", start)));
+        assert!(!limiter.check("hot", start));
+        assert!(limiter.check("other", start));
+
+        // Halfway into the next window, half of the previous hits still count
+        let later = start + Duration::from_secs(15);
+        assert!((0..5).all(|_| limiter.check("hot", later)));
+        assert!(!limiter.check("hot", later));
+
+        assert_eq!(limiter.prune(start + Duration::from_secs(25)), 1);
+        assert!(limiter.check("hot", start + Duration::from_secs(40)));
+    }
+}
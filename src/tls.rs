@@ -0,0 +1,52 @@
+//! Serving the app over HTTPS, without a reverse proxy terminating TLS.
+//!
+//! Only available with the `tls` feature. Like `axum::serve`, [`serve`] stops accepting
+//! connections once the shutdown future completes and waits for open connections to finish.
+
+use std::{future::Future, io, path::Path};
+
+use axum::Router;
+use axum_server::{tls_rustls::RustlsConfig, Handle};
+
+/// Load the certificate chain and private key from PEM files.
+pub async fn load_config(cert: &Path, key: &Path) -> io::Result<RustlsConfig> {
+    // Fails if a provider is already installed, which is fine.
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    RustlsConfig::from_pem_file(cert, key).await
+}
+
+/// Serve the app over TLS on the given listener until `shutdown` completes.
+pub async fn serve(
+    listener: std::net::TcpListener,
+    config: RustlsConfig,
+    app: Router,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> io::Result<()> {
+    listener.set_nonblocking(true)?;
+
+    let handle = Handle::new();
+    let shutdown_handle = handle.clone();
+    tokio::spawn(async move {
+        shutdown.await;
+        shutdown_handle.graceful_shutdown(None);
+    });
+
+    axum_server::from_tcp_rustls(listener, config)
+        .handle(handle)
+        .serve(app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn load_config_missing_files() {
+        let cert = temp_dir().join("landmower_test_missing_cert.pem");
+        let key = temp_dir().join("landmower_test_missing_key.pem");
+        assert!(load_config(&cert, &key).await.is_err());
+    }
+}
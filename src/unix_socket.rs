@@ -0,0 +1,138 @@
+//! Serving the app on a Unix domain socket, e.g. behind a reverse proxy.
+//!
+//! `axum::serve` only accepts TCP listeners, so connections are driven by hyper directly.
+//! Like `axum::serve`, [`serve`] stops accepting connections once the shutdown future
+//! completes and waits for open connections to finish.
+
+use std::{future::Future, io, path::Path, pin::pin};
+
+use axum::Router;
+use hyper::server::conn::http1;
+use hyper_util::{rt::TokioIo, service::TowerToHyperService};
+use tokio::{net::UnixListener, sync::watch};
+
+/// Bind a listener to the socket at the given path, removing a stale socket left behind by
+/// an earlier run.
+///
+/// A socket is only considered stale if connecting to it is refused. Sockets that another
+/// process is still listening on, and other files at the path, are left alone, and binding
+/// fails.
+pub fn bind(path: &Path) -> io::Result<UnixListener> {
+    use std::os::unix::fs::FileTypeExt as _;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => match std::os::unix::net::UnixStream::connect(path) {
+            Ok(_) => return Err(io::Error::new(io::ErrorKind::AddrInUse, "another process is listening on the socket")),
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => std::fs::remove_file(path)?,
+            Err(e) => return Err(e),
+        },
+        Ok(_) => {},
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+        Err(e) => return Err(e),
+    }
+    UnixListener::bind(path)
+}
+
+/// Serve the app on the given listener until `shutdown` completes.
+pub async fn serve(listener: UnixListener, app: Router, shutdown: impl Future<Output = ()>) {
+    let (signal_tx, signal_rx) = watch::channel(());
+    let mut shutdown = pin!(shutdown);
+
+    loop {
+        let stream = tokio::select! {
+            conn = listener.accept() => match conn {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    tracing::debug!("Failed to accept connection: {e}");
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+
+        let service = TowerToHyperService::new(app.clone());
+        let mut signal_rx = signal_rx.clone();
+        tokio::spawn(async move {
+            let conn = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .with_upgrades();
+            let mut conn = pin!(conn);
+            loop {
+                tokio::select! {
+                    res = conn.as_mut() => {
+                        if let Err(e) = res {
+                            tracing::debug!("Failed to serve connection: {e}");
+                        }
+                        break;
+                    }
+                    _ = signal_rx.changed() => conn.as_mut().graceful_shutdown(),
+                }
+            }
+        });
+    }
+
+    // Every connection task holds a receiver, so this waits for all of them to finish.
+    drop(signal_rx);
+    let _ = signal_tx.send(());
+    signal_tx.closed().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+
+    use axum::routing;
+    use tokio::{io::{AsyncReadExt as _, AsyncWriteExt as _}, net::UnixStream, sync::oneshot};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn serve_and_shutdown() {
+        let path = temp_dir().join("landmower_test_serve.sock");
+        // A stale socket from an earlier run
+        drop(bind(&path).unwrap());
+        assert!(path.exists());
+
+        let listener = bind(&path).unwrap();
+        let app = Router::new().route("/", routing::get(|| async { "hello" }));
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let server = tokio::spawn(serve(listener, app, async {
+            let _ = shutdown_rx.await;
+        }));
+
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").await.unwrap();
+        let mut res = String::new();
+        stream.read_to_string(&mut res).await.unwrap();
+        assert!(res.starts_with("HTTP/1.1 200 OK"), "{res}");
+        assert!(res.ends_with("hello"), "{res}");
+
+        shutdown_tx.send(()).unwrap();
+        server.await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn bind_keeps_live_socket() {
+        let path = temp_dir().join("landmower_test_live.sock");
+        let _ = std::fs::remove_file(&path);
+        let _listener = bind(&path).unwrap();
+
+        let err = bind(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+        assert!(UnixStream::connect(&path).await.is_ok());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn bind_keeps_other_files() {
+        let path = temp_dir().join("landmower_test_not_a_socket");
+        std::fs::write(&path, "").unwrap();
+
+        assert!(bind(&path).is_err());
+        assert!(path.exists());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
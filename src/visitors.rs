@@ -0,0 +1,149 @@
+//! Estimating unique visitors per link without storing who they are.
+//!
+//! Each visitor is identified by a hash of their IP address and user agent, which only exists
+//! in memory while the access is queued. Links store a HyperLogLog sketch of these hashes: for
+//! each of its 256 registers, only the longest run of leading zeros seen so far is kept. The
+//! sketch can't be used to tell whether a given visitor has followed a link, and neither
+//! addresses, user agents nor their hashes are ever written to disk.
+//!
+//! The tradeoff is accuracy for memory: a sketch takes 256 bytes (344 characters in the links
+//! file) regardless of the number of visitors, and estimates have a standard error of about
+//! 6.5%, somewhat less for small counts.
+
+use std::hash::{Hash as _, Hasher as _};
+
+use base64::prelude::*;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Number of bits of a hash used to pick its register.
+const PRECISION: u32 = 8;
+/// Number of registers in a sketch.
+const REGISTERS: usize = 1 << PRECISION;
+
+/// Hash identifying a visitor by IP address and user agent.
+pub fn visitor_hash(ip: &str, user_agent: &str) -> u64 {
+    let mut hasher = std::hash::DefaultHasher::new();
+    ip.hash(&mut hasher);
+    user_agent.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// HyperLogLog sketch of the visitors of a link.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VisitorSketch {
+    registers: Box<[u8; REGISTERS]>,
+}
+
+impl Default for VisitorSketch {
+    fn default() -> Self {
+        Self { registers: Box::new([0; REGISTERS]) }
+    }
+}
+
+impl VisitorSketch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a visitor by their [`visitor_hash`].
+    pub fn add(&mut self, hash: u64) {
+        let index = (hash >> (64 - PRECISION)) as usize;
+        // Position of the first set bit in the remaining bits, capped if they're all zero.
+        let rank = ((hash << PRECISION).leading_zeros() + 1).min(64 - PRECISION + 1) as u8;
+        self.registers[index] = self.registers[index].max(rank);
+    }
+
+    /// Add all visitors recorded by the other sketch, e.g. when merging links.
+    pub fn merge(&mut self, other: &Self) {
+        for (register, &other) in self.registers.iter_mut().zip(other.registers.iter()) {
+            *register = (*register).max(other);
+        }
+    }
+
+    /// Estimated number of distinct visitors recorded.
+    pub fn estimate(&self) -> u64 {
+        let m = REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| (-(r as f64)).exp2()).sum();
+        let raw = alpha * m * m / sum;
+
+        // The raw estimate is biased for small counts, where counting empty registers is
+        // more accurate.
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        let estimate = if raw <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        };
+        estimate.round() as u64
+    }
+}
+
+/// Stored as the base64 encoded registers.
+impl Serialize for VisitorSketch {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&BASE64_URL_SAFE_NO_PAD.encode(self.registers.as_slice()))
+    }
+}
+
+impl<'de> Deserialize<'de> for VisitorSketch {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        let bytes = BASE64_URL_SAFE_NO_PAD.decode(&encoded).map_err(de::Error::custom)?;
+        let registers = Box::<[u8; REGISTERS]>::try_from(bytes.into_boxed_slice())
+            .map_err(|_| de::Error::custom(format!("Visitor sketch must have {REGISTERS} registers")))?;
+        Ok(Self { registers })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate() {
+        let mut sketch = VisitorSketch::new();
+        assert_eq!(sketch.estimate(), 0);
+
+        for i in 0..100 {
+            let hash = visitor_hash(&format!("10.0.0.{i}"), "curl/8.0");
+            // Repeat visits don't count
+            sketch.add(hash);
+            sketch.add(hash);
+        }
+        assert!((90..=110).contains(&sketch.estimate()), "{}", sketch.estimate());
+
+        for i in 0..10_000 {
+            sketch.add(visitor_hash(&format!("10.1.{}.{}", i / 256, i % 256), "curl/8.0"));
+        }
+        let estimate = sketch.estimate() as f64;
+        assert!((estimate - 10_100.0).abs() / 10_100.0 < 0.2, "{estimate}");
+    }
+
+    #[test]
+    fn merge() {
+        let mut a = VisitorSketch::new();
+        let mut b = VisitorSketch::new();
+        let mut both = VisitorSketch::new();
+        for i in 0..20 {
+            a.add(visitor_hash(&format!("10.0.0.{i}"), "curl/8.0"));
+            b.add(visitor_hash(&format!("10.0.0.{}", i + 10), "curl/8.0"));
+        }
+        for i in 0..30 {
+            both.add(visitor_hash(&format!("10.0.0.{i}"), "curl/8.0"));
+        }
+        // Overlapping visitors are only counted once
+        a.merge(&b);
+        assert_eq!(a, both);
+    }
+
+    #[test]
+    fn serialization() {
+        let mut sketch = VisitorSketch::new();
+        sketch.add(visitor_hash("10.0.0.1", "curl/8.0"));
+
+        let json = serde_json::to_string(&sketch).unwrap();
+        assert_eq!(serde_json::from_str::<VisitorSketch>(&json).unwrap(), sketch);
+        assert!(serde_json::from_str::<VisitorSketch>("\"AAAA\"").is_err());
+    }
+}
@@ -0,0 +1,154 @@
+//! Notifying other services, e.g. chat integrations, about changes to links.
+//!
+//! Events are POSTed as JSON to the configured webhook URL from a spawned task, so a slow or
+//! unavailable receiver never delays API responses. Failed deliveries are retried once and
+//! then logged. If a secret is configured, the body is signed with HMAC-SHA256 and the
+//! signature sent as `X-Landmower-Signature: sha256=<hex>`, so receivers can verify that
+//! the event came from this instance.
+
+use std::time::Duration;
+
+use reqwest::{header, Client};
+use serde::{Deserialize, Serialize};
+
+use crate::{signing::sign, AppState};
+
+/// Header carrying the signature of the body, see [`crate::signing`].
+pub const SIGNATURE_HEADER: &str = "x-landmower-signature";
+
+/// Delay before retrying a failed delivery.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// What happened to a link.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Created,
+    Deleted,
+}
+
+/// Payload of a webhook request.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Event {
+    pub event: EventKind,
+    pub key: String,
+    pub link: String,
+    pub short_url: String,
+}
+
+impl Event {
+    pub fn new(state: &AppState, event: EventKind, key: &str, link: &str) -> Self {
+        Self {
+            event,
+            key: key.to_string(),
+            link: link.to_string(),
+            short_url: state.config.short_url(key),
+        }
+    }
+}
+
+/// Deliver the event in the background, if a webhook is configured and subscribed to it.
+pub fn notify(state: &AppState, event: Event) {
+    let Some(url) = state.config.webhook_url.clone() else {
+        return;
+    };
+    if event.event == EventKind::Deleted && !state.config.webhook_on_delete {
+        return;
+    }
+    let client = state.http_client.clone();
+    let secret = state.config.webhook_secret.clone();
+    tokio::spawn(async move {
+        if let Err(e) = deliver(&client, &url, secret.as_deref(), &event).await {
+            tracing::error!("Failed to deliver webhook for link '{}': {e}", event.key);
+        }
+    });
+}
+
+/// Send the event, retrying once if the request fails or is not answered with a success
+/// status.
+pub async fn deliver(client: &Client, url: &str, secret: Option<&str>, event: &Event) -> Result<(), String> {
+    let body = serde_json::to_vec(event).map_err(|e| e.to_string())?;
+    let signature = secret.map(|secret| sign(secret, &body));
+
+    let mut result = Ok(());
+    for _ in 0..2 {
+        if let Err(e) = &result {
+            tracing::debug!("Retrying webhook for link '{}': {e}", event.key);
+            tokio::time::sleep(RETRY_DELAY).await;
+        }
+        let mut req = client.post(url)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.clone());
+        if let Some(signature) = &signature {
+            req = req.header(SIGNATURE_HEADER, signature);
+        }
+        result = match req.send().await {
+            Ok(res) if res.status().is_success() => return Ok(()),
+            Ok(res) => Err(format!("Receiver responded with {}", res.status())),
+            Err(e) => Err(e.to_string()),
+        };
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+
+    use axum::{body::Bytes, http::{HeaderMap, StatusCode}, routing, Router};
+    use tokio::{net::TcpListener, sync::mpsc};
+
+    use super::*;
+
+    /// Serve a receiver that fails the first `failures` requests and reports the signature
+    /// and body of every successful one.
+    async fn setup_receiver(failures: usize) -> (String, mpsc::Receiver<(Option<String>, Bytes)>) {
+        let (tx, rx) = mpsc::channel(8);
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let router = Router::new().route("/hook", routing::post(move |headers: HeaderMap, body: Bytes| async move {
+            if attempts.fetch_add(1, Ordering::Relaxed) < failures {
+                return StatusCode::SERVICE_UNAVAILABLE;
+            }
+            let signature = headers.get(SIGNATURE_HEADER).map(|v| v.to_str().unwrap().to_string());
+            tx.send((signature, body)).await.unwrap();
+            StatusCode::NO_CONTENT
+        }));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+        (addr, rx)
+    }
+
+    fn test_event() -> Event {
+        Event {
+            event: EventKind::Created,
+            key: "docs".to_string(),
+            link: "https://example.com/docs".to_string(),
+            short_url: "landmow.er/docs".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn deliver_signed() {
+        let (url, mut rx) = setup_receiver(0).await;
+
+        deliver(&Client::new(), &url, Some("secret"), &test_event()).await.unwrap();
+
+        let (signature, body) = rx.recv().await.unwrap();
+        assert_eq!(signature, Some(sign("secret", &body)));
+        assert_eq!(serde_json::from_slice::<Event>(&body).unwrap(), test_event());
+    }
+
+    #[tokio::test]
+    async fn deliver_retries_once() {
+        let (url, mut rx) = setup_receiver(1).await;
+        deliver(&Client::new(), &url, None, &test_event()).await.unwrap();
+        assert_eq!(rx.recv().await.unwrap().0, None);
+
+        let (url, _rx) = setup_receiver(2).await;
+        assert!(deliver(&Client::new(), &url, None, &test_event()).await.is_err());
+    }
+}
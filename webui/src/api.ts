@@ -11,16 +11,39 @@ export type Entry = {
     used: number;
     last_used: string;
     created: string;
+    source?: string;
   };
+  unique?: number;
+  reachable?: boolean;
+  alive?: boolean;
+  last_checked?: string;
+  expires_at?: string;
+  on_expire?: "gone" | "delete" | { redirect: string };
+  primary?: boolean;
+  protected?: boolean;
+  acl?: string[];
+  tags?: string[];
 };
 
 export type AddLinkResponse = Jsend<AddLinkSuccessData, AddLinkFailData>;
-type AddLinkSuccessData = { key: string; entry: Entry };
+type AddLinkSuccessData = { key: string; entry: Entry; existing: boolean };
 type AddLinkFailData = { link?: string; key?: string };
 
-export type GetLinksResponse = Jsend<Entry[], null>;
+export type GetLinksResponse = Jsend<GetLinksData, null>;
+type GetLinksData = {
+  total: number;
+  total_clicks: number;
+  distinct_targets: number;
+  items: Entry[];
+};
 export type GetLinkResponse = Jsend<Entry, string>;
+export type BatchGetLinksResponse = Jsend<Record<string, Entry | null>, null>;
 export type DeleteLinkResponse = Jsend<null, string>;
+export type GetLinkHeatmapResponse = Jsend<DayCount[], string>;
+type DayCount = {
+  date: string;
+  clicks: number;
+};
 
 export type ValidateAddLinkResponse = Jsend<null, AddLinkFailData>;
 
@@ -29,25 +52,48 @@ export async function add_link(
   custom: boolean,
   customName: string,
 ): Promise<AddLinkResponse> {
-  let res = await axios.post(`/api/links`, {
-    link: link,
-    key: custom ? customName : undefined,
-  });
+  let res = await axios.post(
+    `/api/links`,
+    {
+      link: link,
+      key: custom ? customName : undefined,
+    },
+    { validateStatus: isJsendStatus },
+  );
   return res.data;
 }
 
+// Client errors still carry a JSend `fail` body the caller can inspect
+const isJsendStatus = (status: number) => status < 500;
+
 export async function get_links(): Promise<GetLinksResponse> {
   let res = await axios.get(`/api/links`);
   return res.data;
 }
 
 export async function get_link(key: string): Promise<GetLinkResponse> {
-  let res = await axios.get(`/api/links/${key}`);
+  let res = await axios.get(`/api/links/${key}`, { validateStatus: isJsendStatus });
+  return res.data;
+}
+
+export async function batch_get_links(
+  keys: string[],
+  include_missing: boolean = false,
+): Promise<BatchGetLinksResponse> {
+  let res = await axios.post(`/api/links/batch-get`, { keys, include_missing });
+  return res.data;
+}
+
+export async function get_link_heatmap(
+  key: string,
+  days: number = 90,
+): Promise<GetLinkHeatmapResponse> {
+  let res = await axios.get(`/api/links/${key}/heatmap`, { params: { days }, validateStatus: isJsendStatus });
   return res.data;
 }
 
 export async function delete_link(key: string): Promise<DeleteLinkResponse> {
-  let res = await axios.delete(`/api/links/${key}`);
+  let res = await axios.delete(`/api/links/${key}`, { validateStatus: isJsendStatus });
   return res.data;
 }
 
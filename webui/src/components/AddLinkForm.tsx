@@ -99,6 +99,11 @@ export default function AddLinkForm() {
         </ul>
       </form>
 
+      {addLinkRes && addLinkRes.status === "success" && addLinkRes.data.existing && (
+        <p class="text-gray-300">
+          This URL was already shortened as {addLinkRes.data.key}
+        </p>
+      )}
       {addLinkRes && addLinkRes.status === "success" && (
         <CopyButton
           text={`${import.meta.env.VITE_SERVER_URL}${addLinkRes.data.key}`}
@@ -31,10 +31,10 @@ export default function ManageLinks() {
     <>
       <div class="h-full max-w-2xl w-full flex m-2 px-3 flex-col gap-2 overflow-y-scroll overflow-x-hidden">
         {links?.status === "success" ? (
-          links.data.length === 0 ? (
+          links.data.items.length === 0 ? (
             <div class="text-center text-lg">No links found</div>
           ) : (
-            links.data.map((entry) => (
+            links.data.items.map((entry) => (
               <LinkRow entry={entry} onDelete={() => openDeleteDialog(entry)} />
             ))
           )